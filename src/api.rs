@@ -5,10 +5,13 @@ use std::collections::HashSet;
 use std::convert::TryFrom;
 #[cfg(feature = "extra_counter")]
 use std::ops::AddAssign;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use actix_web::fs::NamedFile;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
 use actix_web::http::Method;
-use actix_web::HttpMessage;
+use actix_web::{HttpMessage, HttpResponse, Responder};
 use chrono::{DateTime, Utc};
 use futures::{Future, IntoFuture, Stream};
 use serde::Deserialize;
@@ -17,13 +20,13 @@ use serde::Serialize;
 
 use blockp_core::api::backends::actix::{FutureResponse, HttpRequest, ResourceHandler};
 use blockp_core::api::{ServiceApiBackend, ServiceApiBuilder};
-use blockp_core::crypto::Hash;
+use blockp_core::crypto::{Hash, PublicKey};
 
 use crate::control;
 use crate::data::conditions::CheckKey;
 use crate::data::contract::ContractId;
 #[cfg(feature = "internal_api")]
-use crate::data::contract::ContractStatus;
+use crate::data::contract::{ContractStatus, TerminationReason};
 use crate::data::cost::Cost;
 use crate::data::lot::LotStatus;
 #[cfg(feature = "internal_api")]
@@ -36,22 +39,30 @@ use crate::data::payment::{Calculation, PaymentDetail};
 #[cfg(feature = "internal_api")]
 use crate::data::strings::verify_node_name;
 use crate::dto::{
-    CalculationInfo, CheckInfo, ConditionsInfo, HashInfo, LotInfo, MemberInfo, ObjectIdentityDto,
-    PaymentDetailsInfo, SignInfo,
+    CalculationInfo, CheckInfo, ConditionsInfo, HashInfo, LotInfo, MemberInfo, MemberProfileInfo,
+    ObjectIdentityDto, ParticipantKeyInfo, PaymentDetailsInfo, SignInfo,
 };
 use crate::error::{Error, FutureResponseError};
 use crate::response::IntoResponse;
 #[cfg(feature = "extra_counter")]
 use crate::schema::Schema;
-use crate::upload::handle_multipart_item;
+#[cfg(feature = "internal_api")]
+use crate::store::REPLICA_SIZE_LIMIT;
+use crate::store::{FileContent, StoredFile};
+use crate::upload::load_multipart;
+#[cfg(feature = "internal_api")]
+use crate::util::get_from_map_nullable_str;
 use crate::util::get_from_multipart_map;
-use crate::util::{get_attachment_from_map, get_str_from_map};
+use crate::util::{get_attachment_from_map, get_str_from_map, is_stored_by_request};
 #[cfg(feature = "internal_api")]
 use crate::util::{get_attachment_nullable_from_map, get_string_from_map};
 use crate::util::{get_from_map, get_from_map_nullable};
 
 pub struct OwnershipApi;
 
+/// Contracts with a deadline within this number of days are listed by default
+const DEFAULT_DEADLINE_WITHIN_DAYS: u64 = 7;
+
 #[derive(Deserialize, Debug, Eq, PartialEq)]
 struct JustObjectIdentity {
     object: ObjectIdentityDto,
@@ -73,6 +84,47 @@ struct AddParticipant {
     node: String,
 }
 
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct RegisterMember {
+    member: MemberInfo,
+    profile: MemberProfileInfo,
+    #[serde(default)]
+    keys: Vec<ParticipantKeyInfo>,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct UpdateMemberProfile {
+    member: MemberInfo,
+    profile: MemberProfileInfo,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct AddParticipantKey {
+    member: MemberInfo,
+    key: ParticipantKeyInfo,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct RemoveParticipantKey {
+    member: MemberInfo,
+    key: PublicKey,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct RotateParticipantKey {
+    member: MemberInfo,
+    old_key: PublicKey,
+    new_key: PublicKey,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct LinkMember {
+    member: MemberInfo,
+    successor: MemberInfo,
+    #[serde(default)]
+    migrate: bool,
+}
+
 #[derive(Deserialize, Debug, Eq, PartialEq)]
 struct AcquireLot {
     requestor: MemberInfo,
@@ -265,6 +317,18 @@ struct MemberToken {
     oid: String,
 }
 
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct ContractDeadline {
+    status: String,
+    days: u64,
+}
+
+#[derive(Deserialize, Debug, Eq, PartialEq)]
+struct RevokePowerOfAttorney {
+    requestor: MemberInfo,
+    attorney_tx_hash: HashInfo,
+}
+
 #[derive(Deserialize, Debug, Eq, PartialEq)]
 struct ConfirmCreate {
     requestor: MemberInfo,
@@ -391,17 +455,28 @@ pub struct ContractsCounter {
     pub stats: HashMap<String, u64>,
 }
 
+/// Streams the stored file in chunks, decrypted data is already in memory
+fn file_response(req: &HttpRequest, stored: StoredFile) -> Result<HttpResponse, Error> {
+    match stored.content {
+        FileContent::File(file) => NamedFile::from_file(file, PathBuf::from(stored.name))?
+            .respond_to(req)
+            .map_err(Error::from),
+        FileContent::Data(data) => Ok(HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .set(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(stored.name)],
+            })
+            .body(data)),
+    }
+}
+
 impl OwnershipApi {
     #[cfg(feature = "internal_api")]
     fn add_object(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
-        req.multipart()
-            .map(handle_multipart_item)
-            .flatten()
-            .from_err()
-            .collect()
-            .and_then(move |params: Vec<(String, Vec<u8>)>| {
-                let params = params.into_iter().collect();
+        load_multipart(req.multipart())
+            .and_then(move |params: HashMap<String, Vec<u8>>| {
                 let object = get_from_multipart_map(&params, "object")?;
                 let data = get_str_from_map(&params, "data")?;
                 let ownership_str = get_str_from_map(&params, "ownership")?;
@@ -415,13 +490,8 @@ impl OwnershipApi {
     #[cfg(feature = "internal_api")]
     fn update_object(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
-        req.multipart()
-            .map(handle_multipart_item)
-            .flatten()
-            .from_err()
-            .collect()
-            .and_then(move |params: Vec<(String, Vec<u8>)>| {
-                let params = params.into_iter().collect();
+        load_multipart(req.multipart())
+            .and_then(move |params: HashMap<String, Vec<u8>>| {
                 let object = get_from_multipart_map(&params, "object")?;
                 let data = get_str_from_map(&params, "data")?;
                 let ownership_str = get_str_from_map(&params, "ownership")?;
@@ -651,6 +721,18 @@ impl OwnershipApi {
             .into_response()
     }
 
+    #[cfg(feature = "internal_api")]
+    fn generate_contract_documents(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+
+        req.json()
+            .from_err()
+            .and_then(|params: DraftContract| {
+                control::generate_contract_documents(state, &params.contract_tx_hash)
+            })
+            .into_response()
+    }
+
     #[cfg(feature = "internal_api")]
     fn draft_contract(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
@@ -781,19 +863,77 @@ impl OwnershipApi {
             .into_response()
     }
 
+    fn get_file_data(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        let requestor = get_from_map(&query, "requestor");
+
+        get_from_map(&query, "doc_tx_hash")
+            .and_then(|doc_tx_hash: HashInfo| {
+                control::get_file_data(state, Some(&requestor?), &doc_tx_hash)
+            })
+            .and_then(|stored| file_response(&req, stored))
+            .map(|response| -> FutureResponse { Box::new(Ok(response).into_future()) })
+            .unwrap_or_else(Error::error_future_response)
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_file_data_private(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+
+        get_from_map(&query, "doc_tx_hash")
+            .and_then(|doc_tx_hash: HashInfo| control::get_file_data(state, None, &doc_tx_hash))
+            .and_then(|stored| file_response(&req, stored))
+            .map(|response| -> FutureResponse { Box::new(Ok(response).into_future()) })
+            .unwrap_or_else(Error::error_future_response)
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_file_replica(req: HttpRequest) -> FutureResponse {
+        get_from_map(&req.query(), "hash")
+            .and_then(|hash: HashInfo| control::get_file_replica(&hash))
+            .and_then(|stored| file_response(&req, stored))
+            .map(|response| -> FutureResponse { Box::new(Ok(response).into_future()) })
+            .unwrap_or_else(Error::error_future_response)
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn put_file_replica(req: HttpRequest) -> FutureResponse {
+        let query = req.query();
+        let params = get_from_map(&query, "hash").and_then(|hash: HashInfo| {
+            let node = get_string_from_map(&query, "node")?;
+            let signature = get_string_from_map(&query, "signature")?;
+            Ok((hash, node, signature))
+        });
+        match params {
+            Ok((hash, node, signature)) => req
+                .body()
+                .limit(REPLICA_SIZE_LIMIT)
+                .from_err()
+                .and_then(move |data| control::put_file_replica(&hash, &node, &signature, &data))
+                .into_response(),
+            Err(e) => e.error_future_response(),
+        }
+    }
+
     fn attach_contract_other_file(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
-        req.multipart()
-            .map(handle_multipart_item)
-            .flatten()
-            .from_err()
-            .collect()
-            .and_then(|params: Vec<(String, Vec<u8>)>| {
-                let params = params.into_iter().collect();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
                 let requestor = get_from_multipart_map(&params, "requestor")?;
                 let contract_tx_hash = get_str_from_map(&params, "contract_tx_hash")?.parse()?;
                 let attachment = get_attachment_from_map(&params)?;
-                control::attach_contract_other_file(state, requestor, &contract_tx_hash, attachment)
+                let stored = is_stored_by_request(&params);
+                let encrypt = get_from_map_nullable(&params, "encrypt")?.unwrap_or(false);
+                control::attach_contract_other_file(
+                    state,
+                    requestor,
+                    &contract_tx_hash,
+                    attachment,
+                    stored,
+                    encrypt,
+                )
             })
             .into_response()
     }
@@ -801,20 +941,70 @@ impl OwnershipApi {
     #[cfg(feature = "internal_api")]
     fn attach_contract_main_file(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
-        req.multipart()
-            .map(handle_multipart_item)
-            .flatten()
-            .from_err()
-            .collect()
-            .and_then(|params: Vec<(String, Vec<u8>)>| {
-                let params = params.into_iter().collect();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
                 let contract_tx_hash = get_str_from_map(&params, "contract_tx_hash")?.parse()?;
                 let attachment = get_attachment_from_map(&params)?;
-                control::attach_contract_main_file(state, &contract_tx_hash, attachment)
+                let stored = is_stored_by_request(&params);
+                let encrypt = get_from_map_nullable(&params, "encrypt")?.unwrap_or(false);
+                control::attach_contract_main_file(
+                    state,
+                    &contract_tx_hash,
+                    attachment,
+                    stored,
+                    encrypt,
+                )
             })
             .into_response()
     }
 
+    fn replace_contract_file(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
+                let requestor = get_from_multipart_map(&params, "requestor")?;
+                let contract_tx_hash = get_str_from_map(&params, "contract_tx_hash")?.parse()?;
+                let doc_tx_hash = get_str_from_map(&params, "doc_tx_hash")?.parse()?;
+                let attachment = get_attachment_from_map(&params)?;
+                let stored = is_stored_by_request(&params);
+                let encrypt = get_from_map_nullable(&params, "encrypt")?.unwrap_or(false);
+                control::replace_contract_file(
+                    state,
+                    requestor,
+                    &contract_tx_hash,
+                    &doc_tx_hash,
+                    attachment,
+                    stored,
+                    encrypt,
+                )
+            })
+            .into_response()
+    }
+
+    fn get_file_versions(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        let requestor = get_from_map(&query, "requestor");
+
+        get_from_map(&query, "doc_tx_hash")
+            .into_future()
+            .and_then(|doc_tx_hash: HashInfo| {
+                control::get_file_versions(state, Some(&requestor?), &doc_tx_hash)
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_file_versions_private(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+
+        get_from_map(&query, "doc_tx_hash")
+            .into_future()
+            .and_then(|doc_tx_hash: HashInfo| control::get_file_versions(state, None, &doc_tx_hash))
+            .into_response()
+    }
+
     fn delete_contract_files(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
 
@@ -882,13 +1072,8 @@ impl OwnershipApi {
     #[cfg(feature = "internal_api")]
     fn approve_contract(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
-        req.multipart()
-            .map(handle_multipart_item)
-            .flatten()
-            .from_err()
-            .collect()
-            .and_then(|params: Vec<(String, Vec<u8>)>| {
-                let params = params.into_iter().collect();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
                 let contract_tx_hash = get_str_from_map(&params, "contract_tx_hash")?.parse()?;
                 let attachment = get_attachment_nullable_from_map(&params)?;
                 control::approve_contract(state, &contract_tx_hash, attachment)
@@ -899,13 +1084,8 @@ impl OwnershipApi {
     #[cfg(feature = "internal_api")]
     fn reject_contract(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
-        req.multipart()
-            .map(handle_multipart_item)
-            .flatten()
-            .from_err()
-            .collect()
-            .and_then(|params: Vec<(String, Vec<u8>)>| {
-                let params = params.into_iter().collect();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
                 let contract_tx_hash = get_str_from_map(&params, "contract_tx_hash")?.parse()?;
                 let reason = get_str_from_map(&params, "reason")?;
                 let attachment = get_attachment_nullable_from_map(&params)?;
@@ -914,6 +1094,20 @@ impl OwnershipApi {
             .into_response()
     }
 
+    #[cfg(feature = "internal_api")]
+    fn terminate_contract(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
+                let contract_tx_hash = get_str_from_map(&params, "contract_tx_hash")?.parse()?;
+                let reason: TerminationReason = get_from_multipart_map(&params, "reason")?;
+                let comment = get_from_map_nullable_str(&params, "comment")?.unwrap_or_default();
+                let attachment = get_attachment_nullable_from_map(&params)?;
+                control::terminate_contract(state, &contract_tx_hash, reason, comment, attachment)
+            })
+            .into_response()
+    }
+
     fn get_contract_checks(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
         let query = req.query();
@@ -954,6 +1148,135 @@ impl OwnershipApi {
             .into_response()
     }
 
+    #[cfg(feature = "internal_api")]
+    fn register_member(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(move |json: RegisterMember| {
+                control::register_member(
+                    state,
+                    json.member.into(),
+                    json.profile.into(),
+                    json.keys.into_iter().map(Into::into).collect(),
+                )
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn update_member_profile(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(move |json: UpdateMemberProfile| {
+                control::update_member_profile(state, json.member.into(), json.profile.into())
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_member_registry(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "member")
+            .into_future()
+            .and_then(|member| control::get_member_registry(state, &member))
+            .into_response()
+    }
+
+    fn get_member_summary(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "requestor")
+            .into_future()
+            .and_then(|requestor| control::get_member_summary(state, &requestor))
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_member_summary_private(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "member")
+            .into_future()
+            .and_then(|member| control::get_member_summary(state, &member))
+            .into_response()
+    }
+
+    fn get_member_inbox(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "requestor")
+            .into_future()
+            .and_then(|requestor| control::get_member_inbox(state, &requestor))
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_member_inbox_private(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "member")
+            .into_future()
+            .and_then(|member| control::get_member_inbox(state, &member))
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn add_participant_key(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(move |json: AddParticipantKey| {
+                control::add_participant_key(state, json.member.into(), json.key.into())
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn remove_participant_key(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(move |json: RemoveParticipantKey| {
+                control::remove_participant_key(state, json.member.into(), &json.key)
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn rotate_participant_key(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(move |json: RotateParticipantKey| {
+                control::rotate_participant_key(
+                    state,
+                    json.member.into(),
+                    &json.old_key,
+                    &json.new_key,
+                )
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn link_member(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(move |json: LinkMember| {
+                control::link_member(
+                    state,
+                    json.member.into(),
+                    json.successor.into(),
+                    json.migrate,
+                )
+            })
+            .into_response()
+    }
+
     fn update_contract(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
         req.json()
@@ -1106,6 +1429,68 @@ impl OwnershipApi {
             .into_response()
     }
 
+    fn get_contract_revisions(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        let requestor = get_from_map(&query, "requestor");
+        let contract_tx_hash = get_from_map(&query, "contract_tx_hash");
+        if query.contains_key("number") {
+            get_from_map(&query, "number")
+                .into_future()
+                .and_then(|number| {
+                    control::get_contract_revision(state, requestor?, &contract_tx_hash?, number)
+                })
+                .into_response()
+        } else {
+            contract_tx_hash
+                .into_future()
+                .and_then(|tx_hash| control::get_contract_revisions(state, requestor?, &tx_hash))
+                .into_response()
+        }
+    }
+
+    fn get_contract_history(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        let requestor = get_from_map(&query, "requestor");
+        get_from_map(&query, "contract_tx_hash")
+            .into_future()
+            .and_then(|tx_hash| control::get_contract_history(state, requestor?, &tx_hash))
+            .into_response()
+    }
+
+    fn get_contract_transitions(req: HttpRequest) -> FutureResponse {
+        let query = req.query();
+        get_from_map_nullable(&query, "format")
+            .into_future()
+            .and_then(control::get_contract_transitions)
+            .into_response()
+    }
+
+    fn get_contract_deadlines(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        let within = get_from_map_nullable(&query, "within");
+        get_from_map(&query, "requestor")
+            .into_future()
+            .and_then(|requestor| {
+                let within = within?.unwrap_or(DEFAULT_DEADLINE_WITHIN_DAYS);
+                control::get_contract_deadlines(state, requestor, within)
+            })
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn set_contract_deadline(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(|params: ContractDeadline| {
+                control::set_contract_deadline(state, &params.status, params.days)
+            })
+            .into_response()
+    }
+
     fn get_contract_conditions(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
         let query = req.query();
@@ -1275,6 +1660,64 @@ impl OwnershipApi {
             .into_response()
     }
 
+    fn add_power_of_attorney(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        load_multipart(req.multipart())
+            .and_then(|params: HashMap<String, Vec<u8>>| {
+                let requestor = get_from_multipart_map(&params, "requestor")?;
+                let representative = get_str_from_map(&params, "representative")?;
+                let powers = get_from_multipart_map(&params, "powers")?;
+                let valid_from = get_str_from_map(&params, "valid_from")?.parse()?;
+                let valid_until = get_str_from_map(&params, "valid_until")?.parse()?;
+                let document = get_attachment_from_map(&params)?;
+                let stored = is_stored_by_request(&params);
+                control::add_power_of_attorney(
+                    state,
+                    requestor,
+                    representative,
+                    powers,
+                    valid_from,
+                    valid_until,
+                    document,
+                    stored,
+                )
+            })
+            .into_response()
+    }
+
+    fn revoke_power_of_attorney(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        req.json()
+            .from_err()
+            .and_then(|json: RevokePowerOfAttorney| {
+                control::revoke_power_of_attorney(
+                    state,
+                    json.requestor.into(),
+                    &json.attorney_tx_hash,
+                )
+            })
+            .into_response()
+    }
+
+    fn get_powers_of_attorney(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "requestor")
+            .into_future()
+            .and_then(|requestor| control::get_powers_of_attorney(state, &requestor))
+            .into_response()
+    }
+
+    #[cfg(feature = "internal_api")]
+    fn get_powers_of_attorney_private(req: HttpRequest) -> FutureResponse {
+        let state = req.state().clone();
+        let query = req.query();
+        get_from_map(&query, "member")
+            .into_future()
+            .and_then(|member| control::get_powers_of_attorney(state, &member))
+            .into_response()
+    }
+
     #[cfg(feature = "extra_counter")]
     fn objects_counter(req: HttpRequest) -> FutureResponse {
         let state = req.state().clone();
@@ -1382,6 +1825,22 @@ impl OwnershipApi {
                 ResourceHandler::new("v1/contracts/conditions")
                     .with(Method::GET, Arc::new(OwnershipApi::get_contract_conditions)),
             )
+            .resource(
+                ResourceHandler::new("v1/contracts/revisions")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_contract_revisions)),
+            )
+            .resource(
+                ResourceHandler::new("v1/contracts/history")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_contract_history)),
+            )
+            .resource(ResourceHandler::new("v1/contracts/transitions").with(
+                Method::GET,
+                Arc::new(OwnershipApi::get_contract_transitions),
+            ))
+            .resource(
+                ResourceHandler::new("v1/contracts/deadlines")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_contract_deadlines)),
+            )
             .resource(
                 ResourceHandler::new("v1/contracts/acquire_lot")
                     .with(Method::POST, Arc::new(OwnershipApi::acquire_lot)),
@@ -1418,6 +1877,18 @@ impl OwnershipApi {
                         Arc::new(OwnershipApi::delete_contract_files),
                     ),
             )
+            .resource(
+                ResourceHandler::new("v1/contracts/documents/data")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_file_data)),
+            )
+            .resource(
+                ResourceHandler::new("v1/contracts/documents/replace")
+                    .with(Method::POST, Arc::new(OwnershipApi::replace_contract_file)),
+            )
+            .resource(
+                ResourceHandler::new("v1/contracts/documents/versions")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_file_versions)),
+            )
             .resource(
                 ResourceHandler::new("v1/contracts/confirm")
                     .with(Method::POST, Arc::new(OwnershipApi::confirm_contract)),
@@ -1447,6 +1918,25 @@ impl OwnershipApi {
             .resource(
                 ResourceHandler::new("v1/esia/token")
                     .with(Method::PUT, Arc::new(OwnershipApi::put_member_token)),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/powers_of_attorney")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_powers_of_attorney))
+                    .with(Method::POST, Arc::new(OwnershipApi::add_power_of_attorney)),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/powers_of_attorney/revoke").with(
+                    Method::POST,
+                    Arc::new(OwnershipApi::revoke_power_of_attorney),
+                ),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/summary")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_member_summary)),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/inbox")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_member_inbox)),
             );
 
         #[cfg(feature = "internal_api")]
@@ -1461,6 +1951,36 @@ impl OwnershipApi {
                 ResourceHandler::new("v1/members")
                     .with(Method::POST, Arc::new(OwnershipApi::add_participant)),
             )
+            .resource(
+                ResourceHandler::new("v1/members/registry")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_member_registry))
+                    .with(Method::POST, Arc::new(OwnershipApi::register_member))
+                    .with(Method::PUT, Arc::new(OwnershipApi::update_member_profile)),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/keys")
+                    .with(Method::POST, Arc::new(OwnershipApi::add_participant_key))
+                    .with(
+                        Method::DELETE,
+                        Arc::new(OwnershipApi::remove_participant_key),
+                    ),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/keys/rotate")
+                    .with(Method::POST, Arc::new(OwnershipApi::rotate_participant_key)),
+            )
+            .resource(
+                ResourceHandler::new("v1/members/link")
+                    .with(Method::POST, Arc::new(OwnershipApi::link_member)),
+            )
+            .resource(ResourceHandler::new("v1/members/summary").with(
+                Method::GET,
+                Arc::new(OwnershipApi::get_member_summary_private),
+            ))
+            .resource(ResourceHandler::new("v1/members/inbox").with(
+                Method::GET,
+                Arc::new(OwnershipApi::get_member_inbox_private),
+            ))
             .resource(
                 ResourceHandler::new("v1/objects")
                     .with(Method::POST, Arc::new(OwnershipApi::add_object))
@@ -1494,6 +2014,27 @@ impl OwnershipApi {
                         Arc::new(OwnershipApi::attach_contract_main_file),
                     ),
             )
+            .resource(
+                ResourceHandler::new("v1/contracts/documents/data")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_file_data_private)),
+            )
+            .resource(
+                ResourceHandler::new("v1/contracts/documents/versions").with(
+                    Method::GET,
+                    Arc::new(OwnershipApi::get_file_versions_private),
+                ),
+            )
+            .resource(
+                ResourceHandler::new("v1/files")
+                    .with(Method::GET, Arc::new(OwnershipApi::get_file_replica))
+                    .with(Method::PUT, Arc::new(OwnershipApi::put_file_replica)),
+            )
+            .resource(
+                ResourceHandler::new("v1/contracts/generate_documents").with(
+                    Method::POST,
+                    Arc::new(OwnershipApi::generate_contract_documents),
+                ),
+            )
             .resource(
                 ResourceHandler::new("v1/contracts/draft")
                     .with(Method::POST, Arc::new(OwnershipApi::draft_contract)),
@@ -1506,6 +2047,14 @@ impl OwnershipApi {
                 ResourceHandler::new("v1/contracts/reject")
                     .with(Method::POST, Arc::new(OwnershipApi::reject_contract)),
             )
+            .resource(
+                ResourceHandler::new("v1/contracts/terminate")
+                    .with(Method::POST, Arc::new(OwnershipApi::terminate_contract)),
+            )
+            .resource(
+                ResourceHandler::new("v1/contracts/deadline")
+                    .with(Method::POST, Arc::new(OwnershipApi::set_contract_deadline)),
+            )
             .resource(
                 ResourceHandler::new("v1/contracts/register")
                     .with(Method::POST, Arc::new(OwnershipApi::register_contract)),
@@ -1553,7 +2102,11 @@ impl OwnershipApi {
             .resource(
                 ResourceHandler::new("v1/esia/token")
                     .with(Method::GET, Arc::new(OwnershipApi::get_member_token)),
-            );
+            )
+            .resource(ResourceHandler::new("v1/members/powers_of_attorney").with(
+                Method::GET,
+                Arc::new(OwnershipApi::get_powers_of_attorney_private),
+            ));
 
         #[cfg(feature = "extra_counter")]
         builder
@@ -2206,6 +2759,22 @@ mod test {
         assert_eq!(true_val, val);
     }
 
+    #[test]
+    fn post_v1_contracts_deadline() {
+        let json = r#"
+        {
+            "status": "confirmed",
+            "days": 30
+        }"#;
+        let true_val = ContractDeadline {
+            status: "confirmed".to_string(),
+            days: 30,
+        };
+
+        let val = serde_json::from_str(json).unwrap();
+        assert_eq!(true_val, val);
+    }
+
     #[test]
     fn get_v1_contract_conditions() {
         let json = r#"
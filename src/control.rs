@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::{TryFrom, TryInto};
 
 use chrono::{DateTime, Utc};
@@ -9,27 +9,45 @@ use blockp_core::crypto::{Hash, PublicKey};
 use blockp_core::node::{TransactionSend, TransactionSendPrivate};
 use blockp_core::storage::Snapshot;
 
-use crate::data::attachment::{Attachment, AttachmentType, DocumentId, Sign};
+use crate::credentials::{CredentialStore, EsiaCredentials};
+use crate::data::attachment::{
+    Attachment, AttachmentMetadata, AttachmentType, DocumentId, ScanVerdict, Sign,
+};
+use crate::data::attorney::{PowerOfAttorney, Powers};
 #[cfg(feature = "internal_api")]
 use crate::data::conditions::Check;
 use crate::data::conditions::{CheckKey, Conditions, ContractType};
 #[cfg(feature = "internal_api")]
 use crate::data::contract::Action;
-use crate::data::contract::{ContractId, ContractStatus, CorrespondenceContacts};
+use crate::data::contract::{
+    transitions_dot, ContractId, ContractStatus, CorrespondenceContacts, PendingAction, TRANSITIONS,
+};
+#[cfg(feature = "internal_api")]
+use crate::data::contract::{TerminationReason, EXPIRABLE_STATUSES, MAX_DEADLINE_DAYS};
 use crate::data::cost::Cost;
 use crate::data::lot::{LotId, LotStatus, SaleType};
-use crate::data::member::MemberIdentity;
+use crate::data::member::{Authority, EsiaAttestation, MemberIdentity};
 use crate::data::object::ObjectIdentity;
 #[cfg(feature = "internal_api")]
 use crate::data::ownership::{Ownership, OwnershipUnstructured};
-#[cfg(feature = "internal_api")]
 use crate::data::payment::PaymentStatus;
 #[cfg(feature = "internal_api")]
 use crate::data::payment::{Calculation, PaymentDetail};
+#[cfg(feature = "internal_api")]
+use crate::data::registry::{MemberProfile, ParticipantKey};
 use crate::dto::*;
+use crate::encryption;
 use crate::error::{Error, Result};
+use crate::identity::{self, ActingPerson};
+use crate::scan::{self, ScanResult};
 use crate::schema::Schema;
-use crate::transactions::{self, get_private_tx, get_transaction, OwnershipTransactions};
+use crate::store::{FileContent, FileStore, StoredFile};
+#[cfg(feature = "internal_api")]
+use crate::template;
+use crate::transactions::{self, get_private_tx, OwnershipTransactions};
+
+/// Number of blocks the ESIA attestation of a member stays valid for
+const ESIA_ATTESTATION_BLOCKS: u64 = 100;
 
 #[cfg(feature = "internal_api")]
 fn split_ownership(
@@ -140,92 +158,144 @@ pub fn get_file<'a>(
     doc_tx_hash: &'a Hash,
 ) -> Result<AttachmentDto> {
     let schema = Schema::new(state.snapshot());
+    let (attachment, contract_signs) = find_attachment(&schema, requestor, doc_tx_hash)?;
+    attachment_dto(&schema, doc_tx_hash, attachment, contract_signs)
+}
 
-    let tx = get_private_tx(&schema, doc_tx_hash)?;
-    let (contract_tx, attachment) = match &tx {
-        OwnershipTransactions::AttachContractMainFile(doc_tx) => {
-            let mut attachment = doc_tx.file();
-            if doc_tx.file().sign().is_none() {
-                if let Some(contract_sign) = schema.get_sign_contract_tx(doc_tx_hash) {
-                    let buyer_sign = contract_sign
-                        .buyer_sign_tx_hash()
-                        .and_then(|sign_info| {
-                            get_private_tx(&schema, &sign_info.sign_tx_hash()).ok()
-                        })
-                        .and_then(|sign_tx| match sign_tx {
-                            OwnershipTransactions::SignContract(tx_body) => Some(tx_body),
-                            _ => None,
-                        })
-                        .zip(doc_tx.file().metadata().file_type().try_into().ok())
-                        .and_then(|(tx_body, attachment_type)| match attachment_type {
-                            AttachmentType::Deed => Some(tx_body.deed_sign()),
-                            AttachmentType::Application => Some(tx_body.application_sign()),
-                            _ => None,
-                        });
-
-                    let seller_sign = contract_sign
-                        .seller_sign_tx_hash()
-                        .and_then(|sign_info| {
-                            get_private_tx(&schema, &sign_info.sign_tx_hash()).ok()
-                        })
-                        .and_then(|sign_tx| match sign_tx {
-                            OwnershipTransactions::SignContract(tx_body) => Some(tx_body),
-                            _ => None,
-                        })
-                        .zip(doc_tx.file().metadata().file_type().try_into().ok())
-                        .and_then(|(tx_body, attachment_type)| match attachment_type {
-                            AttachmentType::Deed => Some(tx_body.deed_sign()),
-                            AttachmentType::Application => Some(tx_body.application_sign()),
-                            _ => None,
-                        });
-                    attachment = Attachment::new(
-                        attachment.metadata(),
-                        attachment.data(),
-                        buyer_sign.clone(),
-                    );
-                    let mut attachment_dto: AttachmentDto = attachment.try_into()?;
-                    attachment_dto.buyer_sign = buyer_sign.map(Into::into);
-                    attachment_dto.seller_sign = seller_sign.map(Into::into);
-                    return Ok(attachment_dto);
-                } else {
-                    if let Some(sign_tx_hash) = schema.deprecated_get_sign_contract_tx(doc_tx_hash)
-                    {
-                        let sign = get_private_tx(&schema, &sign_tx_hash)
-                            .ok()
-                            .and_then(|sign_tx| match sign_tx {
-                                OwnershipTransactions::SignContract(tx_body) => Some(tx_body),
-                                _ => None,
-                            })
-                            .zip(doc_tx.file().metadata().file_type().try_into().ok())
-                            .and_then(|(tx_body, attachment_type)| match attachment_type {
-                                AttachmentType::Deed => Some(tx_body.deed_sign()),
-                                AttachmentType::Application => Some(tx_body.application_sign()),
-                                _ => None,
-                            });
-                        attachment = Attachment::new(attachment.metadata(), attachment.data(), sign)
-                    };
-                }
-            };
+/// Versions of the document from the first one
+pub fn get_file_versions<'a>(
+    state: State,
+    requestor: Option<&'a MemberIdentity>,
+    doc_tx_hash: &'a Hash,
+) -> Result<Vec<DocumentVersionInfo>> {
+    let schema = Schema::new(state.snapshot());
+    find_attachment(&schema, requestor, doc_tx_hash)?;
+    let history = schema.contract_file_history(doc_tx_hash);
+    let count = history.len();
+    history
+        .into_iter()
+        .enumerate()
+        .map(|(i, version_tx_hash)| {
+            let (attachment, contract_signs) =
+                find_attachment(&schema, requestor, &version_tx_hash)?;
+            Ok(DocumentVersionInfo::new(
+                i as u64 + 1,
+                version_tx_hash,
+                i + 1 == count,
+                attachment_dto(&schema, &version_tx_hash, attachment, contract_signs)?,
+            ))
+        })
+        .collect()
+}
 
-            (doc_tx.contract_tx_hash(), attachment)
-        }
-        OwnershipTransactions::AttachContractOtherFile(doc_tx) => {
-            (doc_tx.contract_tx_hash(), doc_tx.file())
-        }
-        OwnershipTransactions::ApproveContract(doc_tx) => {
-            let attachment = doc_tx
-                .attachment()
-                .ok_or_else(|| Error::no_attachment(doc_tx_hash))?;
-            (doc_tx.contract_tx_hash(), attachment)
-        }
-        OwnershipTransactions::RejectContract(doc_tx) => {
-            let attachment = doc_tx
-                .attachment()
-                .ok_or_else(|| Error::no_attachment(doc_tx_hash))?;
-            (doc_tx.contract_tx_hash(), attachment)
+fn attachment_dto<T>(
+    schema: &Schema<T>,
+    doc_tx_hash: &DocumentId,
+    attachment: Attachment,
+    contract_signs: Option<(Option<Sign>, Option<Sign>)>,
+) -> Result<AttachmentDto>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let mut attachment_dto: AttachmentDto = attachment.try_into()?;
+    attachment_dto.metadata.scan_verdict = schema.scan_verdict(doc_tx_hash);
+    attachment_dto.encrypted = schema.is_document_encrypted(doc_tx_hash);
+    if let Some((buyer_sign, seller_sign)) = contract_signs {
+        attachment_dto.buyer_sign = buyer_sign.map(Into::into);
+        attachment_dto.seller_sign = seller_sign.map(Into::into);
+    }
+    Ok(attachment_dto)
+}
+
+/// Opens the file data in the store for streaming.
+/// Encrypted files are decrypted only for the private API (without `requestor`)
+pub fn get_file_data<'a>(
+    state: State,
+    requestor: Option<&'a MemberIdentity>,
+    doc_tx_hash: &'a Hash,
+) -> Result<StoredFile> {
+    let schema = Schema::new(state.snapshot());
+    let (attachment, _) = find_attachment(&schema, requestor, doc_tx_hash)?;
+    let store = FileStore::from_env()?;
+    let content = match deprecated_file_data(&schema, doc_tx_hash) {
+        Some(data) => FileContent::Data(data),
+        None if !schema.is_document_encrypted(doc_tx_hash) => {
+            FileContent::File(store.open(attachment.hash())?)
         }
-        _ => Error::unexpected_tx_type(doc_tx_hash).ok()?,
+        None if requestor.is_some() => Error::encrypted_document(doc_tx_hash).ok()?,
+        None => FileContent::Data(decrypt_attachment(&state, &attachment)?),
     };
+    Ok(StoredFile {
+        content,
+        name: attachment.metadata().name().to_owned(),
+    })
+}
+
+#[cfg(feature = "internal_api")]
+pub fn get_file_replica(hash: &Hash) -> Result<StoredFile> {
+    let file = FileStore::from_env()?.open_local(hash)?;
+    Ok(StoredFile {
+        content: FileContent::File(file),
+        name: hash.to_hex(),
+    })
+}
+
+#[cfg(feature = "internal_api")]
+pub fn put_file_replica(hash: &Hash, node: &str, signature: &str, data: &[u8]) -> Result<()> {
+    let store = FileStore::from_env()?;
+    store.verify_replica_sender(hash, node, signature)?;
+    store.put_replica(hash, data)
+}
+
+/// Finds the attachment of the document transaction and the buyer and seller signs
+/// of the signed contract document
+fn find_attachment<T>(
+    schema: &Schema<T>,
+    requestor: Option<&MemberIdentity>,
+    doc_tx_hash: &Hash,
+) -> Result<(Attachment, Option<(Option<Sign>, Option<Sign>)>)>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let tx = get_private_tx(schema, doc_tx_hash)?;
+    let (contract_tx, attachment) = tx
+        .attachment()
+        .ok_or_else(|| Error::no_attachment(doc_tx_hash))?;
+    // Contract documents are signed by separate signing transactions
+    let is_main_file = match tx {
+        OwnershipTransactions::AttachContractMainFile(_)
+        | OwnershipTransactions::DeprecatedAttachContractMainFile(_) => true,
+        _ => false,
+    };
+    let (attachment, contract_signs) = if is_main_file {
+        with_contract_signs(schema, doc_tx_hash, attachment)
+    } else {
+        (attachment, None)
+    };
+    check_document_access(schema, requestor, &contract_tx)?;
+    Ok((attachment, contract_signs))
+}
+
+/// Data of the document attached before the `FileStore`, it is kept in the transaction
+fn deprecated_file_data<T>(schema: &Schema<T>, doc_tx_hash: &Hash) -> Option<Vec<u8>>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    get_private_tx(schema, doc_tx_hash)
+        .ok()
+        .and_then(|tx| tx.deprecated_attachment())
+        .map(|(_, attachment)| attachment.data().to_vec())
+}
+
+/// Checks that the requestor participates in the contract of the document
+fn check_document_access<T>(
+    schema: &Schema<T>,
+    requestor: Option<&MemberIdentity>,
+    contract_tx: &ContractId,
+) -> Result<()>
+where
+    T: AsRef<dyn Snapshot>,
+{
     let contract = schema
         .contracts()
         .get(contract_tx)
@@ -233,7 +303,45 @@ pub fn get_file<'a>(
     if requestor.is_some() && !contract.is_member(requestor.unwrap()) {
         Err(Error::no_permissions())?
     };
-    Ok(attachment.try_into()?)
+    Ok(())
+}
+
+/// Adds the buyer sign to the signed contract document, returns the buyer and seller signs
+fn with_contract_signs<T>(
+    schema: &Schema<T>,
+    doc_tx_hash: &Hash,
+    mut attachment: Attachment,
+) -> (Attachment, Option<(Option<Sign>, Option<Sign>)>)
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let mut contract_signs = None;
+    if attachment.sign().is_none() {
+        if let Some(contract_sign) = schema.get_sign_contract_tx(doc_tx_hash) {
+            let buyer_sign = contract_sign
+                .buyer_sign_tx_hash()
+                .and_then(|sign_info| get_private_tx(schema, &sign_info.sign_tx_hash()).ok())
+                .zip(attachment.metadata().file_type().try_into().ok())
+                .and_then(|(sign_tx, file_type)| sign_tx.document_sign(&file_type));
+
+            let seller_sign = contract_sign
+                .seller_sign_tx_hash()
+                .and_then(|sign_info| get_private_tx(schema, &sign_info.sign_tx_hash()).ok())
+                .zip(attachment.metadata().file_type().try_into().ok())
+                .and_then(|(sign_tx, file_type)| sign_tx.document_sign(&file_type));
+            attachment = attachment.with_sign(buyer_sign.clone());
+            contract_signs = Some((buyer_sign, seller_sign));
+        } else {
+            if let Some(sign_tx_hash) = schema.deprecated_get_sign_contract_tx(doc_tx_hash) {
+                let sign = get_private_tx(schema, &sign_tx_hash)
+                    .ok()
+                    .zip(attachment.metadata().file_type().try_into().ok())
+                    .and_then(|(sign_tx, file_type)| sign_tx.document_sign(&file_type));
+                attachment = attachment.with_sign(sign)
+            };
+        }
+    };
+    (attachment, contract_signs)
 }
 
 // pub fn add_attachment_sign(
@@ -279,13 +387,15 @@ pub fn open_lot(
     };
     let lot = info.into_lot(requestor.clone(), sale_type)?;
     lot.verify()?;
-    let tx = transactions::open_lot(requestor, lot, conditions.into(), cert);
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+    let tx = transactions::open_lot(requestor, attestation, lot, conditions.into(), cert);
     send(state, tx)
 }
 
 pub fn close_lot(state: State, requestor: MemberIdentity, lot_id: &LotId) -> Result<TxHash> {
     let cert = state.blockchain().certificate();
-    let tx = transactions::close_lot(requestor, &lot_id, cert);
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+    let tx = transactions::close_lot(requestor, attestation, &lot_id, cert);
     send(state, tx)
 }
 
@@ -303,7 +413,9 @@ pub fn extend_lot_period(
     new_expiration_date: DateTime<Utc>,
 ) -> Result<TxHash> {
     let cert = state.blockchain().certificate();
-    let tx = transactions::extend_lot_period(requestor, lot_id, new_expiration_date, cert);
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+    let tx =
+        transactions::extend_lot_period(requestor, attestation, lot_id, new_expiration_date, cert);
     send(state, tx)
 }
 
@@ -343,10 +455,10 @@ pub fn acquire_lot(state: State, requestor: MemberIdentity, lot_id: &LotId) -> R
     let schema = Schema::new(state.snapshot());
     let cert = state.blockchain().certificate();
 
-    let lot_tx = match get_transaction(&schema, lot_id) {
-        Ok(OwnershipTransactions::OpenLot(tx)) => Ok(tx),
-        _ => Error::no_transaction(lot_id).ok(),
-    }?;
+    let lot = schema
+        .lots()
+        .get(lot_id)
+        .ok_or_else(|| Error::no_lot(lot_id))?;
 
     if schema
         .lot_states()
@@ -357,21 +469,11 @@ pub fn acquire_lot(state: State, requestor: MemberIdentity, lot_id: &LotId) -> R
         Error::lot_is_undefined(lot_id).ok()?;
     }
 
-    let owner = lot_tx.requestor();
-    let share = [&owner, &requestor]
-        .iter()
-        .flat_map(|p| {
-            schema
-                .participants(&p.id())
-                .into_iter()
-                .collect::<Vec<String>>()
-        })
-        .map(|s| {
-            PublicKey::from_slice(s.as_bytes()).ok_or_else(|| Error::bad_stored_member(s.as_str()))
-        })
-        .collect::<Result<Vec<PublicKey>>>()?;
+    let share = schema.members_share(&[&lot.seller(), &requestor]);
 
-    let tx = transactions::acquire_lot(requestor, &lot_id, share, cert);
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+
+    let tx = transactions::acquire_lot(requestor, attestation, &lot_id, share, cert);
     send_private(state, tx)
 }
 
@@ -392,21 +494,12 @@ pub fn purchase_offer(
     };
 
     let schema = Schema::new(state.snapshot());
-    let share = [&rightholder, &buyer]
-        .iter()
-        .flat_map(|p| {
-            schema
-                .participants(&p.id())
-                .into_iter()
-                .collect::<Vec<String>>()
-        })
-        .map(|s| {
-            PublicKey::from_slice(s.as_bytes()).ok_or_else(|| Error::bad_stored_member(s.as_str()))
-        })
-        .collect::<Result<Vec<PublicKey>>>()?;
+    let share = schema.members_share(&[&rightholder, &buyer]);
     let cert = state.blockchain().certificate();
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
     let tx = transactions::purchase_offer(
         requestor,
+        attestation,
         buyer,
         rightholder,
         price,
@@ -419,7 +512,8 @@ pub fn purchase_offer(
 
 pub fn add_bid(state: State, member: MemberIdentity, lot_id: &LotId, bid: Cost) -> Result<TxHash> {
     let cert = state.blockchain().certificate();
-    let tx = transactions::add_bid(member, lot_id, bid.into(), cert);
+    let attestation = esia_attestation(&state, &member, Powers::empty())?;
+    let tx = transactions::add_bid(member, attestation, lot_id, bid.into(), cert);
     send_private(state, tx)
 }
 
@@ -595,8 +689,8 @@ pub fn get_object(state: State, object: ObjectIdentity) -> Result<ObjectInformat
         .map(|v| v.into())
         .collect();
     let ownership: Vec<StructuredOwnershipInfo> = schema
-        .rightholders(&object_id)
-        .iter()
+        .object_rights(&object_id)
+        .into_iter()
         .filter_map(|(rightholder, rights)| {
             StructuredOwnershipInfo::from_rights(rights, rightholder).ok()
         })
@@ -629,8 +723,8 @@ pub fn get_objects_pagination(
                     .map(|v| v.into())
                     .collect();
                 let ownership: Vec<StructuredOwnershipInfo> = schema
-                    .rightholders(&object_id)
-                    .iter()
+                    .object_rights(&object_id)
+                    .into_iter()
                     .filter_map(|(rightholder, rights)| {
                         StructuredOwnershipInfo::from_rights(rights, rightholder).ok()
                     })
@@ -656,8 +750,8 @@ pub fn get_objects_pagination(
                     .map(|v| v.into())
                     .collect();
                 let ownership: Vec<StructuredOwnershipInfo> = schema
-                    .rightholders(&object_id)
-                    .iter()
+                    .object_rights(&object_id)
+                    .into_iter()
                     .filter_map(|(rightholder, rights)| {
                         StructuredOwnershipInfo::from_rights(rights, rightholder).ok()
                     })
@@ -735,6 +829,123 @@ pub fn get_contract_status(state: State, contract_tx_hash: &ContractId) -> Resul
     Ok(status)
 }
 
+pub fn get_contract_revisions(
+    state: State,
+    requestor: MemberIdentity,
+    contract_tx_hash: &ContractId,
+) -> Result<Vec<ContractRevisionInfo>> {
+    let schema = Schema::new(state.snapshot());
+    let contract = schema
+        .contracts()
+        .get(contract_tx_hash)
+        .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
+    if !contract.is_member(&requestor) {
+        Error::no_permissions().ok()?
+    }
+
+    let mut previous = None;
+    let mut revisions = Vec::new();
+    for revision in schema.contract_revision_list(contract_tx_hash)? {
+        let height = schema.tx_height(revision.tx_hash());
+        revisions.push(ContractRevisionInfo::new(
+            revision.clone(),
+            previous,
+            height,
+        )?);
+        previous = Some(revision);
+    }
+    Ok(revisions)
+}
+
+pub fn get_contract_revision(
+    state: State,
+    requestor: MemberIdentity,
+    contract_tx_hash: &ContractId,
+    number: u64,
+) -> Result<ContractRevisionInfo> {
+    let schema = Schema::new(state.snapshot());
+    let contract = schema
+        .contracts()
+        .get(contract_tx_hash)
+        .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
+    if !contract.is_member(&requestor) {
+        Error::no_permissions().ok()?
+    }
+
+    let revisions = schema.contract_revision_list(contract_tx_hash)?;
+    let revision = revisions
+        .get(number as usize)
+        .cloned()
+        .ok_or_else(|| Error::no_contract_revision(contract_tx_hash, number))?;
+    let previous = number
+        .checked_sub(1)
+        .and_then(|n| revisions.get(n as usize).cloned());
+    let height = schema.tx_height(revision.tx_hash());
+    ContractRevisionInfo::new(revision, previous, height)
+}
+
+pub fn get_contract_history(
+    state: State,
+    requestor: MemberIdentity,
+    contract_tx_hash: &ContractId,
+) -> Result<Vec<ContractTransitionInfo>> {
+    let schema = Schema::new(state.snapshot());
+    let contract = schema
+        .contracts()
+        .get(contract_tx_hash)
+        .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
+    if !contract.is_member(&requestor) {
+        Error::no_permissions().ok()?
+    }
+
+    schema
+        .contract_history(contract_tx_hash)
+        .iter()
+        .map(ContractTransitionInfo::try_from)
+        .collect()
+}
+
+pub fn get_contract_transitions(format: Option<String>) -> Result<ContractTransitionsDto> {
+    match format.as_ref().map(String::as_str) {
+        None | Some("json") => Ok(ContractTransitionsDto::Table(TRANSITIONS)),
+        Some("dot") => Ok(ContractTransitionsDto::Dot(transitions_dot())),
+        Some(_) => Error::unexpected_param_value("format").ok(),
+    }
+}
+
+pub fn get_contract_deadlines(
+    state: State,
+    requestor: MemberIdentity,
+    within_days: u64,
+) -> Result<Vec<ContractDeadlineInfo>> {
+    let schema = Schema::new(state.snapshot());
+    let limit = Utc::now() + chrono::Duration::days(within_days.min(MAX_DEADLINE_DAYS) as i64);
+
+    let mut deadlines = Vec::new();
+    for (contract_id, _) in schema.member_contracts(&requestor.id()).iter() {
+        let change = match schema.contract_state_changes().get(&contract_id) {
+            Some(change) => change,
+            None => continue,
+        };
+        let since = match change.timestamp() {
+            Some(since) => since,
+            None => continue,
+        };
+        let deadline = match schema.contract_deadline(&contract_id) {
+            Some(deadline) if deadline <= limit => deadline,
+            _ => continue,
+        };
+        deadlines.push(ContractDeadlineInfo {
+            contract_tx_hash: contract_id,
+            status: change.status().to_string(),
+            since,
+            deadline,
+        });
+    }
+    deadlines.sort_by_key(|info| info.deadline);
+    Ok(deadlines)
+}
+
 pub fn get_contract_conditions(
     state: State,
     contract_tx_hash: &ContractId,
@@ -753,21 +964,88 @@ pub fn attach_contract_other_file(
     requestor: MemberIdentity,
     contract_tx_hash: &ContractId,
     attachment: Attachment,
+    stored: bool,
+    encrypt: bool,
 ) -> Result<TxHash> {
     attachment.verify()?;
-    if attachment.metadata().file_type() != AttachmentType::Other as u8 {
-        Error::bad_file_type("file type is not 'other'").ok()?;
+    if !AttachmentType::try_from(attachment.metadata().file_type())?.is_supplementary() {
+        Error::bad_file_type(
+            "file type must be one of: 'other', 'power_of_attorney', 'parent_contract'",
+        )
+        .ok()?;
     }
     let schema = Schema::new(state.snapshot());
     let cert = state.blockchain().certificate();
 
     // share document with seller and buyer
     let share = schema.get_contract_share(contract_tx_hash)?;
+    let scan_verdict = scan_attachment(&attachment, stored)?;
+    let attachment = if encrypt {
+        encrypt_attachment(&state, &schema, attachment, &share)?
+    } else {
+        attachment
+    };
+    replicate(&state, &attachment, &share)?;
+
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
 
     let tx = transactions::attach_contract_other_file(
         requestor,
+        attestation,
+        contract_tx_hash,
+        attachment,
+        scan_verdict,
+        encrypt,
+        share,
+        cert,
+    );
+    send_private(state, tx)
+}
+
+pub fn replace_contract_file(
+    state: State,
+    requestor: MemberIdentity,
+    contract_tx_hash: &ContractId,
+    doc_tx_hash: &DocumentId,
+    attachment: Attachment,
+    stored: bool,
+    encrypt: bool,
+) -> Result<TxHash> {
+    attachment.verify()?;
+    if AttachmentType::try_from(attachment.metadata().file_type())? == AttachmentType::Notification
+    {
+        Error::bad_file_type("notification can't be replaced").ok()?;
+    }
+    let schema = Schema::new(state.snapshot());
+    let cert = state.blockchain().certificate();
+
+    if schema
+        .contract_document(contract_tx_hash, doc_tx_hash)
+        .is_none()
+    {
+        Error::no_attachment(doc_tx_hash).ok()?
+    }
+
+    // share document with seller and buyer
+    let share = schema.get_contract_share(contract_tx_hash)?;
+    let scan_verdict = scan_attachment(&attachment, stored)?;
+    let attachment = if encrypt {
+        encrypt_attachment(&state, &schema, attachment, &share)?
+    } else {
+        attachment
+    };
+    replicate(&state, &attachment, &share)?;
+
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+
+    let tx = transactions::replace_contract_file(
+        requestor,
+        attestation,
         contract_tx_hash,
+        doc_tx_hash,
         attachment,
+        scan_verdict,
+        encrypt,
         share,
         cert,
     );
@@ -779,6 +1057,8 @@ pub fn attach_contract_main_file(
     state: State,
     contract_tx_hash: &ContractId,
     attachment: Attachment,
+    stored: bool,
+    encrypt: bool,
 ) -> Result<TxHash> {
     attachment.verify()?;
     // Deprecated in https://aj.srvdev.ru/browse/FIPSOP-1045
@@ -791,11 +1071,72 @@ pub fn attach_contract_main_file(
 
     // share document with seller and buyer
     let share = schema.get_contract_share(contract_tx_hash)?;
+    let scan_verdict = scan_attachment(&attachment, stored)?;
+    let attachment = if encrypt {
+        encrypt_attachment(&state, &schema, attachment, &share)?
+    } else {
+        attachment
+    };
+    replicate(&state, &attachment, &share)?;
 
-    let tx = transactions::attach_contract_main_file(contract_tx_hash, attachment, share, cert);
+    let tx = transactions::attach_contract_main_file(
+        contract_tx_hash,
+        attachment,
+        scan_verdict,
+        encrypt,
+        &Hash::zero(),
+        share,
+        cert,
+    );
     send_private(state, tx)
 }
 
+#[cfg(feature = "internal_api")]
+pub fn generate_contract_documents(
+    state: State,
+    contract_tx_hash: &ContractId,
+) -> Result<Vec<Hash>> {
+    let schema = Schema::new(state.snapshot());
+    let cert = state.blockchain().certificate();
+    let contract = schema
+        .contracts()
+        .get(contract_tx_hash)
+        .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
+    if !contract.check_modifiable()? {
+        let status = ContractStatus::try_from(contract.state())?;
+        Error::bad_contract_state(status, "generating contract documents").ok()?;
+    }
+
+    // share document with seller and buyer
+    let share = schema.get_contract_share(contract_tx_hash)?;
+
+    let mut tx_hashes = Vec::new();
+    for file_type in vec![AttachmentType::Deed, AttachmentType::Application] {
+        let data = template::render(&schema, contract_tx_hash, &file_type)?;
+        let hash = FileStore::from_env()?.put(&data)?;
+        let metadata = AttachmentMetadata::new(
+            template::file_name(&file_type)?,
+            Some(template::GENERATED_DESCRIPTION.to_owned()),
+            file_type as u8,
+            Utc::now(),
+        );
+        let attachment = Attachment::new(metadata, &hash, None);
+        replicate(&state, &attachment, &share)?;
+        let tx = transactions::attach_contract_main_file(
+            contract_tx_hash,
+            attachment,
+            ScanVerdict::NotScanned,
+            false,
+            &template::version(),
+            share.clone(),
+            cert,
+        );
+        tx_hashes.push(tx.hash());
+        send_private(state.clone(), tx)?;
+    }
+    Ok(tx_hashes)
+}
+
 pub fn delete_contract_files(
     state: State,
     requestor: MemberIdentity,
@@ -807,8 +1148,11 @@ pub fn delete_contract_files(
 
     let share = schema.get_contract_share(contract_tx_hash)?;
 
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+
     let tx = transactions::delete_contract_files(
         requestor,
+        attestation,
         contract_tx_hash,
         doc_tx_hashes,
         share,
@@ -829,8 +1173,11 @@ pub fn confirm_contract(
 
     let share = schema.get_contract_share(contract_tx_hash)?;
 
+    let attestation = esia_attestation(&state, &requestor, Powers::CONFIRM_CONTRACT)?;
+
     let tx = transactions::confirm_contract(
         requestor,
+        attestation,
         contract_tx_hash,
         deed_tx_hash,
         application_tx_hash,
@@ -860,7 +1207,16 @@ pub fn refuse_contract(
 
     let share = schema.get_contract_share(contract_tx_hash)?;
 
-    let tx = transactions::refuse_contract(requestor, contract_tx_hash, reason, share, cert);
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+
+    let tx = transactions::refuse_contract(
+        requestor,
+        attestation,
+        contract_tx_hash,
+        reason,
+        share,
+        cert,
+    );
     send_private(state, tx)
 }
 
@@ -879,9 +1235,12 @@ pub fn update_contract(
     // TODO: Add requestor and rightholder nodes to share
     let share = schema.get_contract_share(contract_tx_hash)?;
 
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+
     let tx = transactions::update_contract(
         contract_tx_hash,
         requestor,
+        attestation,
         price,
         conditions,
         contract_correspondence,
@@ -918,6 +1277,9 @@ pub fn approve_contract(
     let schema = Schema::new(state.snapshot());
     let cert = state.blockchain().certificate();
     let share = schema.get_contract_share(contract_tx_hash)?;
+    if let Some(attachment) = &attachment {
+        replicate(&state, attachment, &share)?;
+    }
 
     let tx = transactions::approve_contract(contract_tx_hash, attachment, share, cert);
     send_private(state, tx)
@@ -950,11 +1312,70 @@ pub fn reject_contract(
     let schema = Schema::new(state.snapshot());
     let cert = state.blockchain().certificate();
     let share = schema.get_contract_share(contract_tx_hash)?;
+    if let Some(attachment) = &attachment {
+        replicate(&state, attachment, &share)?;
+    }
 
     let tx = transactions::reject_contract(contract_tx_hash, reason, attachment, share, cert);
     send_private(state, tx)
 }
 
+#[cfg(feature = "internal_api")]
+pub fn terminate_contract(
+    state: State,
+    contract_tx_hash: &ContractId,
+    reason: TerminationReason,
+    comment: &str,
+    attachment: Option<Attachment>,
+) -> Result<TxHash> {
+    let attachment = attachment
+        .map(|attach| {
+            attach
+                .metadata()
+                .file_type()
+                .try_into()
+                .and_then(|file_metadata: AttachmentType| {
+                    if file_metadata != AttachmentType::Notification {
+                        Error::bad_file_type("file type have to be 'notification'").ok()
+                    } else {
+                        Ok(())
+                    }
+                })
+                .and_then(|_| attach.verify())
+                .map(|_| attach)
+        })
+        .transpose()?;
+    let schema = Schema::new(state.snapshot());
+    let cert = state.blockchain().certificate();
+    let share = schema.get_contract_share(contract_tx_hash)?;
+    if let Some(attachment) = &attachment {
+        replicate(&state, attachment, &share)?;
+    }
+
+    let tx = transactions::terminate_contract(
+        contract_tx_hash,
+        reason,
+        comment,
+        attachment,
+        share,
+        cert,
+    );
+    send_private(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn set_contract_deadline(state: State, status: &str, days: u64) -> Result<TxHash> {
+    if !EXPIRABLE_STATUSES.contains(&status) {
+        Error::unexpected_param_value("status").ok()?
+    }
+    if days > MAX_DEADLINE_DAYS {
+        Error::unexpected_param_value("days").ok()?
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::set_contract_deadline(status, days, cert);
+    send(state, tx)
+}
+
 #[cfg(feature = "internal_api")]
 pub fn add_participant(state: State, user: MemberIdentity, node_name: &str) -> Result<TxHash> {
     let cert = state.blockchain().certificate();
@@ -962,6 +1383,295 @@ pub fn add_participant(state: State, user: MemberIdentity, node_name: &str) -> R
     send(state, tx)
 }
 
+#[cfg(feature = "internal_api")]
+pub fn register_member(
+    state: State,
+    member: MemberIdentity,
+    profile: MemberProfile,
+    keys: Vec<ParticipantKey>,
+) -> Result<TxHash> {
+    profile.verify()?;
+    for (i, key) in keys.iter().enumerate() {
+        key.verify()?;
+        if keys[..i].iter().any(|k| k.key() == key.key()) {
+            Error::participant_key_exists(&member, key.key()).ok()?
+        }
+    }
+    let schema = Schema::new(state.snapshot());
+    if schema.member_profiles().contains(&member) {
+        Error::member_already_registered(&member).ok()?
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::register_member(member, profile, keys, cert);
+    send(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn update_member_profile(
+    state: State,
+    member: MemberIdentity,
+    profile: MemberProfile,
+) -> Result<TxHash> {
+    profile.verify()?;
+    let schema = Schema::new(state.snapshot());
+    if !schema.member_profiles().contains(&member) {
+        Error::no_member_profile(&member).ok()?
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::update_member_profile(member, profile, cert);
+    send(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn add_participant_key(
+    state: State,
+    member: MemberIdentity,
+    key: ParticipantKey,
+) -> Result<TxHash> {
+    key.verify()?;
+    let schema = Schema::new(state.snapshot());
+    if schema
+        .participant_keys(&member.id())
+        .iter()
+        .any(|k| k.key() == key.key())
+    {
+        Error::participant_key_exists(&member, key.key()).ok()?
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::add_participant_key(member, key, cert);
+    send(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn remove_participant_key(
+    state: State,
+    member: MemberIdentity,
+    key: &PublicKey,
+) -> Result<TxHash> {
+    let schema = Schema::new(state.snapshot());
+    if !schema
+        .participant_keys(&member.id())
+        .iter()
+        .any(|k| k.key() == key)
+    {
+        Error::no_participant_key(&member, key).ok()?
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::remove_participant_key(member, key, cert);
+    send(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn rotate_participant_key(
+    state: State,
+    member: MemberIdentity,
+    old_key: &PublicKey,
+    new_key: &PublicKey,
+) -> Result<TxHash> {
+    let schema = Schema::new(state.snapshot());
+    let keys = schema.participant_keys(&member.id());
+    if !keys.iter().any(|k| k.key() == old_key) {
+        Error::no_participant_key(&member, old_key).ok()?
+    }
+    if keys.iter().any(|k| k.key() == new_key) {
+        Error::participant_key_exists(&member, new_key).ok()?
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::rotate_participant_key(member, old_key, new_key, cert);
+    send(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn link_member(
+    state: State,
+    member: MemberIdentity,
+    successor: MemberIdentity,
+    migrate: bool,
+) -> Result<TxHash> {
+    if member == successor {
+        Error::unexpected_param_value("successor").ok()?
+    }
+    let schema = Schema::new(state.snapshot());
+    if let Some(link) = schema.member_links().get(&successor) {
+        Error::member_linked(&successor, &link.successor()).ok()?
+    }
+    if let Some(link) = schema.member_links().get(&member) {
+        if link.successor() != successor || link.migrated() || !migrate {
+            Error::member_linked(&member, &link.successor()).ok()?
+        }
+    }
+    if migrate {
+        share_with_successor(&state, &schema, &member, &successor)?;
+    }
+    let cert = state.blockchain().certificate();
+    let tx = transactions::link_member(member, successor, migrate, cert);
+    send(state, tx)
+}
+
+#[cfg(feature = "internal_api")]
+pub fn get_member_registry(state: State, member: &MemberIdentity) -> Result<MemberRegistryDto> {
+    let schema = Schema::new(state.snapshot());
+    if !member.is_valid() {
+        Error::bad_member_format(&member.to_string()).ok()?
+    }
+    let profile = schema.member_profiles().get(member).map(Into::into);
+    let keys = schema
+        .participant_keys(&member.id())
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<ParticipantKeyInfo>>>()?;
+    let link = schema.member_links().get(member).map(Into::into);
+    Ok(MemberRegistryDto::new(
+        member.clone().into(),
+        profile,
+        keys,
+        link,
+    ))
+}
+
+pub fn get_member_summary(state: State, member: &MemberIdentity) -> Result<MemberSummaryDto> {
+    if !member.is_valid() {
+        Error::bad_member_format(&member.to_string()).ok()?
+    }
+    let schema = Schema::new(state.snapshot());
+    let member_id = member.id();
+
+    let objects = schema
+        .ownership(&member_id)
+        .iter()
+        .flat_map(|(_, object)| {
+            schema
+                .member_rights(member, &object.id())
+                .into_iter()
+                .map(move |rights| {
+                    let ownership = StructuredOwnershipInfo::from_rights(rights, member.clone());
+                    ownership.map(|ownership| MemberObjectInfo {
+                        object: object.clone().into(),
+                        ownership,
+                    })
+                })
+        })
+        .collect::<Result<Vec<MemberObjectInfo>>>()?;
+
+    let lots = schema
+        .member_lots(&member_id)
+        .iter()
+        .map(|(_, lot_id)| lot_id)
+        .filter(|lot_id| {
+            schema.lot_states().get(*lot_id).map_or(false, |lot_state| {
+                lot_state.is_new() || lot_state.is_verified()
+            })
+        })
+        .map(|lot_id| {
+            get_lot_info_with_objects(state.clone(), &lot_id)
+                .map(|lot| HashWrapperDto::into_hash_wrapper(lot, lot_id))
+        })
+        .collect::<Result<Vec<HashWrapperDto<LotInfoWithObjects>>>>()?;
+
+    let mut contracts = BTreeMap::new();
+    for contract_id in schema.member_contracts(&member_id).keys() {
+        let contract = schema
+            .contracts()
+            .get(&contract_id)
+            .ok_or_else(|| Error::no_contract(&contract_id))?;
+        let status = ContractStatus::try_from(contract.state())?;
+        contracts
+            .entry(status.to_string())
+            .or_insert_with(Vec::new)
+            .push(contract_id);
+    }
+    let (pending_actions, outstanding_payments) = awaited_actions(&schema, member)?;
+
+    Ok(MemberSummaryDto {
+        member: member.clone().into(),
+        objects,
+        lots,
+        contracts,
+        pending_actions,
+        outstanding_payments,
+    })
+}
+
+/// Actions awaited from the member, the earliest deadlines first
+pub fn get_member_inbox(state: State, member: &MemberIdentity) -> Result<MemberInboxDto> {
+    if !member.is_valid() {
+        Error::bad_member_format(&member.to_string()).ok()?
+    }
+    let schema = Schema::new(state.snapshot());
+    let (mut actions, payments) = awaited_actions(&schema, member)?;
+    actions.sort_by_key(|action| (action.deadline.is_none(), action.deadline));
+    Ok(MemberInboxDto {
+        member: member.clone().into(),
+        counts: InboxCounts::new(&actions),
+        actions,
+        payments,
+    })
+}
+
+/// Actions the unfinished contracts of the member wait for and their unpaid payments
+fn awaited_actions<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    member: &MemberIdentity,
+) -> Result<(Vec<PendingActionInfo>, Vec<OutstandingPaymentInfo>)> {
+    let mut actions = Vec::new();
+    let mut payments = Vec::new();
+    for contract_id in schema.member_contracts(&member.id()).keys() {
+        let contract = schema
+            .contracts()
+            .get(&contract_id)
+            .ok_or_else(|| Error::no_contract(&contract_id))?;
+        if contract.is_finished()? {
+            continue;
+        }
+        let party = match contract.party(member) {
+            Some(party) => party,
+            None => continue,
+        };
+        let status = ContractStatus::try_from(contract.state())?;
+        let deadline = schema.contract_deadline(&contract_id);
+        let action_info = |action| PendingActionInfo {
+            contract_tx_hash: contract_id,
+            action,
+            party,
+            status: status.to_string(),
+            deadline,
+        };
+
+        if let Some(action) = status.awaited_action(&party) {
+            actions.push(action_info(action));
+        }
+        // Fees are paid by the party which requested the calculation, both parties are
+        // reminded of the calculations nobody requested
+        let pays = schema
+            .contract_payers()
+            .get(&contract_id)
+            .map_or(true, |payer| contract.party(&payer) == Some(party));
+        if !pays {
+            continue;
+        }
+        // Calculations without payment details can't be paid yet
+        let unpaid = schema
+            .get_contract_payment_details(&contract_id)
+            .into_iter()
+            .filter(|payment| {
+                !payment.payment_detail().is_empty()
+                    && payment.status() == PaymentStatus::NotPaid as u8
+            })
+            .map(|payment| {
+                payment.try_into().map(|payment| OutstandingPaymentInfo {
+                    contract_tx_hash: contract_id,
+                    payment,
+                })
+            })
+            .collect::<Result<Vec<OutstandingPaymentInfo>>>()?;
+        if !unpaid.is_empty() {
+            actions.push(action_info(PendingAction::Pay));
+        }
+        payments.extend(unpaid);
+    }
+    Ok((actions, payments))
+}
+
 #[cfg(feature = "internal_api")]
 pub fn register_contract(state: State, contract_tx_hash: &ContractId) -> Result<TxHash> {
     let schema = Schema::new(state.snapshot());
@@ -992,12 +1702,30 @@ pub fn sign_contract(
     let schema = Schema::new(state.snapshot());
     let cert = state.blockchain().certificate();
 
+    // Every node checks the signatures against the document hashes the transaction carries
+    let deed = schema
+        .contract_deed(contract_tx_hash)
+        .ok_or_else(|| Error::deed_file_not_found(contract_tx_hash))?;
+    let application = schema
+        .contract_application(contract_tx_hash)
+        .ok_or_else(|| Error::deed_file_not_found(contract_tx_hash))?;
+    let mut hashes = Vec::new();
+    for (document, sign) in &[(deed, &deed_sign), (application, &application_sign)] {
+        let attachment = schema.get_attachment(document.tx_hash())?;
+        sign.verify_hash(attachment.hash())?;
+        hashes.push(*attachment.hash());
+    }
+
     let share = schema.get_contract_share(contract_tx_hash)?;
+    let attestation = esia_attestation(&state, &requestor, Powers::SIGN_CONTRACT)?;
     let tx = transactions::sign_contract(
         requestor,
+        attestation,
         contract_tx_hash,
         deed_sign,
         application_sign,
+        &hashes[0],
+        &hashes[1],
         share,
         cert,
     );
@@ -1068,6 +1796,16 @@ pub fn contract_reference_number(
     send_private(state, tx)
 }
 
+fn document_dto<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    tx_hash: DocumentId,
+    metadata: AttachmentMetadata,
+) -> Result<AttachmentMetadataWithHashDto> {
+    let mut metadata: AttachmentMetadataDto = metadata.try_into()?;
+    metadata.scan_verdict = schema.scan_verdict(&tx_hash);
+    Ok(AttachmentMetadataWithHashDto::new(tx_hash, metadata))
+}
+
 #[inline]
 pub fn get_contract_documents<T: AsRef<dyn Snapshot>>(
     schema: &Schema<T>,
@@ -1075,27 +1813,21 @@ pub fn get_contract_documents<T: AsRef<dyn Snapshot>>(
 ) -> Result<ContractDocuments> {
     let deed = schema
         .contract_deed(contract_tx_hash)
-        .map(TryInto::try_into)
+        .map(|v| document_dto(schema, *v.tx_hash(), v.metadata()))
         .transpose()?;
     let application = schema
         .contract_application(contract_tx_hash)
-        .map(TryInto::try_into)
+        .map(|v| document_dto(schema, *v.tx_hash(), v.metadata()))
         .transpose()?;
     let stored_docs = schema
         .contract_files(contract_tx_hash)
         .iter()
-        .map(|(k, v)| {
-            v.try_into()
-                .map(|v| AttachmentMetadataWithHashDto::new(k, v))
-        })
+        .map(|(k, v)| document_dto(schema, k, v))
         .collect::<Result<Vec<AttachmentMetadataWithHashDto>>>()?;
     let notifications = schema
         .contract_notifications(contract_tx_hash)
         .iter()
-        .map(|(k, v)| {
-            v.try_into()
-                .map(|v| AttachmentMetadataWithHashDto::new(k, v))
-        })
+        .map(|(k, v)| document_dto(schema, k, v))
         .collect::<Result<Vec<AttachmentMetadataWithHashDto>>>()?;
     Ok(ContractDocuments {
         deed_file: deed,
@@ -1140,6 +1872,12 @@ pub fn get_contract(state: State, contract_tx_hash: &ContractId) -> Result<Contr
         is_undefined: contract.is_undefined(),
         contract_correspondence: contacts.contract_correspondence(),
         objects_correspondence: contacts.objects_correspondence(),
+        refuse_reason: schema.contract_refuse_reasons().get(contract_tx_hash),
+        unrestored_rights: schema
+            .contract_unrestored_rights(contract_tx_hash)
+            .iter()
+            .map(Into::into)
+            .collect(),
     };
     Ok(contract_info)
 }
@@ -1181,7 +1919,9 @@ pub fn tax_request(
 
     let share = schema.get_contract_share(contract_tx_hash)?;
 
-    let tx = transactions::tax_request(requestor, contract_tx_hash, share, cert);
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+
+    let tx = transactions::tax_request(requestor, attestation, contract_tx_hash, share, cert);
     send_private(state, tx)
 }
 
@@ -1279,10 +2019,101 @@ pub fn put_member_token(
     oid: &str,
 ) -> Result<TxHash> {
     let cert = state.blockchain().certificate();
-    let tx = transactions::member_token(member, token, oid, cert);
+    let credentials = EsiaCredentials::new(token, oid);
+    if identity::acting_person(&member, &credentials)?.is_none() {
+        Error::esia_invalid_member(&member).ok()?
+    }
+    CredentialStore::from_env()?.put(&member, &credentials)?;
+    let tx = transactions::member_token(
+        member,
+        &credentials.fingerprint(),
+        credentials.expires_at(),
+        cert,
+    );
     send_private(state, tx)
 }
 
+pub fn add_power_of_attorney(
+    state: State,
+    requestor: MemberIdentity,
+    representative: &str,
+    powers: Powers,
+    valid_from: DateTime<Utc>,
+    valid_until: DateTime<Utc>,
+    document: Attachment,
+    stored: bool,
+) -> Result<TxHash> {
+    document.verify()?;
+    let cert = state.blockchain().certificate();
+    let scan_verdict = scan_attachment(&document, stored)?;
+    let power_of_attorney = PowerOfAttorney::new(
+        requestor.clone(),
+        representative,
+        powers.bits(),
+        valid_from,
+        valid_until,
+        document,
+        false,
+    );
+    power_of_attorney.verify()?;
+
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+    if attestation.authority() != Authority::Role as u8 {
+        Error::no_permissions().ok()?
+    }
+    let tx = transactions::add_power_of_attorney(
+        requestor,
+        attestation,
+        power_of_attorney,
+        scan_verdict,
+        cert,
+    );
+    send(state, tx)
+}
+
+pub fn revoke_power_of_attorney(
+    state: State,
+    requestor: MemberIdentity,
+    attorney_tx_hash: &Hash,
+) -> Result<TxHash> {
+    let schema = Schema::new(state.snapshot());
+    let cert = state.blockchain().certificate();
+    if !schema.powers_of_attorney().contains(attorney_tx_hash) {
+        Error::no_power_of_attorney_tx(attorney_tx_hash).ok()?
+    }
+    if !schema.member_has_power_of_attorney(&requestor.id(), attorney_tx_hash) {
+        Error::no_permissions().ok()?
+    }
+
+    let attestation = esia_attestation(&state, &requestor, Powers::empty())?;
+    if attestation.authority() != Authority::Role as u8 {
+        Error::no_permissions().ok()?
+    }
+    let tx = transactions::revoke_power_of_attorney(requestor, attestation, attorney_tx_hash, cert);
+    send(state, tx)
+}
+
+pub fn get_powers_of_attorney(
+    state: State,
+    member: &MemberIdentity,
+) -> Result<Vec<PowerOfAttorneyDto>> {
+    let schema = Schema::new(state.snapshot());
+    if !member.is_valid() {
+        Error::bad_member_format(&member.to_string()).ok()?
+    }
+    let powers_of_attorney = schema.powers_of_attorney();
+    schema
+        .member_powers_of_attorney(&member.id())
+        .iter()
+        .filter_map(|tx_hash| {
+            powers_of_attorney.get(&tx_hash).map(|power_of_attorney| {
+                (tx_hash, power_of_attorney, schema.scan_verdict(&tx_hash))
+            })
+        })
+        .map(TryInto::try_into)
+        .collect()
+}
+
 #[cfg(feature = "internal_api")]
 pub fn get_member_token(state: State, member: &MemberIdentity) -> Result<MemberEsiaTokenDto> {
     let schema = Schema::new(state.snapshot());
@@ -1340,7 +2171,15 @@ pub fn post_confirm_create(
 
     let share = schema.get_contract_share(contract_tx_hash)?;
 
-    let tx = transactions::contract_confirm_create(requestor_id, contract_tx_hash, share, cert);
+    let attestation = esia_attestation(&state, &requestor_id, Powers::empty())?;
+
+    let tx = transactions::contract_confirm_create(
+        requestor_id,
+        attestation,
+        contract_tx_hash,
+        share,
+        cert,
+    );
     send_private(state, tx)
 }
 
@@ -1363,7 +2202,15 @@ pub fn post_unconfirm_create(
 
     let share = schema.get_contract_share(contract_tx_hash)?;
 
-    let tx = transactions::contract_unconfirm_create(member_id, contract_tx_hash, share, cert);
+    let attestation = esia_attestation(&state, &member_id, Powers::empty())?;
+
+    let tx = transactions::contract_unconfirm_create(
+        member_id,
+        attestation,
+        contract_tx_hash,
+        share,
+        cert,
+    );
     send_private(state, tx)
 }
 
@@ -1395,6 +2242,179 @@ fn send(state: State, tx: Box<dyn Transaction>) -> Result<TxHash> {
         .map_err(|e| Error::unable_to_send_msg(&e.to_string()))
 }
 
+/// Validates the member in ESIA with the registered token and attests it for the transaction.
+/// A representative must hold a valid power of attorney for the `powers` of the operation
+fn esia_attestation(
+    state: &State,
+    member: &MemberIdentity,
+    powers: Powers,
+) -> Result<EsiaAttestation> {
+    let schema = Schema::new(state.snapshot());
+    let token = schema
+        .member_token(member)
+        .ok_or_else(|| Error::no_member_token())?;
+    let credentials = CredentialStore::from_env()?.get(member, &token)?;
+    let acting = identity::acting_person(member, &credentials)?
+        .ok_or_else(|| Error::esia_invalid_member(member))?;
+    let roles = identity::acting_roles();
+    let authority = acting_authority(&schema, member, &acting, &roles, powers, Utc::now())?;
+    Ok(EsiaAttestation::issue(
+        member.clone(),
+        &acting.snils,
+        authority,
+        token.fingerprint(),
+        schema.next_height() + ESIA_ATTESTATION_BLOCKS,
+        state.public_key(),
+        state.secret_key(),
+    ))
+}
+
+/// Authority the person acts for the member with: employees with one of the acting `roles`
+/// act by their role, others must hold a power of attorney for the `powers` of the operation
+fn acting_authority<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    member: &MemberIdentity,
+    acting: &ActingPerson,
+    roles: &str,
+    powers: Powers,
+    now: DateTime<Utc>,
+) -> Result<Authority> {
+    match &acting.role {
+        None => Ok(Authority::Person),
+        Some(role) if role.may_act(roles)? => Ok(Authority::Role),
+        Some(_) if schema.representative_may(&member.id(), &acting.snils, powers, Some(now)) => {
+            Ok(Authority::PowerOfAttorney)
+        }
+        Some(_) => Error::no_power_of_attorney(member, &acting.snils).ok(),
+    }
+}
+
+/// Pushes the attachment data to the nodes the transaction is shared with
+fn replicate(state: &State, attachment: &Attachment, share: &[PublicKey]) -> Result<()> {
+    FileStore::from_env()?.replicate(
+        attachment.hash(),
+        share,
+        state.public_key(),
+        state.secret_key(),
+    )
+}
+
+/// Pushes the documents of the migrated member to the successor's own nodes. Nodes of the
+/// member keep the private data and become the successor's nodes on migration, encrypted
+/// documents stay readable by the nodes they were encrypted for only. ESIA credentials aren't
+/// passed, the successor registers its own token.
+#[cfg(feature = "internal_api")]
+fn share_with_successor<T: AsRef<dyn Snapshot>>(
+    state: &State,
+    schema: &Schema<T>,
+    member: &MemberIdentity,
+    successor: &MemberIdentity,
+) -> Result<()> {
+    let member_nodes = schema.members_share(&[member]);
+    let nodes = schema
+        .members_share(&[successor])
+        .into_iter()
+        .filter(|key| !member_nodes.contains(key))
+        .collect::<Vec<PublicKey>>();
+    if nodes.is_empty() {
+        return Ok(());
+    }
+    let mut documents = Vec::new();
+    for contract_id in schema.member_contracts(&member.id()).keys() {
+        documents.extend(schema.contract_deed(&contract_id).map(|v| *v.tx_hash()));
+        documents.extend(
+            schema
+                .contract_application(&contract_id)
+                .map(|v| *v.tx_hash()),
+        );
+        documents.extend(schema.contract_files(&contract_id).keys());
+    }
+    // Encrypted documents are readable only by the nodes they were encrypted for
+    let mut attachments = documents
+        .iter()
+        .filter(|doc_tx_hash| !schema.is_document_encrypted(doc_tx_hash))
+        .filter_map(|doc_tx_hash| find_attachment(schema, None, doc_tx_hash).ok())
+        .map(|(attachment, _)| attachment)
+        .collect::<Vec<Attachment>>();
+    let powers_of_attorney = schema.powers_of_attorney();
+    attachments.extend(
+        schema
+            .member_powers_of_attorney(&member.id())
+            .iter()
+            .filter_map(|tx_hash| powers_of_attorney.get(&tx_hash))
+            .map(|power_of_attorney| power_of_attorney.document()),
+    );
+
+    for attachment in attachments {
+        if let Err(e) = replicate(state, &attachment, &nodes) {
+            warn!(
+                "Failed to share file {} with the successor: {}",
+                attachment.hash(),
+                e
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Rejects the attachment if the scanner doesn't accept its data.
+/// Rejected data is removed from the store only if it was `stored` by the request,
+/// the data which was there before may belong to other documents.
+fn scan_attachment(attachment: &Attachment, stored: bool) -> Result<ScanVerdict> {
+    let store = FileStore::from_env()?;
+    let path = store.local_path(attachment.hash())?;
+    match scan::scanner().scan(&path)? {
+        ScanResult::Accepted(verdict) => Ok(verdict),
+        ScanResult::Rejected(reason) => {
+            if stored {
+                store.remove(attachment.hash())?;
+            }
+            Error::rejected_file(attachment.metadata().name(), reason).ok()
+        }
+    }
+}
+
+/// Saves the file data encrypted for the node and `share` nodes.
+/// The plain data is removed from the store unless other documents refer to it, the store
+/// is content-addressed. The deed and the application are signed, so they aren't encrypted.
+fn encrypt_attachment<T>(
+    state: &State,
+    schema: &Schema<T>,
+    attachment: Attachment,
+    share: &[PublicKey],
+) -> Result<Attachment>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    match AttachmentType::try_from(attachment.metadata().file_type())? {
+        AttachmentType::Deed | AttachmentType::Application => {
+            Error::bad_file_type("deed and application can't be encrypted").ok()?
+        }
+        _ => (),
+    }
+    let store = FileStore::from_env()?;
+    let data = store.get(attachment.hash())?;
+    let mut nodes = share.to_vec();
+    if !nodes.contains(state.public_key()) {
+        nodes.push(*state.public_key());
+    }
+    let encrypted = encryption::encrypt(&data, &nodes)?;
+    let hash = store.put(&encrypted)?;
+    if schema.data_documents(attachment.hash()).iter().next().is_none() {
+        store.remove(attachment.hash())?;
+    }
+    Ok(Attachment::new(
+        attachment.metadata(),
+        &hash,
+        attachment.sign(),
+    ))
+}
+
+fn decrypt_attachment(state: &State, attachment: &Attachment) -> Result<Vec<u8>> {
+    let data = FileStore::from_env()?.get(attachment.hash())?;
+    encryption::decrypt(&data, state.public_key(), state.secret_key())
+}
+
 fn send_private(state: State, tx: Box<dyn Transaction>) -> Result<TxHash> {
     trace!("SEND PRIVATE TRANS: {:?}", tx);
     state
@@ -1403,3 +2423,67 @@ fn send_private(state: State, tx: Box<dyn Transaction>) -> Result<TxHash> {
         .map(TxHash::from)
         .map_err(|e| Error::unable_to_send_msg(&e.to_string()))
 }
+
+#[cfg(test)]
+mod test {
+    use blockp_core::crypto::hash;
+
+    use crate::fixtures::*;
+    use crate::identity::OrganisationRole;
+
+    use super::*;
+
+    fn employee(chief: bool) -> ActingPerson {
+        ActingPerson {
+            snils: REPRESENTATIVE.to_owned(),
+            role: Some(OrganisationRole {
+                ogrn: "1053600591197".to_owned(),
+                inn: None,
+                full_name: None,
+                chief,
+                admin: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn authority_of_acting_person() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let now = Utc::now();
+        let person = ActingPerson {
+            snils: REPRESENTATIVE.to_owned(),
+            role: None,
+        };
+        let authority = |acting: &ActingPerson, powers| {
+            acting_authority(&schema, &buyer(), acting, "chief", powers, now)
+        };
+        assert_eq!(
+            authority(&person, Powers::empty()).unwrap(),
+            Authority::Person
+        );
+        assert_eq!(
+            authority(&employee(true), Powers::empty()).unwrap(),
+            Authority::Role
+        );
+        authority(&employee(false), Powers::SIGN_CONTRACT).unwrap_err();
+
+        schema.add_power_of_attorney(&hash(b"poa"), power_of_attorney(Powers::SIGN_CONTRACT));
+        let authority = |acting: &ActingPerson, powers| {
+            acting_authority(&schema, &buyer(), acting, "chief", powers, now)
+        };
+        assert_eq!(
+            authority(&employee(false), Powers::SIGN_CONTRACT).unwrap(),
+            Authority::PowerOfAttorney
+        );
+        authority(&employee(false), Powers::CONFIRM_CONTRACT).unwrap_err();
+        authority(&employee(false), Powers::empty()).unwrap_err();
+
+        // Any employee acts by the role by default
+        assert_eq!(
+            acting_authority(&schema, &buyer(), &employee(false), "any", Powers::empty(), now)
+                .unwrap(),
+            Authority::Role
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Node-local storage of ESIA credentials.
+//!
+//! Bearer tokens never get into transactions or the blockchain state, the chain keeps only
+//! their fingerprint and expiry time. Credentials are encrypted with the node credential key.
+//!
+//! The store isn't replicated: a token is usable only on the node it was registered with, so
+//! transactions on behalf of the member must be sent through that node. Other nodes check
+//! the attestation the node adds to the transaction and don't need the token.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sodiumoxide::crypto::secretbox;
+
+use blockp_core::crypto::{self, Hash};
+use blockp_core::encoding::serialize::FromHex;
+
+use crate::data::member::{MemberEsiaToken, MemberIdentity};
+use crate::error::{Error, Result};
+
+/// Directory of the store
+const STORE_DIR_VAR: &str = "FIPS_CREDENTIAL_STORE";
+/// Hex encoded 32-byte key the credentials are encrypted with
+const STORE_KEY_VAR: &str = "FIPS_CREDENTIAL_KEY";
+const DEFAULT_STORE_DIR: &str = "credentials";
+
+/// Lifetime of a token which doesn't carry its expiry time
+const DEFAULT_TOKEN_TTL: i64 = 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct EsiaCredentials {
+    token: String,
+    oid: String,
+}
+
+impl EsiaCredentials {
+    pub fn new(token: &str, oid: &str) -> Self {
+        EsiaCredentials {
+            token: token.to_owned(),
+            oid: oid.to_owned(),
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn oid(&self) -> &str {
+        &self.oid
+    }
+
+    pub fn fingerprint(&self) -> Hash {
+        crypto::hash(format!("{}:{}", self.oid, self.token).as_bytes())
+    }
+
+    /// Takes the `exp` claim of the token if it's a JWT
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        #[derive(Deserialize)]
+        struct Claims {
+            exp: i64,
+        }
+
+        self.token
+            .split('.')
+            .nth(1)
+            .and_then(|payload| base64::decode_config(payload, base64::URL_SAFE_NO_PAD).ok())
+            .and_then(|payload| serde_json::from_slice::<Claims>(&payload).ok())
+            .map(|claims| Utc.timestamp(claims.exp, 0))
+            .unwrap_or_else(|| Utc::now() + Duration::seconds(DEFAULT_TOKEN_TTL))
+    }
+}
+
+pub struct CredentialStore {
+    root: PathBuf,
+    key: secretbox::Key,
+}
+
+impl CredentialStore {
+    pub fn from_env() -> Result<Self> {
+        let root = std::env::var(STORE_DIR_VAR).unwrap_or_else(|_| DEFAULT_STORE_DIR.to_owned());
+        let key = std::env::var(STORE_KEY_VAR)
+            .map_err(|_| Error::bad_credential_store(&format!("{} isn't set", STORE_KEY_VAR)))?;
+        let key = Vec::<u8>::from_hex(key.trim())
+            .ok()
+            .and_then(|key| secretbox::Key::from_slice(&key))
+            .ok_or_else(|| {
+                Error::bad_credential_store(&format!("{} must be 32 hex bytes", STORE_KEY_VAR))
+            })?;
+        Ok(CredentialStore {
+            root: PathBuf::from(root),
+            key,
+        })
+    }
+
+    fn path(&self, member: &MemberIdentity) -> PathBuf {
+        let name = crypto::hash(member.to_string().as_bytes()).to_hex();
+        self.root.join(name)
+    }
+
+    pub fn put(&self, member: &MemberIdentity, credentials: &EsiaCredentials) -> Result<()> {
+        let data = serde_json::to_vec(credentials)?;
+        let nonce = secretbox::gen_nonce();
+        let mut encrypted = nonce.as_ref().to_vec();
+        encrypted.extend(secretbox::seal(&data, &nonce, &self.key));
+        fs::create_dir_all(&self.root)?;
+        fs::write(self.path(member), encrypted)?;
+        Ok(())
+    }
+
+    fn load(&self, member: &MemberIdentity) -> Result<Option<EsiaCredentials>> {
+        let path = self.path(member);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(path)?;
+        if data.len() < secretbox::NONCEBYTES {
+            Error::bad_credential_store("credentials are corrupted").ok()?
+        }
+        let (nonce, encrypted) = data.split_at(secretbox::NONCEBYTES);
+        let nonce = secretbox::Nonce::from_slice(nonce).expect("nonce size is checked");
+        let data = secretbox::open(encrypted, &nonce, &self.key)
+            .map_err(|_| Error::bad_credential_store("unable to decrypt credentials"))?;
+        Ok(Some(serde_json::from_slice(&data)?))
+    }
+
+    /// Returns the credentials matching the token registered for the member on chain
+    pub fn get(&self, member: &MemberIdentity, token: &MemberEsiaToken) -> Result<EsiaCredentials> {
+        let credentials = self.load(member)?.ok_or_else(|| Error::no_member_token())?;
+        if credentials.fingerprint() != *token.fingerprint() {
+            Error::esia_token_mismatch(member).ok()?
+        }
+        if token.expires_at() <= Utc::now() {
+            Error::esia_token_expired(member).ok()?
+        }
+        Ok(credentials)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn store(name: &str) -> CredentialStore {
+        let root =
+            std::env::temp_dir().join(format!("fips-credentials-{}-{}", name, std::process::id()));
+        CredentialStore {
+            root,
+            key: secretbox::gen_key(),
+        }
+    }
+
+    #[test]
+    fn token_expiry_from_jwt() {
+        let payload = base64::encode_config(br#"{"exp":1700000000}"#, base64::URL_SAFE_NO_PAD);
+        let credentials = EsiaCredentials::new(&format!("header.{}.sign", payload), "1000");
+        assert_eq!(credentials.expires_at(), Utc.timestamp(1_700_000_000, 0));
+
+        let credentials = EsiaCredentials::new("opaque", "1000");
+        assert!(credentials.expires_at() > Utc::now());
+    }
+
+    #[test]
+    fn put_and_get() {
+        let store = store("put");
+        let member = MemberIdentity::from_str("ogrn::1053600591197").unwrap();
+        let credentials = EsiaCredentials::new("token", "1000");
+        store.put(&member, &credentials).unwrap();
+
+        let data = fs::read(store.path(&member)).unwrap();
+        assert!(!data.windows(5).any(|w| w == b"token"));
+
+        let token = MemberEsiaToken::new(&credentials.fingerprint(), credentials.expires_at());
+        assert_eq!(store.get(&member, &token).unwrap(), credentials);
+
+        let other = MemberEsiaToken::new(&crypto::hash(b"other"), credentials.expires_at());
+        store.get(&member, &other).unwrap_err();
+
+        let expired = MemberEsiaToken::new(&credentials.fingerprint(), Utc.timestamp(0, 0));
+        store.get(&member, &expired).unwrap_err();
+
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+}
@@ -3,6 +3,7 @@ use std::fmt;
 use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
+use num_enum::TryFromPrimitive;
 use serde::{Deserialize, Serialize};
 
 use blockp_core::crypto::{self, Hash};
@@ -66,10 +67,10 @@ impl Sign {
         Ok(())
     }
 
-    #[allow(unused)]
-    pub fn verify_hash(&self, hash: &Hash) -> error::Result<()> {
-        crypto::verify_detached_sign_with_hash(hash.as_ref(), self.data())
-            .map_err(error::Error::from)
+    pub fn verify_hash(&self, _hash: &Hash) -> error::Result<()> {
+        #[cfg(not(feature = "disable_sign_checks"))]
+        crypto::verify_detached_sign_with_hash(_hash.as_ref(), self.data())?;
+        Ok(())
     }
 }
 
@@ -109,15 +110,37 @@ encoding_struct! {
 }
 
 encoding_struct! {
+    /// File data is kept in the `FileStore` under `hash`, encrypted files are kept
+    /// together with the document keys
     struct Attachment {
+        metadata: AttachmentMetadata,
+        hash: &Hash,
+        sign: Option<Sign>,
+    }
+}
+
+encoding_struct! {
+    /// Attachment with the file data kept in the transaction, documents attached
+    /// before the `FileStore` are read with it
+    struct DeprecatedAttachment {
         metadata: AttachmentMetadata,
         data: &[u8],
-        sign: Option<Sign>
+        sign: Option<Sign>,
+    }
+}
 
+impl DeprecatedAttachment {
+    /// Attachment referring to the data by hash, the data itself is only in the transaction
+    pub fn upgrade(&self) -> Attachment {
+        Attachment::new(self.metadata(), &crypto::hash(self.data()), self.sign())
     }
 }
 
 impl Attachment {
+    pub fn with_sign(&self, sign: Option<Sign>) -> Self {
+        Attachment::new(self.metadata(), self.hash(), sign)
+    }
+
     pub fn verify(&self) -> Result<(), Error> {
         verify_filename(self.metadata().name())?;
         if let Some(data) = self.metadata().description() {
@@ -136,6 +159,33 @@ pub enum AttachmentType {
     Deed = 1,
     Application = 2,
     Notification = 3,
+    PowerOfAttorney = 4,
+    /// Licence or concession the sublicence is granted on the basis of
+    ParentContract = 5,
+}
+
+impl AttachmentType {
+    /// Files stored in the contract documents list besides the main ones
+    pub fn is_supplementary(&self) -> bool {
+        match self {
+            AttachmentType::Other
+            | AttachmentType::PowerOfAttorney
+            | AttachmentType::ParentContract => true,
+            AttachmentType::Deed | AttachmentType::Application | AttachmentType::Notification => {
+                false
+            }
+        }
+    }
+}
+
+impl fmt::Display for AttachmentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            serde_plain::to_string(self).expect("serde_plain::to_string failed on AttachmentType")
+        )
+    }
 }
 
 impl FromStr for AttachmentType {
@@ -155,24 +205,48 @@ impl TryFrom<u8> for AttachmentType {
             1 => AttachmentType::Deed,
             2 => AttachmentType::Application,
             3 => AttachmentType::Notification,
+            4 => AttachmentType::PowerOfAttorney,
+            5 => AttachmentType::ParentContract,
             _ => return Err(Error::unexpected_file_type()),
         };
         Ok(result)
     }
 }
 
+/// Result of the scan of the file data before it was attached
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, TryFromPrimitive)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanVerdict {
+    /// Generated by the node or attached before scanning was introduced
+    NotScanned = 0,
+    /// Passed the built-in format checks, no antivirus is configured
+    FormatChecked = 1,
+    /// Passed the format checks and the antivirus
+    Clean = 2,
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn de_attachment_type() {
-        let strings = vec!["other", "deed", "application", "notification"];
+        let strings = vec![
+            "other",
+            "deed",
+            "application",
+            "notification",
+            "power_of_attorney",
+            "parent_contract",
+        ];
         let att_tpye = vec![
             AttachmentType::Other,
             AttachmentType::Deed,
             AttachmentType::Application,
             AttachmentType::Notification,
+            AttachmentType::PowerOfAttorney,
+            AttachmentType::ParentContract,
         ];
         strings
             .into_iter()
@@ -206,4 +280,14 @@ mod test {
             sign.unwrap_err();
         };
     }
+
+    #[test]
+    fn upgrade_deprecated_attachment() {
+        let metadata =
+            AttachmentMetadata::new("deed.rtf", None, AttachmentType::Deed as u8, Utc::now());
+        let deprecated = DeprecatedAttachment::new(metadata.clone(), b"deed", None);
+        let attachment = deprecated.upgrade();
+        assert_eq!(attachment.hash(), &crypto::hash(b"deed"));
+        assert_eq!(attachment.metadata(), metadata);
+    }
 }
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+
+use crate::data::attachment::{Attachment, AttachmentType};
+use crate::data::member::{MemberIdentity, MemberType};
+use crate::error::{self, Error};
+
+bitflags! {
+    /// Operations the representative may perform on behalf of the member
+    pub struct Powers: u8 {
+        const CONFIRM_CONTRACT = 1;
+        const SIGN_CONTRACT = 2;
+    }
+}
+
+impl Powers {
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.contains(Powers::CONFIRM_CONTRACT) {
+            names.push("confirm_contract");
+        }
+        if self.contains(Powers::SIGN_CONTRACT) {
+            names.push("sign_contract");
+        }
+        names
+    }
+}
+
+impl FromStr for Powers {
+    type Err = Error;
+
+    /// Parses comma separated operation names
+    fn from_str(src: &str) -> error::Result<Self> {
+        src.split(',')
+            .map(str::trim)
+            .try_fold(Powers::empty(), |powers, name| {
+                let power = match name {
+                    "confirm_contract" => Powers::CONFIRM_CONTRACT,
+                    "sign_contract" => Powers::SIGN_CONTRACT,
+                    _ => return Err(Error::bad_powers(name)),
+                };
+                Ok(powers | power)
+            })
+    }
+}
+
+encoding_struct! {
+    /// Machine-readable power of attorney (МЧД) issued by the member to the person with SNILS
+    /// `representative`, the signed document is attached as `document`
+    struct PowerOfAttorney {
+        member: MemberIdentity,
+        representative: &str,
+        powers: u8,
+        valid_from: DateTime<Utc>,
+        valid_until: DateTime<Utc>,
+        document: Attachment,
+        revoked: bool,
+    }
+}
+
+impl PowerOfAttorney {
+    pub fn verify(&self) -> error::Result<()> {
+        if self.member().is_person() {
+            Error::bad_member_format(&self.member().to_string()).ok()?
+        }
+        let representative = MemberIdentity::new(MemberType::Snils as u8, self.representative());
+        if !representative.is_valid() {
+            Error::bad_member_format(&representative.to_string()).ok()?
+        }
+        if self.valid_from() >= self.valid_until() {
+            Error::bad_power_of_attorney("validity period is empty").ok()?
+        }
+        let powers = Powers::from_bits(self.powers())
+            .ok_or_else(|| Error::bad_power_of_attorney("unknown powers"))?;
+        if powers.is_empty() {
+            Error::bad_power_of_attorney("no powers are granted").ok()?
+        }
+        if self.document().metadata().file_type() != AttachmentType::PowerOfAttorney as u8 {
+            Error::bad_file_type("file type must be 'power_of_attorney'").ok()?
+        }
+        self.document().verify()
+    }
+
+    /// Checks that the power of attorney isn't revoked and grants `powers` to the person
+    pub fn allows(&self, representative: &str, powers: Powers) -> bool {
+        !self.revoked()
+            && self.representative() == representative
+            && Powers::from_bits_truncate(self.powers()).contains(powers)
+    }
+
+    pub fn is_valid_at(&self, time: DateTime<Utc>) -> bool {
+        self.valid_from() <= time && time < self.valid_until()
+    }
+
+    pub fn revoke(&self) -> Self {
+        PowerOfAttorney::new(
+            self.member(),
+            self.representative(),
+            self.powers(),
+            self.valid_from(),
+            self.valid_until(),
+            self.document(),
+            true,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_powers() {
+        assert_eq!(
+            Powers::from_str("confirm_contract, sign_contract").unwrap(),
+            Powers::CONFIRM_CONTRACT | Powers::SIGN_CONTRACT
+        );
+        assert_eq!(
+            Powers::from_str("sign_contract").unwrap().names(),
+            vec!["sign_contract"]
+        );
+        Powers::from_str("open_lot").unwrap_err();
+    }
+}
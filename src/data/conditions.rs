@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
 
@@ -8,12 +9,16 @@ use blockp_core::storage::Snapshot;
 
 use crate::error::Error;
 use crate::schema::Schema;
+use crate::template;
 use crate::util::contains_diplicates;
 
+use super::attachment::AttachmentType;
 use super::classifier::Classifier;
+use super::contract::ContractId;
 use super::location::Location;
 use super::member::MemberIdentity;
 use super::object::ObjectIdentity;
+use super::ownership::Rights;
 use super::time::Term;
 
 #[repr(u8)]
@@ -89,10 +94,27 @@ impl Conditions {
             || self.contract_type() == ContractType::SubconcessionAgreement as u8
     }
 
+    pub fn is_sublicense(&self) -> bool {
+        self.contract_type() == ContractType::Sublicense as u8
+            || self.contract_type() == ContractType::SubconcessionAgreement as u8
+    }
+
+    pub fn grants_license(&self) -> bool {
+        self.contract_type() == ContractType::License as u8
+            || self.contract_type() == ContractType::ConcessionAgreement as u8
+            || self.is_sublicense()
+    }
+
     pub fn is_expropriation(&self) -> bool {
         self.contract_type() == ContractType::Expropriation as u8
     }
 
+    /// Whether the approved contract changes the rights registry
+    pub fn transfers_rights(&self) -> bool {
+        self.contract_type() != ContractType::Undefined as u8
+            && self.contract_type() != ContractType::PledgeAgreement as u8
+    }
+
     fn contains_trademark(&self) -> bool {
         self.objects().iter().any(|o| o.object().is_trademark())
     }
@@ -146,6 +168,7 @@ impl Conditions {
 
         let mut term_check = CheckKey::DurationValid.new_check_chain();
         let mut struct_check = CheckKey::NoUnstructuredData.new_check_chain();
+        let mut scope_check = CheckKey::SublicenseScopeValid.new_check_chain();
 
         for obj_ownership in self.objects() {
             let object = obj_ownership.object();
@@ -158,11 +181,13 @@ impl Conditions {
             if !schema.ownership_unstructured(obj_id).is_empty() {
                 struct_check.and(0);
                 term_check.and(0);
+                scope_check.and(0);
             }
             // All ownership information is structured
-            else if let Some(rights) = schema.rights(&seller, obj_id) {
+            else if let Some(rights) = schema.member_rights(&seller, obj_id).into_iter().next() {
                 struct_check.and(1);
                 term_check.and(rights.check_term(&object, obj_ownership.contract_term())?);
+                scope_check.and(obj_ownership.check_scope(&rights));
             }
             // No ownership information found
             else {
@@ -172,10 +197,194 @@ impl Conditions {
 
         results.push(term_check.finalize());
         results.push(struct_check.finalize());
+        if self.is_sublicense() {
+            results.push(scope_check.finalize());
+        }
 
         Ok(results)
     }
 
+    /// Looks for exclusive rights and in-flight contracts overlapping with the offered ones
+    pub fn check_conflicts<T>(
+        &self,
+        schema: &Schema<T>,
+        contract_id: &ContractId,
+        seller: &MemberIdentity,
+        buyer: &MemberIdentity,
+    ) -> Result<Check, Error>
+    where
+        T: AsRef<dyn Snapshot>,
+    {
+        if !self.grants_license() {
+            return Ok(CheckKey::NoExclusiveConflicts.ok());
+        }
+
+        let mut check = CheckKey::NoExclusiveConflicts.new_check_chain();
+        let mut conflicts = Vec::new();
+        for obj_ownership in self.objects() {
+            let obj_id = &obj_ownership.object().id();
+
+            for (holder, rights) in schema.object_rights(obj_id) {
+                if &holder == seller || &holder == buyer || rights.is_owner() {
+                    continue;
+                }
+                if !obj_ownership.exclusive() && !rights.is_exclusive() {
+                    continue;
+                }
+                match obj_ownership.overlaps(&rights.location(), &rights.classifiers()) {
+                    Some(true) => conflicts.push(format!("rightholder {}", holder)),
+                    Some(false) => (),
+                    None => {
+                        check.and(0);
+                    }
+                }
+            }
+
+            for (other_id, _) in schema.object_publications_contract(obj_id).iter() {
+                if &other_id == contract_id {
+                    continue;
+                }
+                let other = match schema.contracts().get(&other_id) {
+                    Some(other) => other,
+                    None => continue,
+                };
+                if other.is_finished()? || !other.conditions().grants_license() {
+                    continue;
+                }
+                let overlap = other
+                    .conditions()
+                    .objects()
+                    .iter()
+                    .filter(|o| &o.object().id() == obj_id)
+                    .filter(|o| obj_ownership.exclusive() || o.exclusive())
+                    .map(|o| obj_ownership.overlaps(&o.location(), &o.classifiers()))
+                    .fold(Some(false), |acc, o| match (acc, o) {
+                        (Some(true), _) | (_, Some(true)) => Some(true),
+                        (None, _) | (_, None) => None,
+                        _ => Some(false),
+                    });
+                match overlap {
+                    Some(true) => conflicts.push(format!("contract {}", other_id)),
+                    Some(false) => (),
+                    None => {
+                        check.and(0);
+                    }
+                }
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(check.finalize())
+        } else {
+            Ok(CheckKey::NoExclusiveConflicts.err_with(&conflicts.join(", ")))
+        }
+    }
+
+    /// Documents the contract must have, a type is listed once per required file
+    /// together with the party which has to attach it, if it matters
+    pub fn required_documents(
+        &self,
+        seller: &MemberIdentity,
+        buyer: &MemberIdentity,
+    ) -> Vec<(AttachmentType, Option<MemberIdentity>)> {
+        let mut documents = vec![
+            (AttachmentType::Deed, None),
+            (AttachmentType::Application, None),
+        ];
+        // Sublicence is granted on the basis of the seller's own licence or concession
+        if self.is_sublicense() {
+            documents.push((AttachmentType::ParentContract, None));
+        }
+        // Legal entities act through representatives, each one proves its own powers
+        for member in &[seller, buyer] {
+            if member.is_legal_entity() {
+                documents.push((AttachmentType::PowerOfAttorney, Some((*member).clone())));
+            }
+        }
+        documents
+    }
+
+    /// Checks that the required documents are attached and the generated deed and application
+    /// still match the contract, uploaded ones need a manual check
+    pub fn check_documents<T>(
+        &self,
+        schema: &Schema<T>,
+        contract_id: &ContractId,
+        seller: &MemberIdentity,
+        buyer: &MemberIdentity,
+    ) -> Result<Check, Error>
+    where
+        T: AsRef<dyn Snapshot>,
+    {
+        let deed = schema.contract_deed(contract_id);
+        let application = schema.contract_application(contract_id);
+
+        let owners = schema.contract_file_owners();
+        let mut attached = schema
+            .contract_files(contract_id)
+            .iter()
+            .filter_map(|(document_id, metadata)| {
+                AttachmentType::try_from(metadata.file_type())
+                    .ok()
+                    .map(|file_type| (file_type, owners.get(&document_id)))
+            })
+            .collect::<Vec<(AttachmentType, Option<MemberIdentity>)>>();
+        if deed.is_some() {
+            attached.push((AttachmentType::Deed, None));
+        }
+        if application.is_some() {
+            attached.push((AttachmentType::Application, None));
+        }
+
+        let mut check = CheckKey::DocumentsMatchCondition.new_check_chain();
+        let mut details = Vec::new();
+        for (document, party) in self.required_documents(seller, buyer) {
+            let found = attached.iter().position(|(file_type, owner)| {
+                file_type == &document && (party.is_none() || owner == &party)
+            });
+            if let Some(i) = found {
+                attached.swap_remove(i);
+                continue;
+            }
+            // Documents attached by the node itself or before the owners were
+            // recorded can't be matched to the party and need a manual check
+            let unowned = attached
+                .iter()
+                .position(|(file_type, owner)| file_type == &document && owner.is_none());
+            match (unowned, party) {
+                (Some(i), _) => {
+                    attached.swap_remove(i);
+                    check.and(0);
+                }
+                (None, Some(party)) => details.push(format!("missing {} of {}", document, party)),
+                (None, None) => details.push(format!("missing {}", document)),
+            }
+        }
+
+        if let (Some(deed), Some(application)) = (deed, application) {
+            let documents = [
+                (AttachmentType::Deed, *deed.tx_hash()),
+                (AttachmentType::Application, *application.tx_hash()),
+            ];
+            match template::mismatched_documents(schema, contract_id, &documents)? {
+                Some(mismatched) => details.extend(
+                    mismatched
+                        .into_iter()
+                        .map(|name| format!("mismatched {}", name)),
+                ),
+                None => {
+                    check.and(0);
+                }
+            }
+        }
+
+        if details.is_empty() {
+            Ok(check.finalize())
+        } else {
+            Ok(CheckKey::DocumentsMatchCondition.err_with(&details.join(", ")))
+        }
+    }
+
     fn check_locations(&self) -> Check {
         if self.objects().iter().all(|o| o.all_locations_oktmo()) {
             CheckKey::LocationValid.ok()
@@ -240,6 +449,68 @@ encoding_struct! {
 }
 
 impl ObjectOwnership {
+    /// Whether the territory and classifiers intersect, `None` if it can't be checked automatically
+    fn overlaps(&self, location: &[Location], classifiers: &[Classifier]) -> Option<bool> {
+        let classifiers_overlap = is_unrestricted_classifiers(classifiers)
+            || is_unrestricted_classifiers(&self.classifiers())
+            || self.classifiers().iter().any(|offered| {
+                classifiers
+                    .iter()
+                    .any(|c| c.registry() == offered.registry() && c.value() == offered.value())
+            });
+        if !classifiers_overlap {
+            return Some(false);
+        }
+        if is_unrestricted_location(location) || is_unrestricted_location(&self.location()) {
+            return Some(true);
+        }
+        let mut result = Some(false);
+        for offered in self.location() {
+            for held in location {
+                if offered.is_custom() || held.is_custom() {
+                    result = result.and(None);
+                } else if held.covers(&offered).unwrap_or(false)
+                    || offered.covers(held).unwrap_or(false)
+                {
+                    return Some(true);
+                }
+            }
+        }
+        result
+    }
+
+    /// Checks that the offered rights are within the scope of the seller's own rights
+    fn check_scope(&self, rights: &Rights) -> i8 {
+        let distribution = if rights.can_distribute() {
+            1
+        } else if rights.is_distribute_with_written_permission() {
+            0
+        } else {
+            -1
+        };
+        let exclusive = if self.exclusive() && !rights.is_exclusive() {
+            -1
+        } else {
+            1
+        };
+        let locations = self
+            .location()
+            .iter()
+            .map(|offered| location_scope(&rights.location(), offered))
+            .min()
+            .unwrap_or(1);
+        let classifiers = self
+            .classifiers()
+            .iter()
+            .map(|offered| classifier_scope(&rights.classifiers(), offered))
+            .min()
+            .unwrap_or(1);
+        *[distribution, exclusive, locations, classifiers]
+            .iter()
+            .min()
+            .unwrap()
+    }
+
     fn all_locations_oktmo(&self) -> bool {
         for location in self.location() {
             if !location.is_oktmo() {
@@ -250,6 +521,44 @@ impl ObjectOwnership {
     }
 }
 
+fn is_unrestricted_location(location: &[Location]) -> bool {
+    location.is_empty() || location.iter().any(|l| l == &Location::default())
+}
+
+fn is_unrestricted_classifiers(classifiers: &[Classifier]) -> bool {
+    classifiers.is_empty() || classifiers.iter().any(|c| c == &Classifier::default())
+}
+
+fn location_scope(held: &[Location], offered: &Location) -> i8 {
+    // Rights without territory restrictions cover any location
+    if is_unrestricted_location(held) {
+        return 1;
+    }
+    if offered.is_custom() || held.iter().any(|l| l.is_custom()) {
+        return 0;
+    }
+    if held.iter().any(|l| l.covers(offered).unwrap_or(false)) {
+        1
+    } else {
+        -1
+    }
+}
+
+fn classifier_scope(held: &[Classifier], offered: &Classifier) -> i8 {
+    // Rights without classifier restrictions cover any classifier
+    if is_unrestricted_classifiers(held) {
+        return 1;
+    }
+    if held
+        .iter()
+        .any(|c| c.registry() == offered.registry() && c.value() == offered.value())
+    {
+        1
+    } else {
+        -1
+    }
+}
+
 encoding_struct! {
     #[derive(Eq)]
     struct CheckResult {
@@ -286,6 +595,10 @@ pub enum CheckKey {
     ContainsTrademark,
     #[serde(skip_deserializing)]
     NoUnstructuredData,
+    #[serde(skip_deserializing)]
+    SublicenseScopeValid,
+    #[serde(skip_deserializing)]
+    NoExclusiveConflicts,
 
     // External checks
     TaxPaymentInfoAdded = 32768, // 9
@@ -308,6 +621,8 @@ impl CheckKey {
             CheckKey::ObjectsSellable => "ОИС проверяемого вида могут участвать в сделке",
             CheckKey::ContainsTrademark => "ТЗ присутствует в сделке",
             CheckKey::NoUnstructuredData => "Вся информация о владении ОИС структурирована, возможна автоматическая обработка",
+            CheckKey::SublicenseScopeValid => "Передаваемые права не превышают объем прав продавца",
+            CheckKey::NoExclusiveConflicts => "Пересечения с исключительными правами и другими договорами отсутствуют",
             CheckKey::TaxPaymentInfoAdded => "Полученные данные подтверждают уплату пошлины в необходимом размере и требуемые сроки",
             CheckKey::DurationValid => "Текущая дата меньше установленной даты окончания срока действия исключительного права",
             CheckKey::Blacklist => "Действующие записи отсутствуют в списке",
@@ -318,11 +633,15 @@ impl CheckKey {
 
     pub fn err(self) -> Check {
         let desc = match self {
+            CheckKey::DocumentsMatchCondition => "Комплект документов не соответствует условиям контракта",
             CheckKey::CanSell => "Продавец не соответствует требованиям ГК РФ п. 3 ст. 1027",
             CheckKey::CanBuy => "Покупатель не соответствует требованиям ГК РФ п. 3 ст. 1027",
             CheckKey::ObjectDuplicates => "ОИС проверяемого вида не могут участвать в сделке",
             CheckKey::ObjectsSellable => "ОИС проверяемого вида не могут участвать в сделке",
             CheckKey::ContainsTrademark => "ТЗ не присутствует в сделке",
+            CheckKey::NoUnstructuredData => "Присутствует неструктурированая информация о владении ОИС, внесение прав в реестр невозможно",
+            CheckKey::SublicenseScopeValid => "Передаваемые права превышают объем прав продавца",
+            CheckKey::NoExclusiveConflicts => "Передаваемые права пересекаются с исключительными правами или другими договорами",
             CheckKey::TaxPaymentInfoAdded => "Полученные данные свидетельствуют об отсутствии уплаты пошлины в необходимом размере и требуемые сроки",
             CheckKey::DurationValid => "Текущая дата больше установленной даты окончания срока действия исключительного права",
             _ => "",
@@ -330,12 +649,20 @@ impl CheckKey {
         self.result(-1, desc)
     }
 
+    pub fn err_with(self, details: &str) -> Check {
+        let check = self.err();
+        let desc = format!("{}: {}", check.result().desc(), details);
+        self.result(-1, &desc)
+    }
+
     pub fn unknown(self) -> Check {
         let desc = match self {
             CheckKey::DocumentsMatchCondition => "Невозможно проверить соответствие документов условиям контракта",
             CheckKey::NoUnstructuredData => "Присутствует неструктурированая информация о владении ОИС, автоматическая обработка невозможна",
             CheckKey::DurationValid => "",
             CheckKey::LocationValid => "Применен свободный ввод территории",
+            CheckKey::SublicenseScopeValid => "Невозможно автоматически сопоставить передаваемые права с правами продавца",
+            CheckKey::NoExclusiveConflicts => "Применен свободный ввод территории, пересечение прав необходимо проверить",
             CheckKey::Blacklist => "Действующие записи присутствуют в списке",
             _ => "",
         };
@@ -390,3 +717,277 @@ impl From<(u16, CheckResult)> for Check {
         Self::new(key, result)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use blockp_core::crypto::hash;
+    use blockp_core::storage::Fork;
+    use chrono::Utc;
+
+    use crate::fixtures::*;
+
+    use super::super::attachment::AttachmentMetadata;
+    use super::super::classifier::ClassifierRegistry;
+    use super::super::contract::Contract;
+    use super::super::ownership::OwnershipUnstructured;
+    use super::*;
+
+    /// Distributable non-exclusive licence for classes 8 and 13 in oktmo 45
+    fn seller_licence() -> Rights {
+        scoped_licence(CAN_DISTRIBUTE, vec![oktmo(45)], vec![mktu("8"), mktu("13")])
+    }
+
+    fn add_object(schema: &mut Schema<&mut Fork>, holders: Vec<(MemberIdentity, Rights)>) {
+        schema.update_object_data(&object().id(), "{}", &hash(b"object"), object());
+        schema.update_rights(
+            &object(),
+            holders
+                .into_iter()
+                .collect::<HashMap<MemberIdentity, Rights>>(),
+        );
+    }
+
+    fn scope_check(checks: Vec<Check>) -> Option<CheckResult> {
+        checks
+            .into_iter()
+            .find(|c| c.key() == CheckKey::SublicenseScopeValid as u16)
+            .map(|c| c.result())
+    }
+
+    #[test]
+    fn scope_of_seller_rights() {
+        let offered = ownership(false, vec![oktmo(45379000)], vec![mktu("8")]);
+        assert_eq!(offered.check_scope(&seller_licence()), 1);
+        let unrestricted = scoped_licence(CAN_DISTRIBUTE, vec![Location::default()], vec![]);
+        assert_eq!(offered.check_scope(&unrestricted), 1);
+        // Seller isn't allowed to distribute
+        let rights = scoped_licence(0, vec![oktmo(45)], vec![mktu("8"), mktu("13")]);
+        assert_eq!(offered.check_scope(&rights), -1);
+        let rights = scoped_licence(
+            WITH_WRITTEN_PERMISSION,
+            vec![oktmo(45)],
+            vec![mktu("8"), mktu("13")],
+        );
+        assert_eq!(offered.check_scope(&rights), 0);
+
+        // One location or classifier out of the licence is enough to fail
+        let offered = ownership(
+            false,
+            vec![oktmo(45379000), oktmo(46000000)],
+            vec![mktu("8")],
+        );
+        assert_eq!(offered.check_scope(&seller_licence()), -1);
+        let offered = ownership(false, vec![oktmo(45379000)], vec![mktu("8"), mktu("25")]);
+        assert_eq!(offered.check_scope(&seller_licence()), -1);
+        let mpk = Classifier::new(ClassifierRegistry::Mpk as u8, "8", "");
+        let offered = ownership(false, vec![oktmo(45379000)], vec![mpk]);
+        assert_eq!(offered.check_scope(&seller_licence()), -1);
+
+        let offered = ownership(false, vec![Location::custom("Москва")], vec![mktu("8")]);
+        assert_eq!(offered.check_scope(&seller_licence()), 0);
+
+        // Exclusive sublicence from a non-exclusive licence
+        let offered = ownership(true, vec![oktmo(45379000)], vec![mktu("8")]);
+        assert_eq!(offered.check_scope(&seller_licence()), -1);
+        let rights = scoped_licence(
+            CAN_DISTRIBUTE | EXCLUSIVE,
+            vec![oktmo(45)],
+            vec![mktu("8"), mktu("13")],
+        );
+        assert_eq!(offered.check_scope(&rights), 1);
+    }
+
+    #[test]
+    fn sublicense_scope_check() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        add_object(&mut schema, vec![(seller(), seller_licence())]);
+
+        let inside = ownership(false, vec![oktmo(45379000)], vec![mktu("13")]);
+        let checks = conditions(ContractType::Sublicense, vec![inside.clone()])
+            .check_rights(&schema, &seller())
+            .unwrap();
+        assert_eq!(scope_check(checks).unwrap().result(), 1);
+
+        let outside = ownership(false, vec![oktmo(46000000)], vec![mktu("13")]);
+        let checks = conditions(ContractType::SubconcessionAgreement, vec![outside.clone()])
+            .check_rights(&schema, &seller())
+            .unwrap();
+        let result = scope_check(checks).unwrap();
+        assert_eq!(result.result(), -1);
+        assert!(result.is_error());
+
+        // Only sublicences are limited by the seller's rights
+        let checks = conditions(ContractType::License, vec![inside])
+            .check_rights(&schema, &seller())
+            .unwrap();
+        assert!(scope_check(checks).is_none());
+
+        // Rights of the seller may be wider than the registered ones
+        schema.update_unstructured_ownership(
+            &object(),
+            vec![OwnershipUnstructured::new("licence", Some(seller()), None)],
+        );
+        let checks = conditions(ContractType::Sublicense, vec![outside])
+            .check_rights(&schema, &seller())
+            .unwrap();
+        assert_eq!(scope_check(checks).unwrap().result(), 0);
+    }
+
+    fn conflicts_check(
+        schema: &Schema<&mut Fork>,
+        contract_type: ContractType,
+        offered: ObjectOwnership,
+    ) -> CheckResult {
+        conditions(contract_type, vec![offered])
+            .check_conflicts(schema, &hash(b"contract"), &seller(), &buyer())
+            .unwrap()
+            .result()
+    }
+
+    #[test]
+    fn ownership_overlaps() {
+        let offered = ownership(true, vec![oktmo(45379000)], vec![mktu("8")]);
+        assert_eq!(offered.overlaps(&[oktmo(45)], &[mktu("8")]), Some(true));
+        assert_eq!(offered.overlaps(&[oktmo(45379000)], &[]), Some(true));
+        assert_eq!(
+            offered.overlaps(&[Location::default()], &[mktu("8")]),
+            Some(true)
+        );
+        assert_eq!(offered.overlaps(&[oktmo(46)], &[mktu("8")]), Some(false));
+        assert_eq!(offered.overlaps(&[oktmo(45)], &[mktu("13")]), Some(false));
+        // Offered territory covers the held one
+        let wide = ownership(true, vec![oktmo(45)], vec![mktu("8")]);
+        assert_eq!(wide.overlaps(&[oktmo(45379000)], &[mktu("8")]), Some(true));
+
+        // Disjoint classifiers make custom territories irrelevant
+        let custom = [Location::custom("Москва")];
+        assert_eq!(offered.overlaps(&custom, &[mktu("13")]), Some(false));
+        assert_eq!(offered.overlaps(&custom, &[mktu("8")]), None);
+    }
+
+    #[test]
+    fn exclusive_licence_conflicts() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let other_licensee = MemberIdentity::from_str("ogrn::1027700132195").unwrap();
+        let exclusive = scoped_licence(EXCLUSIVE, vec![oktmo(45)], vec![mktu("8")]);
+        let non_exclusive = scoped_licence(0, vec![oktmo(45)], vec![mktu("13")]);
+        add_object(
+            &mut schema,
+            vec![
+                (seller(), Rights::new_owned()),
+                (licensee(), exclusive),
+                (other_licensee, non_exclusive),
+            ],
+        );
+        let other_id = hash(b"other contract");
+        let other = ownership(true, vec![oktmo(46)], vec![mktu("8")]);
+        schema.update_contract(
+            &other_id,
+            Contract::buy(
+                licensee(),
+                seller(),
+                100,
+                conditions(ContractType::License, vec![other]),
+            ),
+        );
+        schema.set_published_contract(&object().id(), &other_id);
+
+        let offered = ownership(false, vec![oktmo(45379000)], vec![mktu("8")]);
+        let result = conflicts_check(&schema, ContractType::License, offered.clone());
+        assert_eq!(result.result(), -1);
+        assert!(result.desc().contains("rightholder"));
+        // Expropriation doesn't grant a licence
+        let result = conflicts_check(&schema, ContractType::Expropriation, offered);
+        assert_eq!(result.result(), 1);
+
+        // Non-exclusive licences don't conflict with each other
+        let offered = ownership(false, vec![oktmo(45379000)], vec![mktu("13")]);
+        let result = conflicts_check(&schema, ContractType::License, offered);
+        assert_eq!(result.result(), 1);
+        let offered = ownership(true, vec![oktmo(45379000)], vec![mktu("13")]);
+        let result = conflicts_check(&schema, ContractType::License, offered);
+        assert_eq!(result.result(), -1);
+
+        let offered = ownership(false, vec![oktmo(46)], vec![mktu("8")]);
+        let result = conflicts_check(&schema, ContractType::License, offered);
+        assert_eq!(result.result(), -1);
+        assert!(result.desc().contains(&other_id.to_string()));
+
+        let offered = ownership(true, vec![oktmo(47)], vec![mktu("8")]);
+        let result = conflicts_check(&schema, ContractType::License, offered);
+        assert_eq!(result.result(), 1);
+
+        let offered = ownership(false, vec![Location::custom("Москва")], vec![mktu("8")]);
+        let result = conflicts_check(&schema, ContractType::License, offered);
+        assert_eq!(result.result(), 0);
+    }
+
+    fn attach(
+        schema: &mut Schema<&mut Fork>,
+        file_type: AttachmentType,
+        owner: Option<MemberIdentity>,
+    ) {
+        let document_id = hash(format!("{} {:?}", file_type, owner).as_bytes());
+        let metadata = AttachmentMetadata::new("file.pdf", None, file_type as u8, Utc::now());
+        schema.attach_contract_file(&contract_id(), &document_id, metadata);
+        if let Some(owner) = owner {
+            schema.set_contract_file_owner(&document_id, &owner);
+        }
+    }
+
+    fn contract_id() -> ContractId {
+        hash(b"contract")
+    }
+
+    fn documents_check(schema: &Schema<&mut Fork>, seller: &MemberIdentity) -> CheckResult {
+        conditions(ContractType::License, vec![])
+            .check_documents(schema, &contract_id(), seller, &buyer())
+            .unwrap()
+            .result()
+    }
+
+    #[test]
+    fn power_of_attorney_of_each_legal_entity() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+
+        // Power of attorney attached by an individual entrepreneur is not the buyer's one
+        attach(&mut schema, AttachmentType::PowerOfAttorney, Some(seller()));
+        let result = documents_check(&schema, &seller());
+        assert!(result
+            .desc()
+            .contains(&format!("missing power_of_attorney of {}", buyer())));
+        assert!(!result.desc().contains(&seller().to_string()));
+
+        // The buyer's power of attorney doesn't prove the seller's powers
+        let legal_seller = MemberIdentity::from_str("ogrn::1027700132195").unwrap();
+        attach(&mut schema, AttachmentType::PowerOfAttorney, Some(buyer()));
+        let result = documents_check(&schema, &legal_seller);
+        assert!(result
+            .desc()
+            .contains(&format!("missing power_of_attorney of {}", legal_seller)));
+        assert!(!result
+            .desc()
+            .contains(&format!("missing power_of_attorney of {}", buyer())));
+        attach(
+            &mut schema,
+            AttachmentType::PowerOfAttorney,
+            Some(legal_seller.clone()),
+        );
+        let result = documents_check(&schema, &legal_seller);
+        assert!(!result.desc().contains("power_of_attorney"));
+
+        // Documents with unknown owner are counted but need a manual check
+        let other_seller = MemberIdentity::from_str("ogrn::5068681643685").unwrap();
+        let result = documents_check(&schema, &other_seller);
+        assert!(result.desc().contains("missing power_of_attorney"));
+        attach(&mut schema, AttachmentType::PowerOfAttorney, None);
+        let result = documents_check(&schema, &other_seller);
+        assert!(!result.desc().contains("power_of_attorney"));
+        assert!(result.desc().contains("missing deed"));
+    }
+}
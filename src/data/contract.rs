@@ -1,7 +1,12 @@
 use std::convert::TryFrom;
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use num_enum::TryFromPrimitive;
+use serde::{Deserialize, Serialize};
 
 use blockp_core::crypto::Hash;
+use chrono::{DateTime, Duration, Utc};
 
 use crate::error::Error;
 
@@ -49,6 +54,81 @@ impl ContractSign {
     }
 }
 
+encoding_struct! {
+    struct ContractRevision {
+        number: u64,
+        proposer: MemberIdentity,
+        tx_hash: &Hash,
+        price: u64,
+        conditions: Conditions,
+        /// Consensus time of the time service, `None` if it wasn't reported yet
+        timestamp: Option<DateTime<Utc>>,
+    }
+}
+
+encoding_struct! {
+    struct ContractTransition {
+        from: u16,
+        to: u16,
+        action: &str,
+        /// `None` for actions made by the registry and internal services
+        actor: Option<MemberIdentity>,
+        tx_hash: &Hash,
+        height: u64,
+    }
+}
+
+/// Action of the history entry recorded when the contract is opened
+pub const OPEN_ACTION: &str = "open";
+
+impl ContractTransition {
+    /// Whether the entry records opening of the contract rather than a status change
+    pub fn is_open(&self) -> bool {
+        self.action() == OPEN_ACTION
+    }
+}
+
+/// Statuses that can't be held by a contract longer than the configured deadline
+pub const EXPIRABLE_STATUSES: [&str; 3] = ["request_confirm", "draft", "confirmed"];
+
+/// Longest deadline of a status, larger values overflow the date arithmetic
+pub const MAX_DEADLINE_DAYS: u64 = 36_500;
+
+encoding_struct! {
+    struct ContractStateChange {
+        status: &str,
+        /// `None` if the state was changed before the time service reported the consensus time
+        timestamp: Option<DateTime<Utc>>,
+    }
+}
+
+impl ContractStateChange {
+    pub fn deadline(&self, days: u64) -> Option<DateTime<Utc>> {
+        let days = Duration::days(days.min(MAX_DEADLINE_DAYS) as i64);
+        self.timestamp()?.checked_add_signed(days)
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, TryFromPrimitive)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationReason {
+    /// Contract is terminated by the parties' agreement
+    Agreement = 1,
+    /// Contract is terminated by the registry (e.g. court decision)
+    RegistryDecision = 2,
+}
+
+impl FromStr for TerminationReason {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        serde_plain::from_str(s).map_err(|_| Error::bad_termination_reason(s))
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum BuyerSeller {
     Buyer,
     Seller,
@@ -106,6 +186,16 @@ impl Contract {
         self.seller() == *member
     }
 
+    pub fn party(&self, member: &MemberIdentity) -> Option<BuyerSeller> {
+        if self.is_buyer(member) {
+            Some(BuyerSeller::Buyer)
+        } else if self.is_seller(member) {
+            Some(BuyerSeller::Seller)
+        } else {
+            None
+        }
+    }
+
     pub fn is_draft(&self) -> Result<bool, Error> {
         let status = ContractStatus::try_from(self.state())?;
         match status {
@@ -117,9 +207,10 @@ impl Contract {
     pub fn is_finished(&self) -> Result<bool, Error> {
         let status = ContractStatus::try_from(self.state())?;
         match status {
-            ContractStatus::Refused | ContractStatus::Approved | ContractStatus::Rejected => {
-                Ok(true)
-            }
+            ContractStatus::Refused
+            | ContractStatus::Approved
+            | ContractStatus::Rejected
+            | ContractStatus::Terminated => Ok(true),
             _ => Ok(false),
         }
     }
@@ -166,63 +257,53 @@ impl Contract {
         )
     }
 
+    /// The same contract with `member` replaced by its successor
+    pub fn replace_member(self, member: &MemberIdentity, successor: &MemberIdentity) -> Self {
+        let replace = |party: MemberIdentity| {
+            if party == *member {
+                successor.clone()
+            } else {
+                party
+            }
+        };
+        Self::new(
+            replace(self.buyer()),
+            replace(self.seller()),
+            self.price(),
+            self.conditions(),
+            self.state(),
+            self.undefined(),
+        )
+    }
+
     pub fn apply(self, action: Action) -> Result<Self, Error> {
-        use Action::*;
-        use ContractStatus as Status;
         let status = ContractStatus::try_from(self.state())?;
-        match (status, action.clone()) {
-            (Status::RequestConfirm(c), Confirm(u)) if self.buyer() == u => {
-                Ok(self.set(c.confirm_buyer().into()))
-            }
-            (Status::RequestConfirm(c), Unconfirm(u)) if self.buyer() == u => {
-                Ok(self.set(c.unconfirm_buyer().into()))
-            }
-            (Status::RequestConfirm(c), Confirm(u)) if self.seller() == u => {
-                Ok(self.set(c.confirm_seller().into()))
-            }
-            (Status::RequestConfirm(c), Unconfirm(u)) if self.seller() == u => {
-                Ok(self.set(c.unconfirm_seller().into()))
-            }
-            (
-                Status::RequestConfirm(RequestConfirm {
-                    buyer: true,
-                    seller: true,
-                }),
-                New,
-            ) => Ok(self.set(Status::New)),
-            (Status::New, MakeDraft) => Ok(self.set(Status::Draft(Draft::new()))),
-            (Status::New, Reject) => Ok(self.set(Status::Rejected)),
-            (Status::Draft(_), Update { price, conditions }) => {
-                Ok(self.modify(price, conditions, State::NEW.bits))
-            }
-            (Status::Draft(_), Refuse) => Ok(self.set(Status::Refused)),
-            (Status::Draft(d), Confirm(u)) if self.buyer() == u => {
-                Ok(self.set(d.confirm_buyer().into()))
-            }
-            (Status::Draft(d), Confirm(u)) if self.seller() == u => {
-                Ok(self.set(d.confirm_seller().into()))
+        let next = self
+            .next_status(status, &action)
+            .ok_or_else(|| Error::bad_contract_state(status, &format!("{:?}", action)))?;
+        debug_assert!(find_transition(status.kind(), action.kind())
+            .map_or(false, |t| t.to.contains(&next.kind())));
+        match action {
+            Action::Update { price, conditions } => {
+                Ok(self.modify(price, conditions, State::from(next).bits))
             }
-            (Status::Draft(_), MakeDraft) => Ok(self.set(Status::Draft(Draft::new()))),
-            (Status::Confirmed(_), Refuse) => Ok(self.set(Status::Refused)),
-            (Status::Confirmed(_), MakeDraft) => Ok(self.set(Status::Draft(Draft::new()))),
-            (Status::Confirmed(_), Update { price, conditions }) => {
-                Ok(self.modify(price, conditions, State::NEW.bits))
-            }
-            (Status::Confirmed(c), Action::Sign(u)) if self.buyer() == u => {
-                Ok(self.set(c.sign_buyer().into()))
-            }
-            (Status::Confirmed(c), Action::Sign(u)) if self.seller() == u => {
-                Ok(self.set(c.sign_seller().into()))
-            }
-            (Status::Signed, Refuse) => Ok(self.set(Status::Refused)),
-            (Status::Signed, ReadyForRegistering) => Ok(self.set(Status::ReadyForRegistering)),
-            (Status::ReadyForRegistering, Register) => Ok(self.set(Status::Registering)),
-            (Status::Registering, AwaitUserAction) => Ok(self.set(Status::AwaitingUserAction)),
-            (Status::Registering, Reject) => Ok(self.set(Status::Rejected)),
-            (Status::Registering, Approve) => Ok(self.set(Status::Approved)),
-            (Status::AwaitingUserAction, Reject) => Ok(self.set(Status::Rejected)),
-            (Status::AwaitingUserAction, Approve) => Ok(self.set(Status::Approved)),
-            _ => Error::bad_contract_state(status, &format!("{:?}", action)).ok(),
+            _ => Ok(self.set(next)),
+        }
+    }
+
+    /// Resolves the target of a transition allowed by the table,
+    /// `None` if the guard of the transition doesn't hold
+    fn next_status(&self, status: ContractStatus, action: &Action) -> Option<ContractStatus> {
+        let transition = find_transition(status.kind(), action.kind())?;
+        match transition.guard {
+            Guard::None => Some(transition.to[0].initial()),
+            Guard::Party => status.progress(action.kind(), &self.party(action.actor()?)?),
+            Guard::BothConfirmed => match status {
+                ContractStatus::RequestConfirm(c) if c.buyer && c.seller => {
+                    Some(transition.to[0].initial())
+                }
+                _ => None,
+            },
         }
     }
 
@@ -376,6 +457,7 @@ pub enum ContractStatus {
     Refused,
     Approved,
     Rejected,
+    Terminated,
 }
 
 impl TryFrom<u16> for ContractStatus {
@@ -388,6 +470,8 @@ impl TryFrom<u16> for ContractStatus {
             ContractStatus::New
         } else if state == State::REJECTED {
             ContractStatus::Rejected
+        } else if state == State::TERMINATED {
+            ContractStatus::Terminated
         } else if state == State::APPROVED {
             ContractStatus::Approved
         } else if state == State::REFUSED {
@@ -466,28 +550,133 @@ impl From<ContractStatus> for State {
             ContractStatus::AwaitingUserAction => State::AWAITING_USER_ACTION,
             ContractStatus::Rejected => State::REJECTED,
             ContractStatus::Approved => State::APPROVED,
+            ContractStatus::Terminated => State::TERMINATED,
         }
     }
 }
 
 impl Display for ContractStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.kind().fmt(f)
+    }
+}
+
+impl ContractStatus {
+    pub fn kind(&self) -> StatusKind {
+        match self {
+            Self::RequestConfirm(_) => StatusKind::RequestConfirm,
+            Self::New => StatusKind::New,
+            Self::Draft(_) => StatusKind::Draft,
+            Self::Confirmed(_) => StatusKind::Confirmed,
+            Self::Signed => StatusKind::Signed,
+            Self::ReadyForRegistering => StatusKind::ReadyForRegistering,
+            Self::Registering => StatusKind::Registering,
+            Self::AwaitingUserAction => StatusKind::AwaitingUserAction,
+            Self::Refused => StatusKind::Refused,
+            Self::Approved => StatusKind::Approved,
+            Self::Rejected => StatusKind::Rejected,
+            Self::Terminated => StatusKind::Terminated,
+        }
+    }
+
+    /// Status with the party's confirmation or signature recorded
+    fn progress(self, action: ActionKind, party: &BuyerSeller) -> Option<ContractStatus> {
+        use BuyerSeller::*;
+        let next = match (self, action, party) {
+            (Self::RequestConfirm(c), ActionKind::Confirm, Buyer) => c.confirm_buyer().into(),
+            (Self::RequestConfirm(c), ActionKind::Confirm, Seller) => c.confirm_seller().into(),
+            (Self::RequestConfirm(c), ActionKind::Unconfirm, Buyer) => c.unconfirm_buyer().into(),
+            (Self::RequestConfirm(c), ActionKind::Unconfirm, Seller) => {
+                c.unconfirm_seller().into()
+            }
+            (Self::Draft(d), ActionKind::Confirm, Buyer) => d.confirm_buyer(),
+            (Self::Draft(d), ActionKind::Confirm, Seller) => d.confirm_seller(),
+            (Self::Confirmed(c), ActionKind::Sign, Buyer) => c.sign_buyer().into(),
+            (Self::Confirmed(c), ActionKind::Sign, Seller) => c.sign_seller().into(),
+            _ => return None,
+        };
+        Some(next)
+    }
+
+    /// Action the contract waits for from the party, payments aren't taken into account
+    pub fn awaited_action(&self, party: &BuyerSeller) -> Option<PendingAction> {
+        let proceeded = |buyer: bool, seller: bool| match party {
+            BuyerSeller::Buyer => buyer,
+            BuyerSeller::Seller => seller,
+        };
+        match *self {
+            Self::RequestConfirm(RequestConfirm { buyer, seller })
+            | Self::Draft(Draft { buyer, seller })
+                if !proceeded(buyer, seller) =>
+            {
+                Some(PendingAction::Confirm)
+            }
+            Self::Confirmed(Confirmed { buyer, seller }) if !proceeded(buyer, seller) => {
+                Some(PendingAction::Sign)
+            }
+            Self::AwaitingUserAction => Some(PendingAction::UserAction),
+            _ => None,
+        }
+    }
+}
+
+/// Action of a participant the contract waits for
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingAction {
+    Confirm,
+    Sign,
+    UserAction,
+    Pay,
+}
+
+/// Contract status without the participants' progress
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusKind {
+    RequestConfirm,
+    New,
+    Draft,
+    Confirmed,
+    Signed,
+    ReadyForRegistering,
+    Registering,
+    AwaitingUserAction,
+    Refused,
+    Approved,
+    Rejected,
+    Terminated,
+}
+
+impl StatusKind {
+    /// Status the contract gets when it enters this kind of status
+    fn initial(self) -> ContractStatus {
+        match self {
+            StatusKind::RequestConfirm => ContractStatus::RequestConfirm(RequestConfirm {
+                buyer: false,
+                seller: false,
+            }),
+            StatusKind::New => ContractStatus::New,
+            StatusKind::Draft => ContractStatus::Draft(Draft::new()),
+            StatusKind::Confirmed => ContractStatus::Confirmed(Confirmed::new()),
+            StatusKind::Signed => ContractStatus::Signed,
+            StatusKind::ReadyForRegistering => ContractStatus::ReadyForRegistering,
+            StatusKind::Registering => ContractStatus::Registering,
+            StatusKind::AwaitingUserAction => ContractStatus::AwaitingUserAction,
+            StatusKind::Refused => ContractStatus::Refused,
+            StatusKind::Approved => ContractStatus::Approved,
+            StatusKind::Rejected => ContractStatus::Rejected,
+            StatusKind::Terminated => ContractStatus::Terminated,
+        }
+    }
+}
+
+impl Display for StatusKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "{}",
-            match self {
-                Self::RequestConfirm(_) => "request_confirm",
-                Self::New => "new",
-                Self::Draft(_) => "draft",
-                Self::Confirmed(_) => "confirmed",
-                Self::Signed => "signed",
-                Self::Refused => "refused",
-                Self::ReadyForRegistering => "ready_for_registering",
-                Self::Registering => "registering",
-                Self::AwaitingUserAction => "awaiting_user_action",
-                Self::Approved => "approved",
-                Self::Rejected => "rejected",
-            }
+            serde_plain::to_string(self).expect("serde_plain::to_string failed on StatusKind")
         )
     }
 }
@@ -506,6 +695,180 @@ pub enum Action {
     Register,
     AwaitUserAction,
     ReadyForRegistering,
+    Terminate,
+    Expire,
+}
+
+impl Action {
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Action::New => ActionKind::New,
+            Action::MakeDraft => ActionKind::MakeDraft,
+            Action::Confirm(_) => ActionKind::Confirm,
+            Action::Unconfirm(_) => ActionKind::Unconfirm,
+            Action::Sign(_) => ActionKind::Sign,
+            Action::Update { .. } => ActionKind::Update,
+            Action::Refuse => ActionKind::Refuse,
+            Action::Approve => ActionKind::Approve,
+            Action::Reject => ActionKind::Reject,
+            Action::Register => ActionKind::Register,
+            Action::AwaitUserAction => ActionKind::AwaitUserAction,
+            Action::ReadyForRegistering => ActionKind::ReadyForRegistering,
+            Action::Terminate => ActionKind::Terminate,
+            Action::Expire => ActionKind::Expire,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.kind().name()
+    }
+
+    /// Participant performing the action, if the action is a participant's one
+    pub fn actor(&self) -> Option<&MemberIdentity> {
+        match self {
+            Action::Confirm(actor) | Action::Unconfirm(actor) | Action::Sign(actor) => Some(actor),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    New,
+    MakeDraft,
+    Confirm,
+    Unconfirm,
+    Sign,
+    Update,
+    Refuse,
+    Approve,
+    Reject,
+    Register,
+    AwaitUserAction,
+    ReadyForRegistering,
+    Terminate,
+    Expire,
+}
+
+impl ActionKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ActionKind::New => "new",
+            ActionKind::MakeDraft => "make_draft",
+            ActionKind::Confirm => "confirm",
+            ActionKind::Unconfirm => "unconfirm",
+            ActionKind::Sign => "sign",
+            ActionKind::Update => "update",
+            ActionKind::Refuse => "refuse",
+            ActionKind::Approve => "approve",
+            ActionKind::Reject => "reject",
+            ActionKind::Register => "register",
+            ActionKind::AwaitUserAction => "await_user_action",
+            ActionKind::ReadyForRegistering => "ready_for_registering",
+            ActionKind::Terminate => "terminate",
+            ActionKind::Expire => "expire",
+        }
+    }
+}
+
+/// Condition of a transition besides the status and the action
+#[derive(Debug, Copy, Clone, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Guard {
+    /// The contract enters the first target status
+    None,
+    /// Only the buyer or the seller may act, the status records the party's progress
+    Party,
+    /// Both parties have confirmed the request, the contract enters the first target status
+    BothConfirmed,
+}
+
+impl Guard {
+    pub fn name(self) -> &'static str {
+        match self {
+            Guard::None => "none",
+            Guard::Party => "party",
+            Guard::BothConfirmed => "both_confirmed",
+        }
+    }
+}
+
+/// Allowed contract status change, `to` lists every status the action may lead to
+#[derive(Debug, Serialize, Eq, PartialEq)]
+pub struct Transition {
+    pub from: StatusKind,
+    pub action: ActionKind,
+    pub to: &'static [StatusKind],
+    pub guard: Guard,
+}
+
+macro_rules! transitions {
+    (@guard) => { Guard::None };
+    (@guard $guard:ident) => { Guard::$guard };
+    ($($from:ident + $action:ident => [$($to:ident),+] $(if $guard:ident)?;)*) => {
+        &[$(Transition {
+            from: StatusKind::$from,
+            action: ActionKind::$action,
+            to: &[$(StatusKind::$to),+],
+            guard: transitions!(@guard $($guard)?),
+        }),*]
+    };
+}
+
+/// Contract state machine. The first target is the one the contract gets
+/// unless participants' progress moves it further.
+pub const TRANSITIONS: &[Transition] = transitions! {
+    RequestConfirm + Confirm => [RequestConfirm] if Party;
+    RequestConfirm + Unconfirm => [RequestConfirm] if Party;
+    RequestConfirm + New => [New] if BothConfirmed;
+    RequestConfirm + Expire => [Refused];
+    New + MakeDraft => [Draft];
+    New + Reject => [Rejected];
+    Draft + Update => [New];
+    Draft + Refuse => [Refused];
+    Draft + Confirm => [Draft, Confirmed] if Party;
+    Draft + MakeDraft => [Draft];
+    Draft + Expire => [Refused];
+    Confirmed + Refuse => [Refused];
+    Confirmed + MakeDraft => [Draft];
+    Confirmed + Update => [New];
+    Confirmed + Sign => [Confirmed, Signed] if Party;
+    Confirmed + Expire => [Refused];
+    Signed + Refuse => [Refused];
+    Signed + ReadyForRegistering => [ReadyForRegistering];
+    ReadyForRegistering + Register => [Registering];
+    Registering + AwaitUserAction => [AwaitingUserAction];
+    Registering + Reject => [Rejected];
+    Registering + Approve => [Approved];
+    AwaitingUserAction + Reject => [Rejected];
+    AwaitingUserAction + Approve => [Approved];
+    Approved + Terminate => [Terminated];
+};
+
+pub fn find_transition(from: StatusKind, action: ActionKind) -> Option<&'static Transition> {
+    TRANSITIONS
+        .iter()
+        .find(|t| t.from == from && t.action == action)
+}
+
+/// Transition table in the Graphviz DOT format
+pub fn transitions_dot() -> String {
+    let mut dot = String::from("digraph contract {\n");
+    for transition in TRANSITIONS {
+        let label = match transition.guard {
+            Guard::None => transition.action.name().to_string(),
+            guard => format!("{} [{}]", transition.action.name(), guard.name()),
+        };
+        for to in transition.to {
+            dot.push_str(&format!(
+                "    {} -> {} [label=\"{}\"];\n",
+                transition.from, to, label
+            ));
+        }
+    }
+    dot.push_str("}\n");
+    dot
 }
 
 bitflags! {
@@ -523,6 +886,7 @@ bitflags! {
         const AWAITING_USER_ACTION = 512;
         const PAID = 1024;
         const REQUEST_CONFIRM = 2048;
+        const TERMINATED = 4096;
     }
 }
 
@@ -532,3 +896,163 @@ encoding_struct! {
         objects_correspondence: Option<String>,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::fixtures::{buyer, seller};
+
+    use super::*;
+
+    fn all_statuses() -> Vec<ContractStatus> {
+        let mut statuses = vec![
+            ContractStatus::New,
+            ContractStatus::Signed,
+            ContractStatus::ReadyForRegistering,
+            ContractStatus::Registering,
+            ContractStatus::AwaitingUserAction,
+            ContractStatus::Refused,
+            ContractStatus::Approved,
+            ContractStatus::Rejected,
+            ContractStatus::Terminated,
+        ];
+        for &buyer in &[false, true] {
+            for &seller in &[false, true] {
+                statuses.push(RequestConfirm { buyer, seller }.into());
+                statuses.push(ContractStatus::Draft(Draft { buyer, seller }));
+                statuses.push(ContractStatus::Confirmed(Confirmed { buyer, seller }));
+            }
+        }
+        statuses
+    }
+
+    fn all_actions() -> Vec<Action> {
+        vec![
+            Action::New,
+            Action::MakeDraft,
+            Action::Confirm(buyer()),
+            Action::Confirm(seller()),
+            Action::Unconfirm(buyer()),
+            Action::Unconfirm(seller()),
+            Action::Sign(buyer()),
+            Action::Sign(seller()),
+            Action::Update {
+                price: 100,
+                conditions: Conditions::default(),
+            },
+            Action::Refuse,
+            Action::Approve,
+            Action::Reject,
+            Action::Register,
+            Action::AwaitUserAction,
+            Action::ReadyForRegistering,
+            Action::Terminate,
+            Action::Expire,
+        ]
+    }
+
+    fn contract(status: ContractStatus) -> Contract {
+        let state = State::from(status).bits;
+        Contract::new(buyer(), seller(), 0, Conditions::default(), state, false)
+    }
+
+    #[test]
+    fn status_round_trips_through_state() {
+        for status in all_statuses() {
+            let state = State::from(status).bits;
+            assert_eq!(ContractStatus::try_from(state).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn every_stored_state_decodes() {
+        for bits in 0..=u16::max_value() {
+            if let Ok(status) = ContractStatus::try_from(bits) {
+                let state = State::from(status).bits;
+                assert_eq!(ContractStatus::try_from(state).unwrap(), status);
+            }
+        }
+    }
+
+    #[test]
+    fn transitions_follow_table() {
+        for status in all_statuses() {
+            for action in all_actions() {
+                let transition = find_transition(status.kind(), action.kind());
+                let result = contract(status).apply(action.clone());
+                match (transition, result) {
+                    (Some(t), Ok(next)) => {
+                        let next_status = ContractStatus::try_from(next.state()).unwrap();
+                        assert!(t.to.contains(&next_status.kind()), "{:?}", t);
+                        assert_eq!(State::from(next_status).bits, next.state());
+                    }
+                    (None, Ok(_)) => panic!("{} -> {:?} isn't in the table", status, action),
+                    (_, Err(_)) => {}
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_transition_is_reachable() {
+        for t in TRANSITIONS {
+            for &to in t.to {
+                let reached = all_statuses().into_iter().any(|status| {
+                    status.kind() == t.from
+                        && all_actions().into_iter().any(|action| {
+                            action.kind() == t.action
+                                && contract(status).apply(action).ok().map_or(false, |c| {
+                                    ContractStatus::try_from(c.state()).unwrap().kind() == to
+                                })
+                        })
+                });
+                assert!(reached, "{} -> {} by {}", t.from, to, t.action.name());
+            }
+        }
+    }
+
+    #[test]
+    fn awaited_actions() {
+        let draft = ContractStatus::Draft(Draft {
+            buyer: true,
+            seller: false,
+        });
+        assert_eq!(draft.awaited_action(&BuyerSeller::Buyer), None);
+        assert_eq!(
+            draft.awaited_action(&BuyerSeller::Seller),
+            Some(PendingAction::Confirm)
+        );
+        let confirmed = ContractStatus::Confirmed(Confirmed::new());
+        assert_eq!(
+            confirmed.awaited_action(&BuyerSeller::Buyer),
+            Some(PendingAction::Sign)
+        );
+        assert_eq!(
+            ContractStatus::AwaitingUserAction.awaited_action(&BuyerSeller::Seller),
+            Some(PendingAction::UserAction)
+        );
+        assert_eq!(
+            ContractStatus::Registering.awaited_action(&BuyerSeller::Buyer),
+            None
+        );
+    }
+
+    #[test]
+    fn replace_member_keeps_state() {
+        let successor = MemberIdentity::from_str("ogrn::1027700132195").unwrap();
+        let replaced = contract(ContractStatus::New).replace_member(&seller(), &successor);
+        assert_eq!(replaced.buyer(), buyer());
+        assert_eq!(replaced.seller(), successor);
+        assert_eq!(replaced.state(), contract(ContractStatus::New).state());
+    }
+
+    #[test]
+    fn transitions_export() {
+        let json = serde_json::to_value(TRANSITIONS).unwrap();
+        assert_eq!(json[0]["from"], "request_confirm");
+        assert_eq!(json[0]["action"], "confirm");
+        assert_eq!(json[0]["guard"], "party");
+        assert!(transitions_dot().contains("approved -> terminated [label=\"terminate\"];"));
+        let guarded = "request_confirm -> new [label=\"new [both_confirmed]\"];";
+        assert!(transitions_dot().contains(guarded));
+    }
+}
@@ -79,6 +79,18 @@ impl Lot {
     pub fn is_private_sale(&self) -> bool {
         SaleType::try_from(self.sale_type()) == Ok(SaleType::PrivateSale)
     }
+
+    pub fn with_seller(&self, seller: MemberIdentity) -> Self {
+        Lot::new(
+            self.name(),
+            self.desc(),
+            seller,
+            self.price(),
+            self.sale_type(),
+            self.opening_time(),
+            self.closing_time(),
+        )
+    }
 }
 
 encoding_struct! {
@@ -2,7 +2,11 @@ use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
-use blockp_core::crypto::{self, Hash};
+use chrono::{DateTime, Utc};
+use num_enum::TryFromPrimitive;
+use rand::{thread_rng, RngCore};
+
+use blockp_core::crypto::{self, Hash, PublicKey, SecretKey, Signature};
 use blockp_core::storage::StorageKey;
 
 use crate::error::Error;
@@ -15,6 +19,9 @@ pub enum MemberType {
     Ogrn = 0,
     Ogrnip = 1,
     Snils = 2,
+    /// Taxpayer number. An organisation number may be given with KPP: `{inn}:{kpp}`, KPP is
+    /// dropped, so the organisation is the same member with any KPP
+    Inn = 3,
 }
 
 impl TryFrom<u8> for MemberType {
@@ -25,6 +32,7 @@ impl TryFrom<u8> for MemberType {
             0 => Ok(MemberType::Ogrn),
             1 => Ok(MemberType::Ogrnip),
             2 => Ok(MemberType::Snils),
+            3 => Ok(MemberType::Inn),
             _ => Err(num),
         }
     }
@@ -36,6 +44,7 @@ impl fmt::Display for MemberType {
             MemberType::Ogrn => write!(f, "ogrn"),
             MemberType::Ogrnip => write!(f, "ogrnip"),
             MemberType::Snils => write!(f, "snils"),
+            MemberType::Inn => write!(f, "inn"),
         }
     }
 }
@@ -74,16 +83,34 @@ impl MemberIdentity {
             .hash()
     }
 
+    /// Organisations identified by OGRN or INN of an organisation
     pub fn is_legal_entity(&self) -> bool {
-        MemberType::try_from(self.class()) == Ok(MemberType::Ogrn)
+        match MemberType::try_from(self.class()) {
+            Ok(MemberType::Ogrn) => true,
+            Ok(MemberType::Inn) => self.inn().map_or(false, |inn| inn.len() == 10),
+            _ => false,
+        }
+    }
+
+    /// INN of a member identified by INN
+    pub fn inn(&self) -> Option<&str> {
+        if MemberType::try_from(self.class()) != Ok(MemberType::Inn) {
+            return None;
+        }
+        Some(self.number())
     }
 
     pub fn is_entrepreneur(&self) -> bool {
         MemberType::try_from(self.class()) == Ok(MemberType::Ogrnip)
     }
 
+    /// Individuals identified by SNILS or by INN of an individual
     pub fn is_person(&self) -> bool {
-        MemberType::try_from(self.class()) == Ok(MemberType::Snils)
+        match MemberType::try_from(self.class()) {
+            Ok(MemberType::Snils) => true,
+            Ok(MemberType::Inn) => self.inn().map_or(false, |inn| inn.len() == 12),
+            _ => false,
+        }
     }
 
     #[allow(unreachable_code)]
@@ -95,6 +122,7 @@ impl MemberIdentity {
             0 => self.is_valid_ogrn(),
             1 => self.is_valid_ogrnip(),
             2 => self.is_valid_snils(),
+            3 => is_valid_inn(self.number()),
             _ => false,
         }
     }
@@ -148,6 +176,61 @@ impl MemberIdentity {
             })
             .is_some()
     }
+
+}
+
+/// INN of the number given as `{inn}` or `{inn}:{kpp}`, `None` if KPP is malformed.
+/// KPP identifies the registration of the organisation or its branch at a tax office, it isn't
+/// a part of the member identity
+pub fn inn_without_kpp(number: &str) -> Option<&str> {
+    let mut parts = number.splitn(2, ':');
+    let inn = parts.next().unwrap_or_default();
+    match parts.next() {
+        None => Some(inn),
+        // KPP is assigned to organisations only
+        Some(kpp) if inn.len() == 10 && is_valid_kpp(kpp) => Some(inn),
+        Some(_) => None,
+    }
+}
+
+/// KPP: tax office code, reason code of digits or latin letters, serial number
+fn is_valid_kpp(kpp: &str) -> bool {
+    let kpp = kpp.as_bytes();
+    kpp.len() == 9
+        && kpp[..4].iter().all(u8::is_ascii_digit)
+        && kpp[4..6]
+            .iter()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+        && kpp[6..].iter().all(u8::is_ascii_digit)
+}
+
+/// Checks the length and the control digits of a taxpayer number (INN) of an organisation
+/// (10 digits) or a person (12 digits)
+pub fn is_valid_inn(inn: &str) -> bool {
+    const WEIGHTS: [u32; 11] = [3, 7, 2, 4, 10, 3, 5, 9, 4, 6, 8];
+
+    let digits = match inn
+        .chars()
+        .map(|c| c.to_digit(10))
+        .collect::<Option<Vec<u32>>>()
+    {
+        Some(digits) => digits,
+        None => return false,
+    };
+    // Control digit at `pos` is calculated with the last `pos` weights
+    let control_matches = |pos: usize| {
+        let sum: u32 = WEIGHTS[WEIGHTS.len() - pos..]
+            .iter()
+            .zip(&digits)
+            .map(|(w, d)| w * d)
+            .sum();
+        sum % 11 % 10 == digits[pos]
+    };
+    match digits.len() {
+        10 => control_matches(9),
+        12 => control_matches(10) && control_matches(11),
+        _ => false,
+    }
 }
 
 impl FromStr for MemberIdentity {
@@ -159,6 +242,9 @@ impl FromStr for MemberIdentity {
             &["ogrn", id] => Ok(MemberIdentity::new(MemberType::Ogrn as u8, id)),
             &["ogrnip", id] => Ok(MemberIdentity::new(MemberType::Ogrnip as u8, id)),
             &["snils", id] => Ok(MemberIdentity::new(MemberType::Snils as u8, id)),
+            &["inn", id] => inn_without_kpp(id)
+                .map(|inn| MemberIdentity::new(MemberType::Inn as u8, inn))
+                .ok_or_else(|| Error::bad_member_format(member_str)),
             _ => Error::bad_member_format(member_str).ok(),
         }
         .and_then(|identity| {
@@ -179,13 +265,122 @@ impl fmt::Display for MemberIdentity {
 }
 
 encoding_struct! {
+    /// ESIA token registered for the member, the token itself is kept in the node `CredentialStore`
     #[derive(Eq, Hash)]
     struct MemberEsiaToken {
+        fingerprint: &Hash,
+        expires_at: DateTime<Utc>,
+    }
+}
+
+encoding_struct! {
+    /// ESIA token kept in the state before the `CredentialStore`, read only to remove it
+    #[derive(Eq, Hash)]
+    struct DeprecatedMemberEsiaToken {
         token: &str,
         oid: &str,
     }
 }
 
+/// Grounds of the person to act on behalf of the member
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+pub enum Authority {
+    /// The member is the person
+    Person = 0,
+    /// The person has an ESIA role in the organisation allowing to act for it
+    Role = 1,
+    /// The person acts by a power of attorney of the organisation
+    PowerOfAttorney = 2,
+}
+
+encoding_struct! {
+    /// Confirmation by the `node` that the person with SNILS `person` passed ESIA validation
+    /// with the registered token of the member, valid till the block at height `valid_until`.
+    /// The random `nonce` makes every attestation unique, so it's accepted only once
+    struct EsiaAttestation {
+        member: MemberIdentity,
+        person: &str,
+        authority: u8,
+        token_fingerprint: &Hash,
+        valid_until: u64,
+        nonce: u64,
+        node: &PublicKey,
+        signature: &Signature,
+    }
+}
+
+impl EsiaAttestation {
+    pub fn issue(
+        member: MemberIdentity,
+        person: &str,
+        authority: Authority,
+        token_fingerprint: &Hash,
+        valid_until: u64,
+        node: &PublicKey,
+        secret_key: &SecretKey,
+    ) -> Self {
+        let nonce = thread_rng().next_u64();
+        let digest = Self::digest(
+            &member,
+            person,
+            authority as u8,
+            token_fingerprint,
+            valid_until,
+            nonce,
+            node,
+        );
+        let signature = crypto::sign(digest.as_ref(), secret_key);
+        Self::new(
+            member,
+            person,
+            authority as u8,
+            token_fingerprint,
+            valid_until,
+            nonce,
+            node,
+            &signature,
+        )
+    }
+
+    /// Signed digest of the attestation, identifies it among the accepted ones
+    pub fn id(&self) -> Hash {
+        Self::digest(
+            &self.member(),
+            self.person(),
+            self.authority(),
+            self.token_fingerprint(),
+            self.valid_until(),
+            self.nonce(),
+            self.node(),
+        )
+    }
+
+    pub fn verify_signature(&self) -> bool {
+        crypto::verify(self.signature(), self.id().as_ref(), self.node())
+    }
+
+    fn digest(
+        member: &MemberIdentity,
+        person: &str,
+        authority: u8,
+        token_fingerprint: &Hash,
+        valid_until: u64,
+        nonce: u64,
+        node: &PublicKey,
+    ) -> Hash {
+        crypto::HashStream::new()
+            .update(member.id().as_ref())
+            .update(crypto::hash(person.as_bytes()).as_ref())
+            .update(&[authority])
+            .update(token_fingerprint.as_ref())
+            .update(&valid_until.to_le_bytes())
+            .update(&nonce.to_le_bytes())
+            .update(node.as_ref())
+            .hash()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::fmt::Debug;
@@ -198,6 +393,7 @@ mod tests {
     fn storage_key_member_identity() {
         let members = [
             MemberIdentity::from_str("ogrn::1053600591197").unwrap(),
+            MemberIdentity::from_str("inn::7707083893:773601001").unwrap(),
             MemberIdentity::new(0, ""),
             MemberIdentity::new(0, "asdfjasdjfkj23904u9fjoadjfojf2940jufojadfjaspofjoaasdfjasdjfkj23904u9fjoadjfojf2940jufojadfjaspofjoaasdfjasdjfkj23904u9fjoadjfojf2940jufojadfjaspofjoaasdfjasdjfkj23904u9fjoadjfojf2940jufojadfjaspofjoaasdfjasdjfkj23904u9fjoadjfojf2940jufojadfjaspofjoaasdfjasdjfkj23904u9fjoadjfojf2940jufojadfjaspofjoa"),
         ];
@@ -233,4 +429,76 @@ mod tests {
             println!("{}", member.unwrap_err());
         };
     }
+
+    #[test]
+    fn parse_inn() {
+        let organisation = MemberIdentity::from_str("inn::7707083893").unwrap();
+        assert!(organisation.is_legal_entity());
+        assert_eq!(organisation.inn(), Some("7707083893"));
+        let branch = MemberIdentity::from_str("inn::7707083893:77360A001").unwrap();
+        assert_eq!(branch, organisation);
+        assert_eq!(branch.id(), organisation.id());
+        assert_eq!(branch.to_string(), "inn::7707083893");
+        let person = MemberIdentity::from_str("inn::500100732259").unwrap();
+        assert!(!person.is_legal_entity());
+        assert!(person.is_person());
+        assert!(!organisation.is_person());
+        assert!(!branch.is_person());
+
+        if !cfg!(feature = "disable_member_identity_validation") {
+            MemberIdentity::from_str("inn::500100732259:773601001").unwrap_err();
+            MemberIdentity::from_str("inn::7707083893:7736").unwrap_err();
+            MemberIdentity::from_str("foreign::DE:HRB-12345").unwrap_err();
+        }
+    }
+
+    #[test]
+    fn inn_control_digits() {
+        assert!(is_valid_inn("7707083893"));
+        assert!(is_valid_inn("500100732259"));
+        assert!(!is_valid_inn("7707083894"));
+        assert!(!is_valid_inn("500100732258"));
+        assert!(!is_valid_inn("77070838"));
+        assert!(!is_valid_inn("77070838a3"));
+    }
+
+    #[test]
+    fn esia_attestation_signature() {
+        let (public_key, secret_key) = crypto::gen_keypair();
+        let member = MemberIdentity::from_str("ogrn::1053600591197").unwrap();
+        let fingerprint = crypto::hash(b"token");
+        let attestation = EsiaAttestation::issue(
+            member.clone(),
+            "11223344595",
+            Authority::Role,
+            &fingerprint,
+            10,
+            &public_key,
+            &secret_key,
+        );
+        assert!(attestation.verify_signature());
+
+        let forged = EsiaAttestation::new(
+            member.clone(),
+            "11223344595",
+            Authority::PowerOfAttorney as u8,
+            &fingerprint,
+            1000,
+            attestation.nonce(),
+            &public_key,
+            attestation.signature(),
+        );
+        assert!(!forged.verify_signature());
+
+        let other = EsiaAttestation::issue(
+            member,
+            "11223344595",
+            Authority::Role,
+            &fingerprint,
+            10,
+            &public_key,
+            &secret_key,
+        );
+        assert_ne!(attestation.id(), other.id());
+    }
 }
@@ -1,4 +1,5 @@
 pub mod attachment;
+pub mod attorney;
 pub mod classifier;
 pub mod conditions;
 pub mod contract;
@@ -9,5 +10,6 @@ pub mod member;
 pub mod object;
 pub mod ownership;
 pub mod payment;
+pub mod registry;
 pub mod strings;
 pub mod time;
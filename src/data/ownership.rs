@@ -8,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use crate::error::Error;
 
 use super::classifier::Classifier;
-use super::conditions::ContractType;
+use super::conditions::{ContractType, ObjectOwnership};
 use super::location::Location;
 use super::member::MemberIdentity;
 use super::object::{ObjectIdentity, ObjectType};
@@ -63,6 +63,28 @@ encoding_struct! {
     }
 }
 
+encoding_struct! {
+    /// Change the approved contract made to the rights to the object: `granted` was given to the
+    /// buyer in place of `buyer_before` or as a `separate` record, `seller_before` is set if the
+    /// seller's rights were removed
+    struct RightsChange {
+        object: ObjectIdentity,
+        granted: Rights,
+        separate: bool,
+        buyer_before: Option<Rights>,
+        seller_before: Option<Rights>,
+    }
+}
+
+encoding_struct! {
+    /// Rights received by `rightholder` under an approved contract, kept apart from the
+    /// rightholder's other rights to the object
+    struct ContractRights {
+        rightholder: MemberIdentity,
+        rights: Rights,
+    }
+}
+
 #[repr(u8)]
 #[derive(Debug, Serialize, Deserialize, TryFromPrimitive, Eq, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -97,6 +119,48 @@ impl Rights {
         )
     }
 
+    /// Rights obtained by the buyer under an approved contract
+    pub fn from_contract(
+        ownership: &ObjectOwnership,
+        contract_type: u8,
+        starting_time: DateTime<Utc>,
+    ) -> Result<Rights, Error> {
+        let mut flags = Flag::empty();
+        if ownership.exclusive() {
+            flags.set(Flag::EXCLUSIVE, true);
+        }
+        match Distribution::try_from(ownership.can_distribute()) {
+            Ok(Distribution::Able) => flags.set(Flag::CAN_DISTRIBUTE, true),
+            Ok(Distribution::WithWrittenPermission) => {
+                flags.set(Flag::DISTRIBUTE_WITH_WRITTEN_PERMISSION, true)
+            }
+            Ok(Distribution::Unable) => (),
+            Err(e) => return Err(Error::internal_bad_struct(&e.to_string())),
+        }
+        let expiration_time = ownership.contract_term().expiration_time(starting_time)?;
+        Ok(Rights::new(
+            flags.bits(),
+            contract_type,
+            ownership.location(),
+            ownership.classifiers(),
+            starting_time,
+            expiration_time,
+        ))
+    }
+
+    /// Owner rights transferred to the buyer by expropriation
+    pub fn transfer_owned(&self, starting_time: DateTime<Utc>) -> Rights {
+        let flags = Flag::from_bits(self.flags()).unwrap_or(Flag::UNDEFINED) | Flag::OWNER;
+        Rights::new(
+            flags.bits(),
+            ContractType::Undefined as u8,
+            self.location(),
+            self.classifiers(),
+            starting_time,
+            self.expiration_time(),
+        )
+    }
+
     pub fn check_term(&self, object: &ObjectIdentity, term: Term) -> Result<i8, Error> {
         use ObjectType::*;
         let default_duration = match object
@@ -181,3 +245,44 @@ bitflags! {
         const OWNER = 128;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn ownership(can_distribute: u8) -> ObjectOwnership {
+        ObjectOwnership::new(
+            ObjectIdentity::from_str("trademark::123451").unwrap(),
+            Term::new(Specification::Forever as u8, None, None),
+            true,
+            can_distribute,
+            vec![Location::default()],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn rights_from_contract() {
+        let start = "2020-01-31T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let rights = Rights::from_contract(
+            &ownership(Distribution::WithWrittenPermission as u8),
+            ContractType::License as u8,
+            start,
+        )
+        .unwrap();
+        assert!(rights.is_exclusive());
+        assert!(rights.is_distribute_with_written_permission());
+        assert!(!rights.can_distribute());
+        assert!(!rights.is_owner());
+        assert_eq!(rights.starting_time(), start);
+        assert_eq!(rights.expiration_time(), None);
+    }
+
+    #[test]
+    fn rights_from_contract_with_bad_distribution() {
+        let rights = Rights::from_contract(&ownership(0), ContractType::License as u8, Utc::now());
+        assert!(rights.is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::convert::TryFrom;
+
+use num_enum::TryFromPrimitive;
+use serde::{Deserialize, Serialize};
+
+use blockp_core::crypto::{Hash, PublicKey};
+
+use crate::data::member::{is_valid_inn, MemberIdentity};
+use crate::data::strings::{verify_node_name, verify_str};
+use crate::error::{self, Error};
+
+/// Purpose of a participant key
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyType {
+    /// Node receiving private transactions and documents of the member
+    Node = 0,
+    /// Node which only attests persons acting for the member
+    Attestation = 1,
+    /// Node receiving the member data which also attests persons acting for the member.
+    /// Nodes added by `AddParticipant` act for the member, as they did before attestations
+    NodeAttestation = 2,
+}
+
+encoding_struct! {
+    /// Registry data of a member
+    struct MemberProfile {
+        name: &str,
+        inn: &str,
+        legal_address: &str,
+        postal_address: Option<String>,
+        email: Option<String>,
+        phone: Option<String>,
+    }
+}
+
+impl MemberProfile {
+    pub fn verify(&self) -> error::Result<()> {
+        if self.name().trim().is_empty() {
+            Error::empty_param("name").ok()?
+        }
+        if self.legal_address().trim().is_empty() {
+            Error::empty_param("legal_address").ok()?
+        }
+        verify_str(self.name(), "name")?;
+        verify_str(self.legal_address(), "legal_address")?;
+        if let Some(address) = self.postal_address() {
+            verify_str(&address, "postal_address")?;
+        }
+        if let Some(phone) = self.phone() {
+            verify_str(&phone, "phone")?;
+        }
+        if let Some(email) = self.email() {
+            verify_str(&email, "email")?;
+            if !email.contains('@') {
+                Error::unexpected_param_value("email").ok()?
+            }
+        }
+        if !is_valid_inn(self.inn()) {
+            Error::bad_inn(self.inn()).ok()?
+        }
+        Ok(())
+    }
+}
+
+encoding_struct! {
+    /// Public key of a node acting for the member, `name` is the name of the node certificate
+    struct ParticipantKey {
+        key: &PublicKey,
+        key_type: u8,
+        name: &str,
+    }
+}
+
+impl ParticipantKey {
+    pub fn verify(&self) -> error::Result<()> {
+        KeyType::try_from(self.key_type())
+            .map_err(|_| Error::unexpected_param_value("key_type"))?;
+        verify_node_name(self.name())
+    }
+
+    /// Checks that the node receives private data of the member
+    pub fn is_node(&self) -> bool {
+        self.key_type() == KeyType::Node as u8 || self.key_type() == KeyType::NodeAttestation as u8
+    }
+
+    /// Checks that the node may attest persons acting for the member
+    pub fn is_attestation(&self) -> bool {
+        self.key_type() == KeyType::Attestation as u8
+            || self.key_type() == KeyType::NodeAttestation as u8
+    }
+
+    /// The same key with another public key, used for key rotation
+    pub fn with_key(&self, key: &PublicKey) -> Self {
+        ParticipantKey::new(key, self.key_type(), self.name())
+    }
+}
+
+encoding_struct! {
+    /// Link of a member identity to the identity succeeding it, e.g. the OGRN of the company an
+    /// entrepreneur has incorporated. `migrated` is set once rights, lots and contracts of the
+    /// member are moved to the successor by the transaction `tx_hash`
+    struct MemberLink {
+        successor: MemberIdentity,
+        tx_hash: &Hash,
+        migrated: bool,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn profile(inn: &str, email: Option<&str>) -> MemberProfile {
+        MemberProfile::new(
+            "ООО Ромашка",
+            inn,
+            "г. Москва, ул. Ленина, д. 1",
+            None,
+            email.map(ToOwned::to_owned),
+            None,
+        )
+    }
+
+    #[test]
+    fn verify_profile() {
+        profile("7707083893", Some("info@example.ru"))
+            .verify()
+            .unwrap();
+        profile("7707083894", None).verify().unwrap_err();
+        profile("7707083893", Some("example.ru"))
+            .verify()
+            .unwrap_err();
+    }
+}
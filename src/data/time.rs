@@ -1,7 +1,8 @@
+use std::convert::TryFrom;
 use std::fmt;
 use std::str::FromStr;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use num_enum::TryFromPrimitive;
 use serde::{Deserialize, Serialize};
 
@@ -53,3 +54,61 @@ encoding_struct! {
         date: Option<DateTime<Utc>>,
     }
 }
+
+impl Term {
+    /// Calculates the end of the term starting at `starting_time`, `None` means indefinitely
+    pub fn expiration_time(
+        &self,
+        starting_time: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let specification = Specification::try_from(self.specification())
+            .map_err(|_| Error::internal_bad_struct("Term"))?;
+        match specification {
+            Specification::For => {
+                let duration = self
+                    .duration()
+                    .ok_or_else(|| Error::internal_bad_struct("Term"))?;
+                let time = add_months(starting_time, duration.months())
+                    + chrono::Duration::days(duration.days().into());
+                Ok(Some(time))
+            }
+            Specification::To | Specification::Until => self
+                .date()
+                .map(Some)
+                .ok_or_else(|| Error::internal_bad_struct("Term")),
+            Specification::Forever => Ok(None),
+        }
+    }
+}
+
+fn add_months(time: DateTime<Utc>, months: u16) -> DateTime<Utc> {
+    let naive = time.naive_utc();
+    let total = naive.year() * 12 + naive.month0() as i32 + i32::from(months);
+    let (year, month) = (total / 12, total % 12 + 1);
+    // Clamp the day to the last day of the target month
+    let date = (1..=naive.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month as u32, day))
+        .expect("month always has at least one day");
+    DateTime::from_utc(date.and_time(naive.time()), Utc)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn term_expiration_for_duration() {
+        let start = "2020-01-31T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let term = Term::new(Specification::For as u8, Some(Duration::new(13, 2)), None);
+        let expected = "2021-03-02T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(term.expiration_time(start).unwrap(), Some(expected));
+    }
+
+    #[test]
+    fn term_expiration_forever() {
+        let start = "2020-01-31T10:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let term = Term::new(Specification::Forever as u8, None, None);
+        assert_eq!(term.expiration_time(start).unwrap(), None);
+    }
+}
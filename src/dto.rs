@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::convert::{TryFrom, TryInto};
 use std::ops::Deref;
 use std::str::FromStr;
@@ -8,26 +9,34 @@ use serde::{Deserialize, Deserializer, Serialize};
 
 use blockp_core::blockchain::Transaction;
 use blockp_core::crypto::Hash;
+use blockp_core::crypto::PublicKey;
 use blockp_core::crypto::HASH_SIZE;
 use blockp_core::encoding::serialize::FromHex;
 
 use crate::data::attachment::{
-    Attachment, AttachmentMetadata, AttachmentMetadataWithHash, AttachmentType, DocumentId, Sign,
+    Attachment, AttachmentMetadata, AttachmentMetadataWithHash, AttachmentType, DocumentId,
+    ScanVerdict, Sign,
 };
+use crate::data::attorney::{PowerOfAttorney, Powers};
 use crate::data::classifier::Classifier;
 use crate::data::conditions::{
     CheckResult, Conditions, ContractType, ExtraCondition, ObjectOwnership, TerminationCondition,
 };
-use crate::data::contract::{ContractId, RequestConfirm};
+use crate::data::contract::{
+    BuyerSeller, ContractId, ContractRevision, ContractStatus, ContractTransition, PendingAction,
+    RequestConfirm, Transition,
+};
 use crate::data::cost::Cost;
 use crate::data::location::Location;
 use crate::data::lot::{Lot, LotId, LotStatus, SaleType};
 #[cfg(feature = "internal_api")]
 use crate::data::member::MemberEsiaToken;
 use crate::data::member::MemberIdentity;
+use crate::data::member::{inn_without_kpp, MemberType};
 use crate::data::object::ObjectIdentity;
 use crate::data::ownership::{Distribution, Ownership, OwnershipUnstructured, Rights};
 use crate::data::payment::{Calculation, PaymentDetail, PaymentStatus};
+use crate::data::registry::{KeyType, MemberLink, MemberProfile, ParticipantKey};
 use crate::data::time::{Duration, Specification, Term};
 use crate::error::{Error, Result};
 
@@ -77,7 +86,12 @@ impl<'de> Deserialize<'de> for MemberInfo {
 
 impl From<MemberInfo> for MemberIdentity {
     fn from(v: MemberInfo) -> MemberIdentity {
-        MemberIdentity::new(v.class, &v.number)
+        // Malformed numbers are kept to be rejected by the validation
+        let number = match MemberType::try_from(v.class) {
+            Ok(MemberType::Inn) => inn_without_kpp(&v.number).unwrap_or(&v.number),
+            _ => &v.number,
+        };
+        MemberIdentity::new(v.class, number)
     }
 }
 
@@ -482,6 +496,10 @@ impl ObjectOwnershipInfo {
     pub fn is_exclusive(&self) -> bool {
         self.exclusive
     }
+
+    fn identity(&self) -> String {
+        ObjectIdentity::from(self.object.clone()).to_string()
+    }
 }
 
 fn vec_default_on_null<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
@@ -742,6 +760,204 @@ pub struct ContractInfo {
     pub is_undefined: bool,
     pub contract_correspondence: Option<String>,
     pub objects_correspondence: Option<String>,
+    pub refuse_reason: Option<String>,
+    /// Objects whose rights weren't restored on termination
+    pub unrestored_rights: Vec<ObjectIdentityDto>,
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct ContractDeadlineInfo {
+    pub contract_tx_hash: ContractId,
+    pub status: String,
+    pub since: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct PendingActionInfo {
+    pub contract_tx_hash: ContractId,
+    pub action: PendingAction,
+    pub party: BuyerSeller,
+    pub status: String,
+    /// Time the contract expires in its status, if the status has a deadline
+    pub deadline: Option<DateTime<Utc>>,
+}
+
+/// Numbers of awaited actions for UI badges
+#[derive(Serialize, Debug, Default, Eq, PartialEq)]
+pub struct InboxCounts {
+    pub total: usize,
+    pub confirm: usize,
+    pub sign: usize,
+    pub user_action: usize,
+    pub pay: usize,
+}
+
+impl InboxCounts {
+    pub fn new(actions: &[PendingActionInfo]) -> Self {
+        let mut counts = InboxCounts {
+            total: actions.len(),
+            ..Default::default()
+        };
+        for info in actions {
+            match info.action {
+                PendingAction::Confirm => counts.confirm += 1,
+                PendingAction::Sign => counts.sign += 1,
+                PendingAction::UserAction => counts.user_action += 1,
+                PendingAction::Pay => counts.pay += 1,
+            }
+        }
+        counts
+    }
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct OutstandingPaymentInfo {
+    pub contract_tx_hash: ContractId,
+    #[serde(flatten)]
+    pub payment: CalculationWithPaymentDetailInfo,
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct ContractTransitionInfo {
+    /// `None` for the entry recorded when the contract was opened
+    pub from: Option<String>,
+    pub to: String,
+    pub action: String,
+    pub actor: Option<MemberInfo>,
+    pub tx_hash: Hash,
+    pub height: u64,
+}
+
+impl TryFrom<ContractTransition> for ContractTransitionInfo {
+    type Error = Error;
+
+    fn try_from(transition: ContractTransition) -> Result<Self> {
+        Ok(Self {
+            from: if transition.is_open() {
+                None
+            } else {
+                Some(ContractStatus::try_from(transition.from())?.to_string())
+            },
+            to: ContractStatus::try_from(transition.to())?.to_string(),
+            action: transition.action().to_string(),
+            actor: transition.actor().map(Into::into),
+            tx_hash: *transition.tx_hash(),
+            height: transition.height(),
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum ContractTransitionsDto {
+    Table(&'static [Transition]),
+    Dot(String),
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct ContractRevisionInfo {
+    pub number: u64,
+    pub proposer: MemberInfo,
+    pub tx_hash: Hash,
+    pub height: Option<u64>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub price: u64,
+    pub conditions: ConditionsInfo,
+    pub changes: Vec<TermsChangeInfo>,
+}
+
+impl ContractRevisionInfo {
+    pub fn new(
+        revision: ContractRevision,
+        previous: Option<ContractRevision>,
+        height: Option<u64>,
+    ) -> Result<Self> {
+        let conditions = ConditionsInfo::try_from(revision.conditions())?;
+        let changes = match previous {
+            Some(previous) => {
+                let previous_conditions = ConditionsInfo::try_from(previous.conditions())?;
+                terms_diff(
+                    Some((previous.price(), &previous_conditions)),
+                    (revision.price(), &conditions),
+                )?
+            }
+            None => terms_diff(None, (revision.price(), &conditions))?,
+        };
+        Ok(Self {
+            number: revision.number(),
+            proposer: revision.proposer().into(),
+            tx_hash: *revision.tx_hash(),
+            height,
+            timestamp: revision.timestamp(),
+            price: revision.price(),
+            conditions,
+            changes,
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Eq, PartialEq)]
+pub struct TermsChangeInfo {
+    pub field: String,
+    pub old: Option<serde_json::Value>,
+    pub new: Option<serde_json::Value>,
+}
+
+const CONDITIONS_DIFF_FIELDS: [&str; 5] = [
+    "contract_type",
+    "payment_conditions",
+    "payment_comment",
+    "termination_conditions",
+    "contract_extras",
+];
+
+// Objects are compared one by one, the field name contains the object identity
+fn terms_diff(
+    old: Option<(u64, &ConditionsInfo)>,
+    new: (u64, &ConditionsInfo),
+) -> Result<Vec<TermsChangeInfo>> {
+    let mut changes = Vec::new();
+    let mut push_change = |field: String, old: Option<serde_json::Value>, new| {
+        if old != new {
+            changes.push(TermsChangeInfo { field, old, new })
+        }
+    };
+
+    let (new_price, new_conditions) = new;
+    push_change(
+        "price".to_string(),
+        old.map(|(price, _)| price.into()),
+        Some(new_price.into()),
+    );
+
+    let old_value = old
+        .map(|(_, conditions)| serde_json::to_value(conditions))
+        .transpose()?;
+    let new_value = serde_json::to_value(new_conditions)?;
+    for field in CONDITIONS_DIFF_FIELDS.iter() {
+        push_change(
+            field.to_string(),
+            old_value.as_ref().and_then(|v| v.get(field)).cloned(),
+            new_value.get(field).cloned(),
+        );
+    }
+
+    let mut objects: BTreeMap<String, (Option<serde_json::Value>, Option<serde_json::Value>)> =
+        BTreeMap::new();
+    if let Some((_, old_conditions)) = old {
+        for object in old_conditions.objects.iter() {
+            objects.entry(object.identity()).or_default().0 = Some(serde_json::to_value(object)?);
+        }
+    }
+    for object in new_conditions.objects.iter() {
+        objects.entry(object.identity()).or_default().1 = Some(serde_json::to_value(object)?);
+    }
+    for (identity, (old, new)) in objects.into_iter() {
+        push_change(format!("objects.{}", identity), old, new);
+    }
+
+    Ok(changes)
 }
 
 #[derive(Serialize, Debug, Eq, PartialEq)]
@@ -907,6 +1123,7 @@ pub struct AttachmentMetadataDto {
     description: Option<String>,
     file_type: AttachmentType,
     timestamp: DateTime<Utc>,
+    pub scan_verdict: ScanVerdict,
 }
 
 impl TryFrom<AttachmentMetadata> for AttachmentMetadataDto {
@@ -918,6 +1135,7 @@ impl TryFrom<AttachmentMetadata> for AttachmentMetadataDto {
             description: v.description(),
             file_type: AttachmentType::try_from(v.file_type())?,
             timestamp: v.timestamp(),
+            scan_verdict: ScanVerdict::NotScanned,
         })
     }
 }
@@ -947,8 +1165,9 @@ impl TryFrom<AttachmentMetadataWithHash> for AttachmentMetadataWithHashDto {
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct AttachmentDto {
-    metadata: AttachmentMetadataDto,
-    data: String,
+    pub metadata: AttachmentMetadataDto,
+    hash: Hash,
+    pub encrypted: bool,
     sign: Option<SignInfo>,
     pub buyer_sign: Option<SignInfo>,
     pub seller_sign: Option<SignInfo>,
@@ -960,7 +1179,8 @@ impl TryFrom<Attachment> for AttachmentDto {
     fn try_from(v: Attachment) -> Result<Self> {
         Ok(AttachmentDto {
             metadata: v.metadata().try_into()?,
-            data: base64::encode(v.data()),
+            hash: *v.hash(),
+            encrypted: false,
             sign: v.sign().map(Into::into),
             buyer_sign: None,
             seller_sign: None,
@@ -990,6 +1210,193 @@ impl TryFrom<Attachment> for AttachmentDto {
 //     }
 // }
 
+/// Version of a contract document, signatures are valid only for the current version
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct DocumentVersionInfo {
+    number: u64,
+    doc_tx_hash: DocumentId,
+    current: bool,
+    attachment: AttachmentDto,
+}
+
+impl DocumentVersionInfo {
+    pub fn new(
+        number: u64,
+        doc_tx_hash: DocumentId,
+        current: bool,
+        attachment: AttachmentDto,
+    ) -> Self {
+        DocumentVersionInfo {
+            number,
+            doc_tx_hash,
+            current,
+            attachment,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MemberProfileInfo {
+    name: String,
+    inn: String,
+    legal_address: String,
+    #[serde(default)]
+    postal_address: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    phone: Option<String>,
+}
+
+impl From<MemberProfileInfo> for MemberProfile {
+    fn from(v: MemberProfileInfo) -> Self {
+        MemberProfile::new(
+            &v.name,
+            &v.inn,
+            &v.legal_address,
+            v.postal_address,
+            v.email,
+            v.phone,
+        )
+    }
+}
+
+impl From<MemberProfile> for MemberProfileInfo {
+    fn from(v: MemberProfile) -> Self {
+        MemberProfileInfo {
+            name: v.name().to_owned(),
+            inn: v.inn().to_owned(),
+            legal_address: v.legal_address().to_owned(),
+            postal_address: v.postal_address(),
+            email: v.email(),
+            phone: v.phone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParticipantKeyInfo {
+    key: PublicKey,
+    key_type: KeyType,
+    name: String,
+}
+
+impl From<ParticipantKeyInfo> for ParticipantKey {
+    fn from(v: ParticipantKeyInfo) -> Self {
+        ParticipantKey::new(&v.key, v.key_type as u8, &v.name)
+    }
+}
+
+impl TryFrom<ParticipantKey> for ParticipantKeyInfo {
+    type Error = Error;
+    fn try_from(v: ParticipantKey) -> Result<Self> {
+        Ok(ParticipantKeyInfo {
+            key: *v.key(),
+            key_type: KeyType::try_from(v.key_type())
+                .map_err(|_| Error::internal_bad_struct("key_type"))?,
+            name: v.name().to_owned(),
+        })
+    }
+}
+
+/// Registry entry of a member, members added before the registry have no profile
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct MemberRegistryDto {
+    member: MemberInfo,
+    profile: Option<MemberProfileInfo>,
+    keys: Vec<ParticipantKeyInfo>,
+    link: Option<MemberLinkInfo>,
+}
+
+impl MemberRegistryDto {
+    pub fn new(
+        member: MemberInfo,
+        profile: Option<MemberProfileInfo>,
+        keys: Vec<ParticipantKeyInfo>,
+        link: Option<MemberLinkInfo>,
+    ) -> Self {
+        MemberRegistryDto {
+            member,
+            profile,
+            keys,
+            link,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct MemberLinkInfo {
+    successor: MemberInfo,
+    tx_hash: Hash,
+    migrated: bool,
+}
+
+impl From<MemberLink> for MemberLinkInfo {
+    fn from(v: MemberLink) -> Self {
+        MemberLinkInfo {
+            successor: v.successor().into(),
+            tx_hash: *v.tx_hash(),
+            migrated: v.migrated(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct MemberObjectInfo {
+    pub object: ObjectIdentityDto,
+    pub ownership: StructuredOwnershipInfo,
+}
+
+/// Dashboard of a member: objects with its rights, active lots, contracts by status and the
+/// actions and payments the contracts wait for
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct MemberSummaryDto {
+    pub member: MemberInfo,
+    pub objects: Vec<MemberObjectInfo>,
+    pub lots: Vec<HashWrapperDto<LotInfoWithObjects>>,
+    pub contracts: BTreeMap<String, Vec<ContractId>>,
+    pub pending_actions: Vec<PendingActionInfo>,
+    pub outstanding_payments: Vec<OutstandingPaymentInfo>,
+}
+
+/// Actions the contracts of a member wait for from it
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct MemberInboxDto {
+    pub member: MemberInfo,
+    pub counts: InboxCounts,
+    pub actions: Vec<PendingActionInfo>,
+    pub payments: Vec<OutstandingPaymentInfo>,
+}
+
+/// Power of attorney identified by the hash of the transaction it was added with
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct PowerOfAttorneyDto {
+    tx_hash: Hash,
+    representative: String,
+    powers: Vec<&'static str>,
+    valid_from: DateTime<Utc>,
+    valid_until: DateTime<Utc>,
+    revoked: bool,
+    document: AttachmentDto,
+}
+
+impl TryFrom<(Hash, PowerOfAttorney, ScanVerdict)> for PowerOfAttorneyDto {
+    type Error = Error;
+    fn try_from((tx_hash, v, scan_verdict): (Hash, PowerOfAttorney, ScanVerdict)) -> Result<Self> {
+        let mut document: AttachmentDto = v.document().try_into()?;
+        document.metadata.scan_verdict = scan_verdict;
+        Ok(PowerOfAttorneyDto {
+            tx_hash,
+            representative: v.representative().to_owned(),
+            powers: Powers::from_bits_truncate(v.powers()).names(),
+            valid_from: v.valid_from(),
+            valid_until: v.valid_until(),
+            revoked: v.revoked(),
+            document,
+        })
+    }
+}
+
 #[derive(Serialize, Debug, PartialEq, Eq)]
 pub struct HashWrapperDto<T: Serialize> {
     #[serde(flatten)]
@@ -1009,16 +1416,16 @@ impl<T: Serialize> HashWrapperDto<T> {
 #[cfg(feature = "internal_api")]
 #[derive(Serialize, Debug, PartialEq, Eq)]
 pub struct MemberEsiaTokenDto {
-    token: String,
-    oid: String,
+    fingerprint: Hash,
+    expires_at: DateTime<Utc>,
 }
 
 #[cfg(feature = "internal_api")]
 impl From<MemberEsiaToken> for MemberEsiaTokenDto {
     fn from(value: MemberEsiaToken) -> Self {
         Self {
-            token: value.token().to_string(),
-            oid: value.oid().to_string(),
+            fingerprint: *value.fingerprint(),
+            expires_at: value.expires_at(),
         }
     }
 }
@@ -1669,4 +2076,108 @@ pub(crate) mod test {
         };
         let _val: CalculationInfo = serde_json::from_str(json).unwrap();
     }
+
+    #[test]
+    fn terms_diff_initial_revision() {
+        let conditions = new_conditions_info();
+        let changes = terms_diff(None, (50000, &conditions)).unwrap();
+        let fields = changes
+            .iter()
+            .map(|c| c.field.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            fields,
+            vec![
+                "price",
+                "contract_type",
+                "payment_conditions",
+                "payment_comment",
+                "termination_conditions",
+                "contract_extras",
+                "objects.trademark::123451",
+            ]
+        );
+        assert!(changes.iter().all(|c| c.old.is_none()));
+    }
+
+    #[test]
+    fn terms_diff_changed_conditions() {
+        let old = new_conditions_info();
+        let mut new = new_conditions_info();
+        new.payment_conditions = "New condition desc text".to_string();
+        new.objects[0].exclusive = true;
+
+        let changes = terms_diff(Some((50000, &old)), (50000, &new)).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].field, "payment_conditions");
+        assert_eq!(
+            changes[0].old,
+            Some(serde_json::Value::from("Condition desc text"))
+        );
+        assert_eq!(
+            changes[0].new,
+            Some(serde_json::Value::from("New condition desc text"))
+        );
+        assert_eq!(changes[1].field, "objects.trademark::123451");
+
+        let unchanged = terms_diff(Some((50000, &old)), (50000, &old)).unwrap();
+        assert!(unchanged.is_empty());
+    }
+
+    #[test]
+    fn inbox_counts() {
+        let action = |action| PendingActionInfo {
+            contract_tx_hash: Hash::zero(),
+            action,
+            party: BuyerSeller::Buyer,
+            status: "confirmed".to_owned(),
+            deadline: None,
+        };
+        let actions = vec![
+            action(PendingAction::Sign),
+            action(PendingAction::Pay),
+            action(PendingAction::Sign),
+        ];
+        let counts = InboxCounts::new(&actions);
+        assert_eq!(
+            counts,
+            InboxCounts {
+                total: 3,
+                sign: 2,
+                pay: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn contract_transition_info() {
+        use crate::data::contract::{Action, Contract, OPEN_ACTION};
+
+        let buyer = MemberIdentity::from_str("ogrn::1053600591197").unwrap();
+        let seller = MemberIdentity::from_str("ogrnip::304500116000157").unwrap();
+        let conditions = Conditions::new(1, vec![], "", "", vec![], vec![]);
+        let contract = Contract::buy(buyer.clone(), seller, 100, conditions);
+        let opened = contract.state();
+        let open = ContractTransition::new(
+            opened,
+            opened,
+            OPEN_ACTION,
+            Some(buyer.clone()),
+            &Hash::zero(),
+            1,
+        );
+        let info = ContractTransitionInfo::try_from(open).unwrap();
+        assert_eq!(info.from, None);
+        assert_eq!(info.to, "request_confirm");
+        assert_eq!(info.action, "open");
+        assert_eq!(info.actor, Some(buyer.into()));
+
+        let refused = contract.apply(Action::Expire).unwrap().state();
+        let expire = ContractTransition::new(opened, refused, "expire", None, &Hash::zero(), 2);
+        let info = ContractTransitionInfo::try_from(expire).unwrap();
+        assert_eq!(info.from, Some("request_confirm".to_owned()));
+        assert_eq!(info.to, "refused");
+        assert_eq!(info.actor, None);
+    }
 }
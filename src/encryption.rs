@@ -0,0 +1,157 @@
+//! Per-document encryption of attachment data.
+//!
+//! The data is encrypted with a random document key, and the key is encrypted for every
+//! node the document is shared with. Node keys are converted from Ed25519 to Curve25519.
+//!
+//! Encrypted data is kept in the `FileStore` with the wrapped keys:
+//! `MAGIC | key count: u16 | (node: 32 bytes | key length: u16 | key)* | nonce | data`,
+//! so the attachment refers to it by hash the same way as to the plain data.
+
+use std::convert::TryFrom;
+
+use sodiumoxide::crypto::box_;
+use sodiumoxide::crypto::sealedbox;
+use sodiumoxide::crypto::secretbox;
+use sodiumoxide::crypto::sign::ed25519;
+
+use blockp_core::crypto::{PublicKey, SecretKey, PUBLIC_KEY_LENGTH};
+
+use crate::error::{Error, Result};
+
+/// Prefix of the encrypted data
+pub const MAGIC: &[u8] = b"FIPSENC1";
+
+/// Checks if the stored data is encrypted
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts the data and wraps the document key for every node of `share`
+pub fn encrypt(data: &[u8], share: &[PublicKey]) -> Result<Vec<u8>> {
+    let key = secretbox::gen_key();
+    let nonce = secretbox::gen_nonce();
+    let count = u16::try_from(share.len())
+        .map_err(|_| Error::bad_encryption("too many nodes to share the document with"))?;
+    let mut encrypted = MAGIC.to_vec();
+    encrypted.extend_from_slice(&count.to_be_bytes());
+    for node in share {
+        let wrapped = sealedbox::seal(key.as_ref(), &curve_public_key(node)?);
+        encrypted.extend_from_slice(node.as_ref());
+        encrypted.extend_from_slice(&(wrapped.len() as u16).to_be_bytes());
+        encrypted.extend(wrapped);
+    }
+    encrypted.extend_from_slice(nonce.as_ref());
+    encrypted.extend(secretbox::seal(data, &nonce, &key));
+    Ok(encrypted)
+}
+
+/// Decrypts the data with the document key wrapped for the node
+pub fn decrypt(data: &[u8], public_key: &PublicKey, secret_key: &SecretKey) -> Result<Vec<u8>> {
+    if !is_encrypted(data) {
+        Error::bad_encryption("file isn't encrypted").ok()?
+    }
+    let mut reader = Reader(&data[MAGIC.len()..]);
+    let count = reader.read_u16()?;
+    let mut wrapped = None;
+    for _ in 0..count {
+        let node = reader.read(PUBLIC_KEY_LENGTH)?;
+        let len = reader.read_u16()?;
+        let key = reader.read(len as usize)?;
+        if node == public_key.as_ref() {
+            wrapped = Some(key);
+        }
+    }
+    let wrapped = wrapped.ok_or_else(|| Error::no_document_key(public_key))?;
+    let key = sealedbox::open(
+        wrapped,
+        &curve_public_key(public_key)?,
+        &curve_secret_key(secret_key)?,
+    )
+    .ok()
+    .and_then(|key| secretbox::Key::from_slice(&key))
+    .ok_or_else(|| Error::bad_encryption("document key can't be decrypted"))?;
+    let nonce = reader.read(secretbox::NONCEBYTES)?;
+    let nonce =
+        secretbox::Nonce::from_slice(nonce).ok_or_else(|| Error::bad_encryption("bad nonce"))?;
+    secretbox::open(reader.0, &nonce, &key)
+        .map_err(|_| Error::bad_encryption("data can't be decrypted"))
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            Error::bad_encryption("encrypted data is too short").ok()?
+        }
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        let value = self.read(2)?;
+        Ok(u16::from_be_bytes([value[0], value[1]]))
+    }
+}
+
+fn curve_public_key(key: &PublicKey) -> Result<box_::PublicKey> {
+    ed25519::PublicKey::from_slice(key.as_ref())
+        .and_then(|key| ed25519::to_curve25519_pk(&key).ok())
+        .and_then(|key| box_::PublicKey::from_slice(&key.0))
+        .ok_or_else(|| Error::bad_encryption("node public key can't be used for encryption"))
+}
+
+fn curve_secret_key(key: &SecretKey) -> Result<box_::SecretKey> {
+    ed25519::SecretKey::from_slice(key.as_ref())
+        .and_then(|key| ed25519::to_curve25519_sk(&key).ok())
+        .and_then(|key| box_::SecretKey::from_slice(&key.0))
+        .ok_or_else(|| Error::bad_encryption("node secret key can't be used for encryption"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use blockp_core::crypto::gen_keypair;
+
+    #[test]
+    fn decrypt_by_share_nodes_only() {
+        let (buyer_pk, buyer_sk) = gen_keypair();
+        let (seller_pk, seller_sk) = gen_keypair();
+        let (other_pk, other_sk) = gen_keypair();
+        let encrypted = encrypt(b"deed", &[buyer_pk, seller_pk]).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!is_encrypted(b"deed"));
+        assert_eq!(
+            decrypt(&encrypted, &buyer_pk, &buyer_sk).unwrap(),
+            b"deed".to_vec()
+        );
+        assert_eq!(
+            decrypt(&encrypted, &seller_pk, &seller_sk).unwrap(),
+            b"deed".to_vec()
+        );
+        decrypt(&encrypted, &other_pk, &other_sk).unwrap_err();
+    }
+
+    #[test]
+    fn wrong_key_fails() {
+        let (buyer_pk, _) = gen_keypair();
+        let (_, other_sk) = gen_keypair();
+        let encrypted = encrypt(b"deed", &[buyer_pk]).unwrap();
+        decrypt(&encrypted, &buyer_pk, &other_sk).unwrap_err();
+    }
+
+    #[test]
+    fn truncated_data_fails() {
+        let (buyer_pk, buyer_sk) = gen_keypair();
+        let encrypted = encrypt(b"deed", &[buyer_pk]).unwrap();
+        for len in &[
+            MAGIC.len(),
+            MAGIC.len() + 1,
+            MAGIC.len() + 2 + PUBLIC_KEY_LENGTH,
+        ] {
+            decrypt(&encrypted[..*len], &buyer_pk, &buyer_sk).unwrap_err();
+        }
+        decrypt(b"deed", &buyer_pk, &buyer_sk).unwrap_err();
+    }
+}
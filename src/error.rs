@@ -11,7 +11,7 @@ use num_enum::TryFromPrimitive;
 use serde::{Serialize, Serializer};
 
 use blockp_core::api::backends::actix::FutureResponse;
-use blockp_core::crypto::Hash;
+use blockp_core::crypto::{Hash, PublicKey};
 
 use crate::data::attachment::DocumentId;
 use crate::data::conditions::{Check, Conditions};
@@ -72,6 +72,9 @@ enum Code {
     #[fail(display = "Error while requesting external resource(for instance ESIA)")]
     ExternalRequest = 16,
 
+    #[fail(display = "Esia token expired, log in again")]
+    EsiaTokenExpired = 17,
+
     #[fail(display = "Other error")]
     Other = 255, // do not use in transactions
 }
@@ -94,11 +97,92 @@ impl Error {
         Error::with_info(Code::AlreadyExists, desc)
     }
 
+    pub fn member_already_registered(member: &MemberIdentity) -> Self {
+        let desc = format!("member is already registered '{}'", member);
+        Error::with_info(Code::AlreadyExists, desc)
+    }
+
+    pub fn no_member_profile(member: &MemberIdentity) -> Self {
+        let desc = format!("member isn't registered '{}'", member);
+        Error::with_info(Code::NotFound, desc)
+    }
+
+    pub fn participant_key_exists(member: &MemberIdentity, key: &PublicKey) -> Self {
+        let desc = format!("key '{}' is already registered for '{}'", key, member);
+        Error::with_info(Code::AlreadyExists, desc)
+    }
+
+    pub fn no_participant_key(member: &MemberIdentity, key: &PublicKey) -> Self {
+        let desc = format!("key '{}' isn't registered for '{}'", key, member);
+        Error::with_info(Code::NotFound, desc)
+    }
+
+    pub fn member_linked(member: &MemberIdentity, successor: &MemberIdentity) -> Self {
+        let desc = format!("member '{}' is already linked to '{}'", member, successor);
+        Error::with_info(Code::BadState, desc)
+    }
+
+    pub fn member_link_conflict(successor: &MemberIdentity, participation: &str) -> Self {
+        let desc = format!(
+            "successor '{}' already participates in {}",
+            successor, participation
+        );
+        Error::with_info(Code::BadState, desc)
+    }
+
+    pub fn bad_inn(inn: &str) -> Self {
+        let desc = format!("invalid INN '{}'", inn);
+        Error::with_info(Code::BadValue, desc)
+    }
+
     pub fn no_attachment(doc_tx_hash: &DocumentId) -> Self {
         let desc = format!("attachment wasn't found '{}'", doc_tx_hash);
         Error::with_info(Code::NotFound, desc)
     }
 
+    pub fn no_file_data(hash: &Hash) -> Self {
+        let desc = format!("file data wasn't found '{}'", hash);
+        Error::with_info(Code::NotFound, desc)
+    }
+
+    pub fn no_document_key(node: &PublicKey) -> Self {
+        let desc = format!("document key wasn't found for node '{}'", node);
+        Error::with_info(Code::NotFound, desc)
+    }
+
+    pub fn bad_encryption(info: &str) -> Self {
+        let desc = format!("encryption error: {}", info);
+        Error::with_info(Code::Crypto, desc)
+    }
+
+    pub fn encrypted_document(doc_tx_hash: &DocumentId) -> Self {
+        let desc = format!("document '{}' is encrypted", doc_tx_hash);
+        Error::with_info(Code::PermissionDenied, desc)
+    }
+
+    pub fn rejected_file(name: &str, reason: &str) -> Self {
+        let desc = format!("file '{}' was rejected: {}", name, reason);
+        Error::with_info(Code::BadValue, desc)
+    }
+
+    pub fn scan_failed(info: &str) -> Self {
+        let desc = format!("unable to scan file: {}", info);
+        Error::with_info(Code::Internal, desc)
+    }
+
+    pub fn bad_file_data(hash: &Hash) -> Self {
+        let desc = format!("file data doesn't match hash '{}'", hash);
+        Error::with_info(Code::BadValue, desc)
+    }
+
+    pub fn signed_document_mismatch(doc_tx_hash: &DocumentId) -> Self {
+        let desc = format!(
+            "signed data doesn't match the current version of document '{}'",
+            doc_tx_hash
+        );
+        Error::with_info(Code::BadState, desc)
+    }
+
     pub fn no_member_token() -> Self {
         let desc = format!("Token not found");
         Error::with_info(Code::NotFound, desc)
@@ -201,6 +285,11 @@ impl Error {
         Error::with_info(Code::BadValue, desc)
     }
 
+    pub fn bad_termination_reason(reason: &str) -> Self {
+        let desc = format!("bad termination reason '{}'", reason);
+        Error::with_info(Code::BadValue, desc)
+    }
+
     pub fn bad_classifier_format(classifier: &str) -> Self {
         let desc = format!("bad classifier '{}'", classifier);
         Error::with_info(Code::BadValue, desc)
@@ -298,6 +387,14 @@ impl Error {
         Error::with_info(Code::NotFound, desc)
     }
 
+    pub fn no_contract_revision(contract_id: &ContractId, number: u64) -> Self {
+        let desc = format!(
+            "revision '{}' of contract '{}' wasn't found",
+            number, contract_id
+        );
+        Error::with_info(Code::NotFound, desc)
+    }
+
     pub fn bad_contract_state(status: ContractStatus, action: &str) -> Self {
         let desc = format!("bad contract state '{:?}' for action '{}'", status, action);
         Error::with_info(Code::BadState, desc)
@@ -313,8 +410,23 @@ impl Error {
         Error::with_info(Code::BadValue, desc)
     }
 
-    pub fn bad_content_type() -> Self {
-        let desc = format!("Content type error. Check parameters sent");
+    pub fn bad_content_type(info: &str) -> Self {
+        let desc = format!("Content type error. {}", info);
+        Error::with_info(Code::BadParam, desc)
+    }
+
+    pub fn bad_multipart_field(info: &str) -> Self {
+        let desc = format!("bad multipart field: {}", info);
+        Error::with_info(Code::BadParam, desc)
+    }
+
+    pub fn too_large_param(name: &str, limit: usize) -> Self {
+        let desc = format!("parameter '{}' is larger than {} bytes", name, limit);
+        Error::with_info(Code::BadParam, desc)
+    }
+
+    pub fn too_large_request(limit: usize) -> Self {
+        let desc = format!("request is larger than {} bytes", limit);
         Error::with_info(Code::BadParam, desc)
     }
 
@@ -462,6 +574,70 @@ impl Error {
         Error::with_info(Code::Esia, desc.to_owned())
     }
 
+    pub fn esia_token_expired(member: &MemberIdentity) -> Self {
+        let desc = format!("ESIA token of {} has expired, log in again", member);
+        Error::with_info(Code::EsiaTokenExpired, desc)
+    }
+
+    pub fn esia_token_mismatch(member: &MemberIdentity) -> Self {
+        let desc = format!(
+            "ESIA token of {} stored on the node doesn't match the registered one",
+            member
+        );
+        Error::with_info(Code::Esia, desc)
+    }
+
+    pub fn esia_attestation_expired(member: &MemberIdentity, valid_until: u64) -> Self {
+        let desc = format!(
+            "ESIA attestation of {} is valid till block {}, send the transaction again",
+            member, valid_until
+        );
+        Error::with_info(Code::Esia, desc)
+    }
+
+    pub fn bad_esia_attestation(member: &MemberIdentity, info: &str) -> Self {
+        let desc = format!("Invalid ESIA attestation of {}: {}", member, info);
+        Error::with_info(Code::Esia, desc)
+    }
+
+    pub fn esia_bad_token(oid: &str) -> Self {
+        let desc = format!("Token of person {} was rejected, log in again", oid);
+        Error::with_info(Code::EsiaTokenExpired, desc)
+    }
+
+    pub fn bad_identity_provider(info: &str) -> Self {
+        let desc = format!("identity provider error: {}", info);
+        Error::with_info(Code::Internal, desc)
+    }
+
+    pub fn bad_credential_store(info: &str) -> Self {
+        let desc = format!("credential store error: {}", info);
+        Error::with_info(Code::Internal, desc)
+    }
+
+    pub fn bad_powers(name: &str) -> Self {
+        let desc = format!("unknown power '{}'", name);
+        Error::with_info(Code::BadValue, desc)
+    }
+
+    pub fn bad_power_of_attorney(info: &str) -> Self {
+        let desc = format!("invalid power of attorney: {}", info);
+        Error::with_info(Code::BadValue, desc)
+    }
+
+    pub fn no_power_of_attorney(member: &MemberIdentity, person: &str) -> Self {
+        let desc = format!(
+            "person {} has no valid power of attorney to act for {}",
+            person, member
+        );
+        Error::with_info(Code::PermissionDenied, desc)
+    }
+
+    pub fn no_power_of_attorney_tx(tx_hash: &Hash) -> Self {
+        let desc = format!("power of attorney wasn't found '{}'", tx_hash);
+        Error::with_info(Code::NotFound, desc)
+    }
+
     pub fn while_requesting(status_code: &reqwest::StatusCode, body: serde_json::Value) -> Self {
         let desc = format!(
             "Unexpected error while requesting with status code: {}, body: {}",
@@ -607,6 +783,27 @@ impl From<std::num::ParseIntError> for Error {
     }
 }
 
+impl From<chrono::ParseError> for Error {
+    fn from(err: chrono::ParseError) -> Self {
+        let desc = format!("parse date error: {}", err);
+        Error::with_info(Code::BadParam, desc)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        let desc = format!("file store error: {}", err);
+        Error::with_info(Code::Internal, desc)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        let desc = format!("document archive error: {}", err);
+        Error::with_info(Code::Internal, desc)
+    }
+}
+
 impl From<Code> for Error {
     fn from(code: Code) -> Self {
         Error::with_info(code, code.to_string())
@@ -699,7 +896,9 @@ impl From<actix_web::error::JsonPayloadError> for Error {
                 let desc = "bad payload: Payload size is bigger than allowed. (default: 256kB)";
                 Error::with_info(Code::BadParam, desc.to_owned())
             }
-            actix_web::error::JsonPayloadError::ContentType => Error::bad_content_type(),
+            actix_web::error::JsonPayloadError::ContentType => {
+                Error::bad_content_type("Expected application/json")
+            }
             actix_web::error::JsonPayloadError::Deserialize(err) => err.into(),
             actix_web::error::JsonPayloadError::Payload(err) => err.into(),
         }
@@ -1,93 +1,235 @@
-use crate::data::member::{MemberIdentity, MemberType};
-use crate::error::{Error as FipsError, Result};
-use reqwest::IntoUrl;
-use serde::{Deserialize, Serialize};
-use std::convert::TryInto;
+use std::thread;
 use std::time::Duration;
 
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::data::member::MemberIdentity;
+use crate::error::{Error as FipsError, Result};
+use crate::identity::{self, IdentityProvider, OrganisationRole, Person};
+
+/// Base URL of ESIA
+const ESIA_URL_VAR: &str = "FIPS_ESIA_URL";
+/// Request timeout in milliseconds
+const ESIA_TIMEOUT_VAR: &str = "FIPS_ESIA_TIMEOUT";
+
+#[cfg(feature = "esia_test")]
+const DEFAULT_ESIA_URL: &str = "https://esia-portal1.test.gosuslugi.ru";
+#[cfg(not(feature = "esia_test"))]
+const DEFAULT_ESIA_URL: &str = "https://esia.gosuslugi.ru";
+const DEFAULT_ESIA_TIMEOUT: u64 = 1000;
+
+/// Attempts of a request failing with a transient error
+const RETRY_ATTEMPTS: u32 = 3;
+/// Delay before the second attempt, doubled for every next one
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+enum RequestError {
+    /// Timeouts, connection failures and server errors, the request may be retried
+    Transient(FipsError),
+    Fatal(FipsError),
+}
+
+impl From<RequestError> for FipsError {
+    fn from(err: RequestError) -> Self {
+        match err {
+            RequestError::Transient(err) | RequestError::Fatal(err) => err,
+        }
+    }
+}
+
 fn send_request<T: for<'de> serde::Deserialize<'de>>(
     duration: Duration,
-    url: impl IntoUrl,
+    url: &str,
     bearer: &str,
+    oid: &str,
 ) -> Result<T> {
+    let mut backoff = RETRY_BACKOFF;
+    for _ in 1..RETRY_ATTEMPTS {
+        match request(duration, url, bearer, oid) {
+            Err(RequestError::Transient(err)) => {
+                warn!("ESIA request failed, retrying in {:?}: {}", backoff, err);
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            result => return result.map_err(Into::into),
+        }
+    }
+    request(duration, url, bearer, oid).map_err(Into::into)
+}
+
+fn request<T: for<'de> serde::Deserialize<'de>>(
+    duration: Duration,
+    url: &str,
+    bearer: &str,
+    oid: &str,
+) -> std::result::Result<T, RequestError> {
     let resp = reqwest::blocking::Client::builder()
         .timeout(duration)
-        .build()?
+        .build()
+        .map_err(|e| RequestError::Fatal(e.into()))?
         .get(url)
         .bearer_auth(bearer)
-        .send()?;
+        .send()
+        .map_err(|e| {
+            if e.is_timeout() || e.is_connect() {
+                RequestError::Transient(e.into())
+            } else {
+                RequestError::Fatal(e.into())
+            }
+        })?;
 
     let status_code = resp.status();
 
+    if status_code == StatusCode::UNAUTHORIZED {
+        return Err(RequestError::Fatal(FipsError::esia_bad_token(oid)));
+    }
     if !status_code.is_success() {
-        let body: serde_json::Value = resp.json()?;
-        return FipsError::while_requesting(&status_code, body).ok();
+        let body = resp.text().unwrap_or_default();
+        let body = serde_json::from_str(&body).unwrap_or(serde_json::Value::String(body));
+        let err = FipsError::while_requesting(&status_code, body);
+        return if status_code.is_server_error() || status_code == StatusCode::TOO_MANY_REQUESTS {
+            Err(RequestError::Transient(err))
+        } else {
+            Err(RequestError::Fatal(err))
+        };
     }
-    Ok(resp.json()?)
+    resp.json().map_err(|e| RequestError::Fatal(e.into()))
 }
 
-#[cfg(feature = "esia_test")]
-const ESIA_URL: &str = "https://esia-portal1.test.gosuslugi.ru";
-#[cfg(not(feature = "esia_test"))]
-const ESIA_URL: &str = "https://esia.gosuslugi.ru";
-
+#[deprecated(note = "use `IdentityProvider::acting_person` of `EsiaProvider`")]
 pub struct EsiaAuth;
 
+#[allow(deprecated)]
 impl EsiaAuth {
+    /// Checks that the person the token is issued to is the member or has a role in it
     pub fn validate(member: &MemberIdentity, bearer_token: &str, oid: &str) -> Result<bool> {
-        #[cfg(feature = "no_esia_reqwest")]
-        return Ok(true);
-
-        let member_type: MemberType = member.class().try_into().unwrap();
-        match member_type {
-            MemberType::Ogrn => Self::validate_ogrn(member.number(), bearer_token, oid),
-            MemberType::Ogrnip => Self::validate_ogrn(member.number(), bearer_token, oid),
-            MemberType::Snils => Self::validate_snils(member.number(), bearer_token, oid),
-        }
+        identity::provider()?
+            .acting_person(member, bearer_token, oid)
+            .map(|acting| acting.is_some())
     }
+}
 
-    fn validate_snils(member_number: &str, bearer_token: &str, oid: &str) -> Result<bool> {
-        let url = format!("{}/rs/prns/{}", ESIA_URL, oid);
-
-        let esia_response: EsiaOidProfile =
-            send_request(Duration::from_secs(1), url, bearer_token)?;
+pub struct EsiaProvider {
+    base_url: String,
+    timeout: Duration,
+}
 
-        Ok(esia_response.compare_with_member_number(member_number))
+impl EsiaProvider {
+    pub fn new(base_url: &str, timeout: Duration) -> Self {
+        EsiaProvider {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+            timeout,
+        }
     }
 
-    fn validate_ogrn(member_number: &str, bearer_token: &str, oid: &str) -> Result<bool> {
-        let url = format!("{}/rs/prns/{}/roles", ESIA_URL, oid);
+    pub fn from_env() -> Result<Self> {
+        let base_url = std::env::var(ESIA_URL_VAR).unwrap_or_else(|_| DEFAULT_ESIA_URL.to_owned());
+        let timeout = match std::env::var(ESIA_TIMEOUT_VAR) {
+            Ok(timeout) => timeout.parse()?,
+            Err(_) => DEFAULT_ESIA_TIMEOUT,
+        };
+        Ok(Self::new(&base_url, Duration::from_millis(timeout)))
+    }
+}
 
-        let esia_response: EsiaOidRoles = send_request(Duration::from_secs(1), url, bearer_token)?;
+impl IdentityProvider for EsiaProvider {
+    fn person(&self, token: &str, oid: &str) -> Result<Person> {
+        let url = format!("{}/rs/prns/{}", self.base_url, oid);
+        let esia_response: EsiaOidProfile = send_request(self.timeout, &url, token, oid)?;
+        Ok(Person {
+            snils: esia_response.snils,
+            inn: esia_response.inn,
+        })
+    }
 
-        let is_success = esia_response
-            .elements
-            .into_iter()
-            .any(|ref element| element.ogrn == member_number);
-        Ok(is_success)
+    fn roles(&self, token: &str, oid: &str) -> Result<Vec<OrganisationRole>> {
+        let url = format!("{}/rs/prns/{}/roles", self.base_url, oid);
+        let esia_response: EsiaOidRoles = send_request(self.timeout, &url, token, oid)?;
+        Ok(esia_response.elements)
     }
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
 struct EsiaOidProfile {
     snils: String,
-}
-
-impl EsiaOidProfile {
-    pub fn compare_with_member_number(&self, member_number: &str) -> bool {
-        self.snils
-            .replace("-", "")
-            .replace(" ", "")
-            .eq(member_number)
-    }
+    #[serde(default)]
+    inn: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
 struct EsiaOidRoles {
-    pub elements: Vec<EsiaOidRolesElement>,
+    pub elements: Vec<OrganisationRole>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
-struct EsiaOidRolesElement {
-    pub ogrn: String,
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    /// Answers the requests with `responses` in turn, counting them
+    fn serve(responses: Vec<(&'static str, &'static str)>) -> (EsiaProvider, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request);
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                     Connection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+        (EsiaProvider::new(&url, Duration::from_secs(5)), requests)
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let (provider, requests) = serve(vec![
+            ("503 Service Unavailable", "{}"),
+            ("429 Too Many Requests", "{}"),
+            ("200 OK", r#"{"snils": "112-233-445 95"}"#),
+        ]);
+        let person = provider.person("token", "1000").unwrap();
+        assert_eq!(person.snils, "112-233-445 95");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn gives_up_after_attempts() {
+        let (provider, requests) = serve(vec![("503 Service Unavailable", "{}"); 3]);
+        provider.person("token", "1000").unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), RETRY_ATTEMPTS as usize);
+    }
+
+    #[test]
+    fn client_error_isnt_retried() {
+        let (provider, requests) = serve(vec![("404 Not Found", "{}")]);
+        provider.roles("token", "1000").unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn unauthorized_token_expires() {
+        let (provider, requests) = serve(vec![("401 Unauthorized", "{}")]);
+        let err = provider.person("token", "1000").unwrap_err();
+        let member = MemberIdentity::from_str("snils::11223344595").unwrap();
+        assert_eq!(err.code(), FipsError::esia_token_expired(&member).code());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
 }
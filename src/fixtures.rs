@@ -0,0 +1,116 @@
+//! Members, objects and rights shared by the unit tests.
+
+use std::str::FromStr;
+
+use chrono::{Duration, Utc};
+
+use blockp_core::crypto::hash;
+use blockp_core::storage::{Database, Fork, MemoryDB};
+
+use crate::data::attachment::{Attachment, AttachmentMetadata, AttachmentType};
+use crate::data::attorney::{PowerOfAttorney, Powers};
+use crate::data::classifier::{Classifier, ClassifierRegistry};
+use crate::data::conditions::{Conditions, ContractType, ObjectOwnership};
+use crate::data::contract::Contract;
+use crate::data::location::{Location, LocationRegistry};
+use crate::data::member::MemberIdentity;
+use crate::data::object::ObjectIdentity;
+use crate::data::ownership::Rights;
+use crate::data::time::{Specification, Term};
+
+/// SNILS of the representative acting by `power_of_attorney`
+pub const REPRESENTATIVE: &str = "02583651380";
+
+// Rights flags
+pub const EXCLUSIVE: u16 = 1;
+pub const CAN_DISTRIBUTE: u16 = 8;
+pub const WITH_WRITTEN_PERMISSION: u16 = 16;
+
+/// Empty storage to build the schema on
+pub fn fork() -> Fork {
+    MemoryDB::new().fork()
+}
+
+/// Legal entity
+pub fn buyer() -> MemberIdentity {
+    MemberIdentity::from_str("ogrn::1053600591197").unwrap()
+}
+
+/// Individual entrepreneur
+pub fn seller() -> MemberIdentity {
+    MemberIdentity::from_str("ogrnip::304500116000157").unwrap()
+}
+
+/// Person
+pub fn licensee() -> MemberIdentity {
+    MemberIdentity::from_str(&format!("snils::{}", REPRESENTATIVE)).unwrap()
+}
+
+pub fn object() -> ObjectIdentity {
+    ObjectIdentity::from_str("trademark::123451").unwrap()
+}
+
+pub fn oktmo(code: u64) -> Location {
+    Location::new(LocationRegistry::Oktmo as u8, code, "")
+}
+
+pub fn mktu(value: &str) -> Classifier {
+    Classifier::new(ClassifierRegistry::Mktu as u8, value, "")
+}
+
+/// Non-exclusive licence without territory and classifier limits
+pub fn licence() -> Rights {
+    scoped_licence(0, vec![Location::default()], vec![])
+}
+
+pub fn scoped_licence(flags: u16, location: Vec<Location>, classifiers: Vec<Classifier>) -> Rights {
+    Rights::new(
+        flags,
+        ContractType::License as u8,
+        location,
+        classifiers,
+        Utc::now(),
+        None,
+    )
+}
+
+/// Rights to the `object` offered by a contract
+pub fn ownership(
+    exclusive: bool,
+    location: Vec<Location>,
+    classifiers: Vec<Classifier>,
+) -> ObjectOwnership {
+    ObjectOwnership::new(
+        object(),
+        Term::new(Specification::Forever as u8, None, None),
+        exclusive,
+        3,
+        location,
+        classifiers,
+    )
+}
+
+pub fn conditions(contract_type: ContractType, objects: Vec<ObjectOwnership>) -> Conditions {
+    Conditions::new(contract_type as u8, objects, "", "", vec![], vec![])
+}
+
+/// Licence contract between the `buyer` and the `seller` without objects
+pub fn contract() -> Contract {
+    Contract::buy(buyer(), seller(), 100, conditions(ContractType::License, vec![]))
+}
+
+/// Power of attorney of the `buyer` to the `REPRESENTATIVE` valid for a day around now
+pub fn power_of_attorney(powers: Powers) -> PowerOfAttorney {
+    let now = Utc::now();
+    let metadata =
+        AttachmentMetadata::new("poa.xml", None, AttachmentType::PowerOfAttorney as u8, now);
+    PowerOfAttorney::new(
+        buyer(),
+        REPRESENTATIVE,
+        powers.bits(),
+        now - Duration::days(1),
+        now + Duration::days(1),
+        Attachment::new(metadata, &hash(b"poa"), None),
+        false,
+    )
+}
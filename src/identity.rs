@@ -0,0 +1,449 @@
+//! Identity providers confirming that a token and an OID belong to a member.
+//!
+//! The provider is chosen by `FIPS_IDENTITY_PROVIDER`: `esia` (default) or `mock`. The mock
+//! provider reads the persons it knows from the JSON file `FIPS_IDENTITY_MOCK`, it's built
+//! only for tests or with the `mock_identity` feature.
+//!
+//! Successful validation results are cached per member, OID and token fingerprint for
+//! `FIPS_IDENTITY_CACHE_TTL` seconds.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+#[cfg(any(test, feature = "mock_identity"))]
+use std::fs;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use blockp_core::crypto::Hash;
+
+use crate::credentials::EsiaCredentials;
+use crate::data::member::{MemberId, MemberIdentity, MemberType};
+use crate::error::{Error, Result};
+use crate::esia::EsiaProvider;
+
+const PROVIDER_VAR: &str = "FIPS_IDENTITY_PROVIDER";
+#[cfg(any(test, feature = "mock_identity"))]
+const MOCK_FILE_VAR: &str = "FIPS_IDENTITY_MOCK";
+const CACHE_TTL_VAR: &str = "FIPS_IDENTITY_CACHE_TTL";
+const DEFAULT_CACHE_TTL: u64 = 300;
+/// Roles allowing to act for an organisation without a power of attorney, separated by commas:
+/// `chief`, `admin`, `any`. Any employee acts for the organisation by default, as before powers
+/// of attorney, which grant only confirming and signing contracts
+const ACTING_ROLES_VAR: &str = "FIPS_IDENTITY_ACTING_ROLES";
+const DEFAULT_ACTING_ROLES: &str = "any";
+
+type CacheKey = (MemberId, String, Hash);
+type Cache = HashMap<CacheKey, (ActingPerson, Instant)>;
+
+/// Acting persons with the time they were received, shared by the API worker threads.
+/// Rejections aren't cached, so a person given a role is accepted at once
+static VALIDATION_CACHE: Mutex<Option<Cache>> = Mutex::new(None);
+
+/// Role of a person in an organisation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrganisationRole {
+    pub ogrn: String,
+    #[serde(default)]
+    pub inn: Option<String>,
+    #[serde(default)]
+    pub full_name: Option<String>,
+    /// Head of the organisation
+    #[serde(default)]
+    pub chief: bool,
+    /// Administrator of the organisation profile
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// Roles allowing to act for an organisation without a power of attorney
+pub fn acting_roles() -> String {
+    std::env::var(ACTING_ROLES_VAR).unwrap_or_else(|_| DEFAULT_ACTING_ROLES.to_owned())
+}
+
+impl OrganisationRole {
+    /// Checks that the role is one of the comma separated `roles` allowing to act for
+    /// the organisation without a power of attorney
+    pub fn may_act(&self, roles: &str) -> Result<bool> {
+        roles
+            .split(',')
+            .map(str::trim)
+            .try_fold(false, |may_act, role| {
+                let matches = match role {
+                    "chief" => self.chief,
+                    "admin" => self.admin,
+                    "any" => true,
+                    _ => {
+                        return Error::bad_identity_provider(&format!(
+                            "unknown acting role '{}'",
+                            role
+                        ))
+                        .ok()
+                    }
+                };
+                Ok(may_act || matches)
+            })
+    }
+}
+
+/// Personal data of the person the token is issued to
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Person {
+    pub snils: String,
+    #[serde(default)]
+    pub inn: Option<String>,
+}
+
+/// Person acting on behalf of a member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActingPerson {
+    pub snils: String,
+    /// Role in the organisation, `None` if the member is the person
+    pub role: Option<OrganisationRole>,
+}
+
+pub trait IdentityProvider {
+    /// The person the token is issued to
+    fn person(&self, token: &str, oid: &str) -> Result<Person>;
+
+    /// Roles of the person in organisations
+    fn roles(&self, token: &str, oid: &str) -> Result<Vec<OrganisationRole>>;
+
+    /// Person the token is issued to, if the person is the member or has a role in it
+    fn acting_person(
+        &self,
+        member: &MemberIdentity,
+        token: &str,
+        oid: &str,
+    ) -> Result<Option<ActingPerson>> {
+        let member_type = MemberType::try_from(member.class())
+            .map_err(|_| Error::bad_member_format(&member.to_string()))?;
+        let person = self.person(token, oid)?;
+        let snils = normalize_snils(&person.snils);
+        let acting_role = |role: Option<OrganisationRole>| {
+            role.map(|role| ActingPerson {
+                snils: snils.clone(),
+                role: Some(role),
+            })
+        };
+        let acting = match member_type {
+            MemberType::Ogrn | MemberType::Ogrnip => acting_role(
+                self.roles(token, oid)?
+                    .into_iter()
+                    .find(|role| role.ogrn == member.number()),
+            ),
+            // INN of an individual may be the person's own one
+            MemberType::Inn if person.inn.as_ref().map(String::as_str) == member.inn() => {
+                Some(ActingPerson { snils, role: None })
+            }
+            MemberType::Inn => acting_role(
+                self.roles(token, oid)?
+                    .into_iter()
+                    .find(|role| role.inn.as_ref().map(String::as_str) == member.inn()),
+            ),
+            MemberType::Snils if snils == member.number() => {
+                Some(ActingPerson { snils, role: None })
+            }
+            MemberType::Snils => None,
+        };
+        Ok(acting)
+    }
+}
+
+#[allow(unreachable_code)]
+pub fn provider() -> Result<Box<dyn IdentityProvider>> {
+    #[cfg(feature = "no_esia_reqwest")]
+    return Ok(Box::new(AcceptAll));
+
+    match std::env::var(PROVIDER_VAR).as_ref().map(String::as_str) {
+        Err(_) | Ok("esia") => Ok(Box::new(EsiaProvider::from_env()?)),
+        #[cfg(any(test, feature = "mock_identity"))]
+        Ok("mock") => Ok(Box::new(MockProvider::from_env()?)),
+        Ok(other) => Error::bad_identity_provider(&format!("unknown provider '{}'", other)).ok(),
+    }
+}
+
+/// Person acting on behalf of the member with the credentials, using cached results
+pub fn acting_person(
+    member: &MemberIdentity,
+    credentials: &EsiaCredentials,
+) -> Result<Option<ActingPerson>> {
+    let ttl = match std::env::var(CACHE_TTL_VAR) {
+        Ok(ttl) => Duration::from_secs(ttl.parse()?),
+        Err(_) => Duration::from_secs(DEFAULT_CACHE_TTL),
+    };
+    cached_acting_person(&*provider()?, member, credentials, ttl)
+}
+
+fn cached_acting_person(
+    provider: &dyn IdentityProvider,
+    member: &MemberIdentity,
+    credentials: &EsiaCredentials,
+    ttl: Duration,
+) -> Result<Option<ActingPerson>> {
+    let key = (
+        member.id(),
+        credentials.oid().to_owned(),
+        credentials.fingerprint(),
+    );
+    let cached = with_cache(|cache| {
+        cache
+            .get(&key)
+            .filter(|(_, received)| received.elapsed() < ttl)
+            .map(|(acting, _)| acting.clone())
+    });
+    if cached.is_some() {
+        return Ok(cached);
+    }
+
+    let acting = provider.acting_person(member, credentials.token(), credentials.oid())?;
+    if let Some(acting) = &acting {
+        with_cache(|cache| {
+            cache.retain(|_, (_, received)| received.elapsed() < ttl);
+            cache.insert(key, (acting.clone(), Instant::now()));
+        });
+    }
+    Ok(acting)
+}
+
+fn with_cache<R>(f: impl FnOnce(&mut Cache) -> R) -> R {
+    // The cache is only a shortcut, so it's used even if another thread panicked holding it
+    let mut cache = VALIDATION_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(cache.get_or_insert_with(HashMap::new))
+}
+
+fn normalize_snils(snils: &str) -> String {
+    snils.replace("-", "").replace(" ", "")
+}
+
+#[cfg(any(test, feature = "mock_identity"))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MockPerson {
+    pub token: String,
+    pub snils: String,
+    #[serde(default)]
+    pub inn: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<OrganisationRole>,
+}
+
+/// Local provider for tests, knows persons by their OID
+#[cfg(any(test, feature = "mock_identity"))]
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    persons: HashMap<String, MockPerson>,
+}
+
+#[cfg(any(test, feature = "mock_identity"))]
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let path = std::env::var(MOCK_FILE_VAR)
+            .map_err(|_| Error::bad_identity_provider(&format!("{} isn't set", MOCK_FILE_VAR)))?;
+        let persons = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(MockProvider { persons })
+    }
+
+    pub fn with_person(mut self, oid: &str, person: MockPerson) -> Self {
+        self.persons.insert(oid.to_owned(), person);
+        self
+    }
+
+    fn mock_person(&self, token: &str, oid: &str) -> Result<&MockPerson> {
+        self.persons
+            .get(oid)
+            .filter(|person| person.token == token)
+            .ok_or_else(|| Error::esia_bad_token(oid))
+    }
+}
+
+#[cfg(any(test, feature = "mock_identity"))]
+impl IdentityProvider for MockProvider {
+    fn person(&self, token: &str, oid: &str) -> Result<Person> {
+        self.mock_person(token, oid).map(|person| Person {
+            snils: person.snils.clone(),
+            inn: person.inn.clone(),
+        })
+    }
+
+    fn roles(&self, token: &str, oid: &str) -> Result<Vec<OrganisationRole>> {
+        self.mock_person(token, oid)
+            .map(|person| person.roles.clone())
+    }
+}
+
+/// Accepts any token for any member
+#[cfg(feature = "no_esia_reqwest")]
+struct AcceptAll;
+
+#[cfg(feature = "no_esia_reqwest")]
+impl IdentityProvider for AcceptAll {
+    fn person(&self, _token: &str, oid: &str) -> Result<Person> {
+        Error::esia_bad_token(oid).ok()
+    }
+
+    fn roles(&self, _token: &str, _oid: &str) -> Result<Vec<OrganisationRole>> {
+        Ok(Vec::new())
+    }
+
+    /// Any person is the head of any organisation
+    fn acting_person(
+        &self,
+        member: &MemberIdentity,
+        _token: &str,
+        _oid: &str,
+    ) -> Result<Option<ActingPerson>> {
+        let role = OrganisationRole {
+            ogrn: member.number().to_owned(),
+            inn: member.inn().map(ToOwned::to_owned),
+            full_name: None,
+            chief: true,
+            admin: true,
+        };
+        Ok(Some(ActingPerson {
+            snils: String::new(),
+            role: Some(role).filter(|_| !member.is_person()),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn provider() -> MockProvider {
+        MockProvider::new().with_person(
+            "1000",
+            MockPerson {
+                token: "token".to_owned(),
+                snils: "112-233-445 95".to_owned(),
+                inn: Some("500100732259".to_owned()),
+                roles: vec![OrganisationRole {
+                    ogrn: "1053600591197".to_owned(),
+                    inn: Some("7707083893".to_owned()),
+                    full_name: None,
+                    chief: true,
+                    admin: false,
+                }],
+            },
+        )
+    }
+
+    #[test]
+    fn mock_finds_acting_person() {
+        let provider = provider();
+        let person = MemberIdentity::from_str("snils::11223344595").unwrap();
+        let company = MemberIdentity::from_str("ogrn::1053600591197").unwrap();
+        let acting = provider.acting_person(&person, "token", "1000").unwrap();
+        assert_eq!(acting.unwrap().snils, "11223344595");
+        let acting = provider.acting_person(&company, "token", "1000").unwrap();
+        let role = acting.unwrap().role.unwrap();
+        assert!(role.may_act("chief").unwrap());
+        assert!(!role.may_act("admin").unwrap());
+        assert!(role.may_act(DEFAULT_ACTING_ROLES).unwrap());
+        role.may_act("owner").unwrap_err();
+        let other = MemberIdentity::from_str("snils::08765430300").unwrap();
+        assert!(provider
+            .acting_person(&other, "token", "1000")
+            .unwrap()
+            .is_none());
+        let by_inn = MemberIdentity::from_str("inn::7707083893:773601001").unwrap();
+        let acting = provider.acting_person(&by_inn, "token", "1000").unwrap();
+        assert!(acting.unwrap().role.is_some());
+        let self_employed = MemberIdentity::from_str("inn::500100732259").unwrap();
+        let acting = provider
+            .acting_person(&self_employed, "token", "1000")
+            .unwrap();
+        assert!(acting.unwrap().role.is_none());
+        provider
+            .acting_person(&company, "other", "1000")
+            .unwrap_err();
+        provider
+            .acting_person(&company, "token", "2000")
+            .unwrap_err();
+    }
+
+    /// Counts the requests to the mock provider
+    struct Counting {
+        provider: MockProvider,
+        requests: std::cell::Cell<usize>,
+    }
+
+    impl IdentityProvider for Counting {
+        fn person(&self, token: &str, oid: &str) -> Result<Person> {
+            self.requests.set(self.requests.get() + 1);
+            self.provider.person(token, oid)
+        }
+
+        fn roles(&self, token: &str, oid: &str) -> Result<Vec<OrganisationRole>> {
+            self.provider.roles(token, oid)
+        }
+    }
+
+    fn counting() -> Counting {
+        Counting {
+            provider: provider(),
+            requests: std::cell::Cell::new(0),
+        }
+    }
+
+    #[test]
+    fn caches_acting_person() {
+        let provider = counting();
+        // Members of the cache tests differ, the cache is shared by the tests
+        let member = MemberIdentity::from_str("snils::11223344595").unwrap();
+        let credentials = EsiaCredentials::new("token", "1000");
+        let ttl = Duration::from_secs(60);
+        for _ in 0..2 {
+            let acting = cached_acting_person(&provider, &member, &credentials, ttl).unwrap();
+            assert_eq!(acting.unwrap().snils, "11223344595");
+        }
+        assert_eq!(provider.requests.get(), 1);
+    }
+
+    #[test]
+    fn cached_acting_person_expires() {
+        let provider = counting();
+        let member = MemberIdentity::from_str("ogrn::1053600591197").unwrap();
+        let credentials = EsiaCredentials::new("token", "1000");
+        for _ in 0..2 {
+            cached_acting_person(&provider, &member, &credentials, Duration::from_secs(0))
+                .unwrap()
+                .unwrap();
+        }
+        assert_eq!(provider.requests.get(), 2);
+    }
+
+    #[test]
+    fn rejection_isnt_cached() {
+        let provider = counting();
+        let member = MemberIdentity::from_str("snils::08765430300").unwrap();
+        let credentials = EsiaCredentials::new("token", "1000");
+        let ttl = Duration::from_secs(60);
+        for _ in 0..2 {
+            let acting = cached_acting_person(&provider, &member, &credentials, ttl).unwrap();
+            assert!(acting.is_none());
+        }
+        assert_eq!(provider.requests.get(), 2);
+    }
+
+    #[test]
+    fn parses_esia_roles() {
+        let roles: Vec<OrganisationRole> = serde_json::from_str(
+            r#"[{"oid": 1, "ogrn": "1053600591197", "fullName": "ООО Ромашка", "chief": true}]"#,
+        )
+        .unwrap();
+        assert_eq!(roles[0].ogrn, "1053600591197");
+        assert!(roles[0].chief);
+        assert!(!roles[0].admin);
+    }
+}
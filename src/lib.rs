@@ -10,18 +10,31 @@ extern crate serde;
 #[macro_use]
 extern crate serde_json;
 
+#[allow(deprecated)]
 pub use esia::EsiaAuth;
+pub use esia::EsiaProvider;
+pub use identity::{IdentityProvider, OrganisationRole};
+#[cfg(any(test, feature = "mock_identity"))]
+pub use identity::{MockPerson, MockProvider};
 pub use service::ServiceFactory;
 
 mod api;
 mod control;
+mod credentials;
 mod data;
 mod dto;
+mod encryption;
 mod error;
 mod esia;
+#[cfg(test)]
+mod fixtures;
+mod identity;
 mod response;
+mod scan;
 mod schema;
 mod service;
+mod store;
+mod template;
 mod transactions;
 mod upload;
 mod util;
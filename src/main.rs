@@ -1,12 +1,16 @@
 use blockp_configuration as configuration;
 use blockp_core::helpers::fabric::NodeBuilder;
+#[cfg(feature = "time_service")]
+use blockp_time as time;
 
 fn main() {
     let _logger_guard = log_custom::init_logger().unwrap();
 
-    let node = NodeBuilder::new()
-        .with_service(Box::new(configuration::ServiceFactory))
-        //.with_service(Box::new(time::ServiceFactory))
-        .with_service(Box::new(fips::ServiceFactory));
+    let node = NodeBuilder::new().with_service(Box::new(configuration::ServiceFactory));
+    // The time service is a part of the genesis block, so every node of the network must be
+    // built with or without it
+    #[cfg(feature = "time_service")]
+    let node = node.with_service(Box::new(time::ServiceFactory));
+    let node = node.with_service(Box::new(fips::ServiceFactory));
     node.run();
 }
@@ -12,9 +12,11 @@ use crate::data::conditions::CheckKey;
 use crate::data::contract::ContractStatus;
 use crate::data::cost::Cost;
 use crate::dto::{
-    AttachmentDto, CheckInfo, ConditionsInfo, ContractInfo, HashWrapperDto, LotInfoWithObjects,
-    ObjectIdentityDto, ObjectInformationDto, ObjectParticipates, PaginationPage, RequestConfirmDto,
-    TxHash, TxList,
+    AttachmentDto, CheckInfo, ConditionsInfo, ContractDeadlineInfo, ContractInfo,
+    ContractRevisionInfo, ContractTransitionInfo, ContractTransitionsDto, DocumentVersionInfo,
+    HashWrapperDto, LotInfoWithObjects, MemberInboxDto, MemberRegistryDto, MemberSummaryDto,
+    ObjectIdentityDto, ObjectInformationDto, ObjectParticipates, PaginationPage,
+    PowerOfAttorneyDto, RequestConfirmDto, TxHash, TxList,
 };
 use crate::error::{Error, Result};
 
@@ -39,10 +41,22 @@ pub enum Data {
     Bids(Vec<Cost>),
     TxHashes(Vec<String>),
     Attachment(AttachmentDto),
+    #[serde(rename(serialize = "versions"))]
+    DocumentVersions(Vec<DocumentVersionInfo>),
     Checks(HashMap<CheckKey, CheckInfo>),
     #[serde(rename(serialize = "status"))]
     ContractStatus(String),
     ContractInfo(ContractInfo),
+    #[serde(rename(serialize = "revision"))]
+    ContractRevision(ContractRevisionInfo),
+    #[serde(rename(serialize = "revisions"))]
+    ContractRevisions(Vec<ContractRevisionInfo>),
+    #[serde(rename(serialize = "history"))]
+    ContractHistory(Vec<ContractTransitionInfo>),
+    #[serde(rename(serialize = "deadlines"))]
+    ContractDeadlines(Vec<ContractDeadlineInfo>),
+    #[serde(rename(serialize = "transitions"))]
+    ContractTransitions(ContractTransitionsDto),
     #[serde(rename(serialize = "conditions"))]
     ConditionsInfo(ConditionsInfo),
     #[serde(rename(serialize = "object"))]
@@ -55,6 +69,13 @@ pub enum Data {
     PageObjects(PaginationPage<ObjectInformationDto, Option<ObjectIdentityDto>>),
     #[cfg(feature = "internal_api")]
     Token(MemberEsiaTokenDto),
+    PowersOfAttorney(Vec<PowerOfAttorneyDto>),
+    #[serde(rename(serialize = "member"))]
+    MemberRegistry(MemberRegistryDto),
+    #[serde(rename(serialize = "summary"))]
+    MemberSummary(MemberSummaryDto),
+    #[serde(rename(serialize = "inbox"))]
+    MemberInbox(MemberInboxDto),
     Status(RequestConfirmDto),
     #[cfg(feature = "extra_counter")]
     #[serde(rename(serialize = "objects_counter"))]
@@ -128,6 +149,36 @@ impl From<ContractInfo> for Data {
     }
 }
 
+impl From<ContractRevisionInfo> for Data {
+    fn from(revision: ContractRevisionInfo) -> Self {
+        Self::ContractRevision(revision)
+    }
+}
+
+impl From<Vec<ContractRevisionInfo>> for Data {
+    fn from(revisions: Vec<ContractRevisionInfo>) -> Self {
+        Self::ContractRevisions(revisions)
+    }
+}
+
+impl From<Vec<ContractTransitionInfo>> for Data {
+    fn from(history: Vec<ContractTransitionInfo>) -> Self {
+        Self::ContractHistory(history)
+    }
+}
+
+impl From<Vec<ContractDeadlineInfo>> for Data {
+    fn from(deadlines: Vec<ContractDeadlineInfo>) -> Self {
+        Self::ContractDeadlines(deadlines)
+    }
+}
+
+impl From<ContractTransitionsDto> for Data {
+    fn from(transitions: ContractTransitionsDto) -> Self {
+        Self::ContractTransitions(transitions)
+    }
+}
+
 impl From<ConditionsInfo> for Data {
     fn from(conditions: ConditionsInfo) -> Self {
         Self::ConditionsInfo(conditions)
@@ -170,6 +221,12 @@ impl From<AttachmentDto> for Data {
     }
 }
 
+impl From<Vec<DocumentVersionInfo>> for Data {
+    fn from(data: Vec<DocumentVersionInfo>) -> Self {
+        Self::DocumentVersions(data)
+    }
+}
+
 #[cfg(feature = "extra_counter")]
 impl From<ObjectsCounter> for Data {
     fn from(counter: ObjectsCounter) -> Self {
@@ -191,6 +248,30 @@ impl From<ContractsCounter> for Data {
     }
 }
 
+impl From<MemberRegistryDto> for Data {
+    fn from(member: MemberRegistryDto) -> Self {
+        Self::MemberRegistry(member)
+    }
+}
+
+impl From<MemberSummaryDto> for Data {
+    fn from(summary: MemberSummaryDto) -> Self {
+        Self::MemberSummary(summary)
+    }
+}
+
+impl From<MemberInboxDto> for Data {
+    fn from(inbox: MemberInboxDto) -> Self {
+        Self::MemberInbox(inbox)
+    }
+}
+
+impl From<Vec<PowerOfAttorneyDto>> for Data {
+    fn from(powers_of_attorney: Vec<PowerOfAttorneyDto>) -> Self {
+        Self::PowersOfAttorney(powers_of_attorney)
+    }
+}
+
 #[cfg(feature = "internal_api")]
 impl From<MemberEsiaTokenDto> for Data {
     fn from(token: MemberEsiaTokenDto) -> Self {
@@ -0,0 +1,141 @@
+//! Scanning of uploaded documents before they're attached to a contract.
+//!
+//! The built-in scanner rejects executables and macro-enabled Office files, and passes the
+//! file to a local antivirus if its command is configured.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::process::Command;
+
+use crate::data::attachment::ScanVerdict;
+use crate::error::{Error, Result};
+
+/// Command of the local scanner, the path of the file is appended to its arguments.
+/// Exit code 0 means the file is clean, 1 means it's infected.
+const SCAN_COMMAND_VAR: &str = "FIPS_SCAN_COMMAND";
+
+/// Checks the file data, returns an error only if the file can't be scanned
+pub trait Scanner {
+    fn scan(&self, path: &Path) -> Result<ScanResult>;
+}
+
+/// Verdict on the accepted file or the reason to reject it
+#[derive(Debug, PartialEq)]
+pub enum ScanResult {
+    Accepted(ScanVerdict),
+    Rejected(&'static str),
+}
+
+pub struct BuiltinScanner {
+    command: Option<Vec<String>>,
+}
+
+impl BuiltinScanner {
+    pub fn from_env() -> Self {
+        let command = std::env::var(SCAN_COMMAND_VAR)
+            .ok()
+            .map(|cmd| cmd.split_whitespace().map(ToOwned::to_owned).collect())
+            .filter(|args: &Vec<String>| !args.is_empty());
+        BuiltinScanner { command }
+    }
+
+    fn run_command(&self, args: &[String], path: &Path) -> Result<ScanResult> {
+        let output = Command::new(&args[0])
+            .args(&args[1..])
+            .arg(path)
+            .output()
+            .map_err(|e| Error::scan_failed(&format!("unable to run '{}': {}", args[0], e)))?;
+        match output.status.code() {
+            Some(0) => Ok(ScanResult::Accepted(ScanVerdict::Clean)),
+            Some(1) => Ok(ScanResult::Rejected("file is infected")),
+            code => Error::scan_failed(&format!(
+                "'{}' exited with {:?}: {}",
+                args[0],
+                code,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+            .ok(),
+        }
+    }
+}
+
+impl Scanner for BuiltinScanner {
+    fn scan(&self, path: &Path) -> Result<ScanResult> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        if let Some(reason) = check_format(&data) {
+            return Ok(ScanResult::Rejected(reason));
+        }
+        match &self.command {
+            Some(args) => self.run_command(args, path),
+            None => Ok(ScanResult::Accepted(ScanVerdict::FormatChecked)),
+        }
+    }
+}
+
+pub fn scanner() -> Box<dyn Scanner> {
+    Box::new(BuiltinScanner::from_env())
+}
+
+/// Returns the reason to reject the file
+fn check_format(data: &[u8]) -> Option<&'static str> {
+    const OLE2: &[u8] = &[0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1];
+    const MACH_O: &[[u8; 4]] = &[
+        [0xfe, 0xed, 0xfa, 0xce],
+        [0xfe, 0xed, 0xfa, 0xcf],
+        [0xce, 0xfa, 0xed, 0xfe],
+        [0xcf, 0xfa, 0xed, 0xfe],
+    ];
+    if data.starts_with(b"MZ")
+        || data.starts_with(b"\x7fELF")
+        || data.starts_with(b"#!")
+        || MACH_O.iter().any(|magic| data.starts_with(magic))
+    {
+        Some("executable files aren't allowed")
+    } else if data.starts_with(OLE2)
+        && (contains(data, &utf16(b"_VBA_PROJECT")) || contains(data, &utf16(b"Macros")))
+    {
+        Some("Office files with macros aren't allowed")
+    } else if data.starts_with(b"PK\x03\x04") && contains(data, b"vbaProject.bin") {
+        Some("Office files with macros aren't allowed")
+    } else {
+        None
+    }
+}
+
+fn contains(data: &[u8], pattern: &[u8]) -> bool {
+    data.windows(pattern.len()).any(|w| w == pattern)
+}
+
+/// OLE2 stream names are stored in UTF-16LE
+fn utf16(s: &[u8]) -> Vec<u8> {
+    s.iter().flat_map(|&c| vec![c, 0]).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rejects_executables() {
+        assert!(check_format(b"MZ\x90\0\x03\0").is_some());
+        assert!(check_format(b"\x7fELF\x02\x01\x01").is_some());
+        assert!(check_format(&[0xcf, 0xfa, 0xed, 0xfe, 0x07]).is_some());
+        assert!(check_format(b"#!/bin/sh\n").is_some());
+        assert!(check_format(b"%PDF-1.7\n").is_none());
+    }
+
+    #[test]
+    fn rejects_office_macros() {
+        let mut doc = vec![0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1, 0, 0];
+        assert!(check_format(&doc).is_none());
+        doc.extend(utf16(b"_VBA_PROJECT"));
+        assert!(check_format(&doc).is_some());
+
+        let mut docm = b"PK\x03\x04\x14\0\x06\0word/document.xml".to_vec();
+        assert!(check_format(&docm).is_none());
+        docm.extend_from_slice(b"word/vbaProject.bin");
+        assert!(check_format(&docm).is_some());
+    }
+}
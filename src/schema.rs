@@ -1,27 +1,41 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use chrono::{DateTime, Duration, Utc};
 
 use blockp_core::blockchain::Schema as CoreSchema;
 use blockp_core::crypto::{Hash, PublicKey};
 use blockp_core::messages::RawMessage;
-use blockp_core::storage::{Fork, ListIndex, MapIndex, ProofListIndex, Snapshot, ValueSetIndex};
+use blockp_core::storage::{
+    Entry, Fork, ListIndex, MapIndex, ProofListIndex, Snapshot, ValueSetIndex,
+};
 
 use crate::data::attachment::{
-    Attachment, AttachmentMetadata, AttachmentMetadataWithHash, DocumentId,
+    Attachment, AttachmentMetadata, AttachmentMetadataWithHash, AttachmentType, DocumentId,
+    ScanVerdict,
 };
+use crate::data::attorney::{PowerOfAttorney, Powers};
 use crate::data::conditions::{Check, CheckResult, Conditions};
 use crate::data::contract::{
-    BuyerSeller, Contract, ContractId, ContractSign, CorrespondenceContacts, State,
+    Action, BuyerSeller, Contract, ContractId, ContractRevision, ContractSign, ContractStateChange,
+    ContractStatus, ContractTransition, CorrespondenceContacts, State, MAX_DEADLINE_DAYS,
+    OPEN_ACTION,
 };
 use crate::data::lot::{Bid, Lot, LotId, LotState};
-use crate::data::member::{MemberEsiaToken, MemberId, MemberIdentity};
+use crate::data::member::{
+    DeprecatedMemberEsiaToken, EsiaAttestation, MemberEsiaToken, MemberId, MemberIdentity,
+};
 use crate::data::object::{Change, ObjectId, ObjectIdentity};
-use crate::data::ownership::{OwnershipUnstructured, Rights};
+use crate::data::ownership::{ContractRights, OwnershipUnstructured, Rights, RightsChange};
 use crate::data::payment::{Calculation, PaymentDetail, PaymentDetailsWrapper, PaymentStatus};
+use crate::data::registry::{KeyType, MemberLink, MemberProfile, ParticipantKey};
 use crate::error::{Error, Result};
 use crate::transactions::{get_private_tx, OwnershipTransactions};
 
 const CONTRACT_CALCULATIONS_INDEX: &str = "fips.contract.calculations";
 const LOT_CALCULATIONS_INDEX: &str = "fips.lot.calculations";
+/// Number of the state migrations, see `Schema::migrate`
+const MIGRATIONS: u64 = 2;
 
 #[derive(Debug)]
 pub struct Schema<T> {
@@ -58,6 +72,27 @@ where
         MapIndex::new_in_family("fips.rightholders", object_id, &self.view)
     }
 
+    /// Rights received under approved contracts by members who already held rights to the object
+    pub fn contract_rights(
+        &self,
+        object_id: &ObjectId,
+    ) -> MapIndex<&T, ContractId, ContractRights> {
+        MapIndex::new_in_family("fips.contract_rights", object_id, &self.view)
+    }
+
+    /// Rights of all rightholders to the object, a member holds a separate record for every
+    /// contract it received rights under while already holding rights
+    pub fn object_rights(&self, object_id: &ObjectId) -> Vec<(MemberIdentity, Rights)> {
+        let contract_rights = self
+            .contract_rights(object_id)
+            .iter()
+            .map(|(_, contract_rights)| (contract_rights.rightholder(), contract_rights.rights()));
+        self.rightholders(object_id)
+            .iter()
+            .chain(contract_rights)
+            .collect()
+    }
+
     pub fn object_history(&self, object_id: &ObjectId) -> ProofListIndex<&T, Change> {
         ProofListIndex::new_in_family("fips.object_history", object_id, &self.view)
     }
@@ -124,6 +159,121 @@ where
         MapIndex::new("fips.contracts.correspondence_contacts", &self.view)
     }
 
+    pub fn contract_revisions(
+        &self,
+        contract_id: &ContractId,
+    ) -> ProofListIndex<&T, ContractRevision> {
+        ProofListIndex::new_in_family("fips.contract_revisions", contract_id, &self.view)
+    }
+
+    /// Revisions of the contract. Contracts opened before revisions were introduced get
+    /// revision `0` built from the stored contract until their first update.
+    pub fn contract_revision_list(
+        &self,
+        contract_id: &ContractId,
+    ) -> Result<Vec<ContractRevision>> {
+        let revisions = self.contract_revisions(contract_id);
+        if !revisions.is_empty() {
+            return Ok(revisions.iter().collect());
+        }
+        let contract = self
+            .contracts()
+            .get(contract_id)
+            .ok_or_else(|| Error::no_contract(contract_id))?;
+        Ok(vec![ContractRevision::new(
+            0,
+            self.contract_initiator(contract_id)?,
+            contract_id,
+            contract.price(),
+            contract.conditions(),
+            None,
+        )])
+    }
+
+    /// Changes made to the registry when the contract was approved
+    pub fn contract_rights_changes(
+        &self,
+        contract_id: &ContractId,
+    ) -> ListIndex<&T, RightsChange> {
+        ListIndex::new_in_family("fips.contract_rights_changes", contract_id, &self.view)
+    }
+
+    /// Objects whose rights weren't restored when the contract was terminated and have to be
+    /// corrected by the registry
+    pub fn contract_unrestored_rights(
+        &self,
+        contract_id: &ContractId,
+    ) -> ListIndex<&T, ObjectIdentity> {
+        ListIndex::new_in_family("fips.contract_unrestored_rights", contract_id, &self.view)
+    }
+
+    pub fn contract_history(
+        &self,
+        contract_id: &ContractId,
+    ) -> ProofListIndex<&T, ContractTransition> {
+        ProofListIndex::new_in_family("fips.contract_history", contract_id, &self.view)
+    }
+
+    /// Deadlines in days per contract status name
+    pub fn contract_deadlines(&self) -> MapIndex<&T, String, u64> {
+        MapIndex::new("fips.contract_deadlines", &self.view)
+    }
+
+    /// Last status change of the contracts which aren't finished yet
+    pub fn contract_state_changes(&self) -> MapIndex<&T, ContractId, ContractStateChange> {
+        MapIndex::new("fips.contract_state_changes", &self.view)
+    }
+
+    pub fn contract_refuse_reasons(&self) -> MapIndex<&T, ContractId, String> {
+        MapIndex::new("fips.contract_refuse_reasons", &self.view)
+    }
+
+    /// Member which requested the fee calculation of the contract and pays the fees
+    pub fn contract_payers(&self) -> MapIndex<&T, ContractId, MemberIdentity> {
+        MapIndex::new("fips.contract_payers", &self.view)
+    }
+
+    pub fn contract_deadline(&self, contract_id: &ContractId) -> Option<DateTime<Utc>> {
+        let change = self.contract_state_changes().get(contract_id)?;
+        let days = self
+            .contract_deadlines()
+            .get(&change.status().to_string())?;
+        change.deadline(days)
+    }
+
+    /// Contracts in the status ordered by the time of the status change, see `status_time_key`
+    pub fn contract_status_times(&self, status: &str) -> MapIndex<&T, Vec<u8>, ContractId> {
+        MapIndex::new_in_family("fips.contract_status_times", &status.to_string(), &self.view)
+    }
+
+    /// Contracts which changed their status before the time service reported the consensus time
+    pub fn contract_untimed_states(&self) -> MapIndex<&T, ContractId, ()> {
+        MapIndex::new("fips.contract_untimed_states", &self.view)
+    }
+
+    /// Only the status changes older than the deadline of their status are visited
+    pub fn expired_contracts(&self, time: DateTime<Utc>) -> Vec<ContractId> {
+        let mut expired = Vec::new();
+        for (status, days) in self.contract_deadlines().iter() {
+            let days = Duration::days(days.min(MAX_DEADLINE_DAYS) as i64);
+            let limit = match time.checked_sub_signed(days) {
+                Some(limit) => status_time_key(limit, &Hash::zero()),
+                None => continue,
+            };
+            let contracts = self
+                .contract_status_times(&status)
+                .iter()
+                .take_while(|(key, _)| key[..8] <= limit[..8])
+                .map(|(_, contract_id)| contract_id)
+                .filter(|contract_id| {
+                    self.contract_deadline(contract_id)
+                        .map_or(false, |deadline| deadline <= time)
+                });
+            expired.extend(contracts);
+        }
+        expired
+    }
+
     pub fn checks(&self, id: &Hash) -> MapIndex<&T, u16, CheckResult> {
         MapIndex::new_in_family("fips.checks", id, &self.view)
     }
@@ -138,7 +288,7 @@ where
 
     pub fn member_token(&self, member_id: &MemberIdentity) -> Option<MemberEsiaToken> {
         let index: MapIndex<&T, MemberIdentity, MemberEsiaToken> =
-            MapIndex::new("fips.esia.member.token", &self.view);
+            MapIndex::new("fips.esia.member.token_fingerprint", &self.view);
         index.get(member_id)
     }
 
@@ -171,18 +321,104 @@ where
         storage.get(contract_id)
     }
 
+    /// Current version of the contract document: the deed, application or one of the other files
+    pub fn contract_document(
+        &self,
+        contract_id: &ContractId,
+        document_id: &DocumentId,
+    ) -> Option<AttachmentMetadata> {
+        let main = vec![
+            self.contract_deed(contract_id),
+            self.contract_application(contract_id),
+        ];
+        main.into_iter()
+            .flatten()
+            .find(|file| file.tx_hash() == document_id)
+            .map(|file| file.metadata())
+            .or_else(|| self.contract_files(contract_id).get(document_id))
+    }
+
+    /// Hashes of the deed and application files data
+    pub fn contract_file_hashes(&self) -> MapIndex<&T, DocumentId, Hash> {
+        MapIndex::new("fips.contract_files.data_hash", &self.view)
+    }
+
+    /// Version of the templates the deed or application was generated with by the node,
+    /// documents attached by the parties aren't listed
+    pub fn generated_documents(&self) -> MapIndex<&T, DocumentId, Hash> {
+        MapIndex::new("fips.contract_files.generated", &self.view)
+    }
+
+    /// Results of the scan of the attached documents and powers of attorney
+    pub fn scan_verdicts(&self) -> MapIndex<&T, DocumentId, u8> {
+        MapIndex::new("fips.documents.scan_verdict", &self.view)
+    }
+
+    /// Documents attached by the node itself or before scanning was introduced aren't scanned
+    pub fn scan_verdict(&self, document_id: &DocumentId) -> ScanVerdict {
+        self.scan_verdicts()
+            .get(document_id)
+            .and_then(|verdict| ScanVerdict::try_from(verdict).ok())
+            .unwrap_or(ScanVerdict::NotScanned)
+    }
+
+    /// Documents attached with the data encrypted in the `FileStore`
+    pub fn encrypted_documents(&self) -> MapIndex<&T, DocumentId, bool> {
+        MapIndex::new("fips.documents.encrypted", &self.view)
+    }
+
+    pub fn is_document_encrypted(&self, document_id: &DocumentId) -> bool {
+        self.encrypted_documents().get(document_id).unwrap_or(false)
+    }
+
+    /// Documents referring to the data by its hash in the `FileStore`
+    pub fn data_documents(&self, data_hash: &Hash) -> ValueSetIndex<&T, DocumentId> {
+        ValueSetIndex::new_in_family("fips.documents.by_data_hash", data_hash, &self.view)
+    }
+
+    /// Members who attached the contract files, documents attached by the node
+    /// itself have no owner
+    pub fn contract_file_owners(&self) -> MapIndex<&T, DocumentId, MemberIdentity> {
+        MapIndex::new("fips.contract_files.owner", &self.view)
+    }
+
+    fn contract_file_roots(&self) -> MapIndex<&T, DocumentId, DocumentId> {
+        MapIndex::new("fips.contract_files.roots", &self.view)
+    }
+
+    fn contract_file_versions(&self, root: &DocumentId) -> ListIndex<&T, DocumentId> {
+        ListIndex::new_in_family("fips.contract_files.versions", root, &self.view)
+    }
+
+    /// All versions of the document from the first one, the last is the current version
+    pub fn contract_file_history(&self, document_id: &DocumentId) -> Vec<DocumentId> {
+        let root = self
+            .contract_file_roots()
+            .get(document_id)
+            .unwrap_or(*document_id);
+        let versions = self.contract_file_versions(&root);
+        if versions.is_empty() {
+            vec![*document_id]
+        } else {
+            versions.iter().collect()
+        }
+    }
+
     pub fn get_attachment(&self, document_id: &DocumentId) -> Result<Attachment> {
-        let doc_tx = get_private_tx(&self, document_id)?;
-        match doc_tx {
-            OwnershipTransactions::AttachContractMainFile(tx) => Ok(tx.file()),
-            OwnershipTransactions::AttachContractOtherFile(tx) => Ok(tx.file()),
-            OwnershipTransactions::ApproveContract(tx) => Ok(tx
-                .attachment()
-                .ok_or_else(|| Error::no_attachment(document_id))?),
-            OwnershipTransactions::RejectContract(tx) => Ok(tx
-                .attachment()
-                .ok_or_else(|| Error::no_attachment(document_id))?),
-            _ => Error::unexpected_tx_type(document_id).ok(),
+        get_private_tx(&self, document_id)?
+            .attachment()
+            .map(|(_, attachment)| attachment)
+            .ok_or_else(|| Error::no_attachment(document_id))
+    }
+
+    // Member who opened the contract with PurchaseOffer or AcquireLot
+    pub fn contract_initiator(&self, contract_id: &ContractId) -> Result<MemberIdentity> {
+        match get_private_tx(&self, contract_id)? {
+            OwnershipTransactions::PurchaseOffer(tx) => Ok(tx.requestor()),
+            OwnershipTransactions::AcquireLot(tx) => Ok(tx.requestor()),
+            OwnershipTransactions::DeprecatedPurchaseOffer(tx) => Ok(tx.requestor()),
+            OwnershipTransactions::DeprecatedAcquireLot(tx) => Ok(tx.requestor()),
+            _ => Error::unexpected_tx_type(contract_id).ok(),
         }
     }
 
@@ -199,10 +435,111 @@ where
         self.deprecated_sign_contract_tx().get(document_id)
     }
 
+    /// Node names added before the member registry, moved to the registry on the first key change
     pub fn participants(&self, member_id: &MemberId) -> ListIndex<&T, String> {
         ListIndex::new_in_family("fips.participants", member_id, &self.view)
     }
 
+    pub fn member_profiles(&self) -> MapIndex<&T, MemberIdentity, MemberProfile> {
+        MapIndex::new("fips.members.profiles", &self.view)
+    }
+
+    pub fn member_keys(&self, member_id: &MemberId) -> MapIndex<&T, PublicKey, ParticipantKey> {
+        MapIndex::new_in_family("fips.members.keys", member_id, &self.view)
+    }
+
+    /// Members linked to their successors
+    pub fn member_links(&self) -> MapIndex<&T, MemberIdentity, MemberLink> {
+        MapIndex::new("fips.members.links", &self.view)
+    }
+
+    /// Registered keys of the member together with the keys of nodes added by name,
+    /// malformed names are skipped. Nodes added by name keep acting for the member,
+    /// so they attest the member as well
+    pub fn participant_keys(&self, member_id: &MemberId) -> Vec<ParticipantKey> {
+        let mut keys: Vec<ParticipantKey> = self.member_keys(member_id).values().collect();
+        for name in self.participants(member_id).iter() {
+            match PublicKey::from_slice(name.as_bytes()) {
+                Some(key) if keys.iter().all(|k| *k.key() != key) => {
+                    let key_type = KeyType::NodeAttestation as u8;
+                    keys.push(ParticipantKey::new(&key, key_type, &name))
+                }
+                Some(_) => {}
+                None => warn!("Skipping malformed participant '{}' of {}", name, member_id),
+            }
+        }
+        keys
+    }
+
+    /// Node keys of the members, private data of the members is shared with these nodes
+    pub fn members_share(&self, members: &[&MemberIdentity]) -> Vec<PublicKey> {
+        let mut share = Vec::new();
+        for member in members {
+            for key in self.participant_keys(&member.id()) {
+                if key.is_node() && !share.contains(key.key()) {
+                    share.push(*key.key());
+                }
+            }
+        }
+        share
+    }
+
+    /// Powers of attorney by the hash of the transaction they were added with
+    pub fn powers_of_attorney(&self) -> MapIndex<&T, Hash, PowerOfAttorney> {
+        MapIndex::new("fips.members.powers_of_attorney", &self.view)
+    }
+
+    /// Transaction hashes of the powers of attorney issued by the member
+    pub fn member_powers_of_attorney(&self, member_id: &MemberId) -> ListIndex<&T, Hash> {
+        ListIndex::new_in_family(
+            "fips.members.powers_of_attorney.by_member",
+            member_id,
+            &self.view,
+        )
+    }
+
+    /// Checks that the power of attorney is issued by the member or by a member migrated to it
+    pub fn member_has_power_of_attorney(&self, member_id: &MemberId, tx_hash: &Hash) -> bool {
+        self.member_powers_of_attorney(member_id)
+            .iter()
+            .any(|attorney_tx_hash| attorney_tx_hash == *tx_hash)
+    }
+
+    /// Powers of attorney of the member issued to the person with SNILS `representative`,
+    /// which aren't revoked
+    pub fn representative_powers(
+        &self,
+        member_id: &MemberId,
+        representative: &str,
+    ) -> Vec<PowerOfAttorney> {
+        let powers_of_attorney = self.powers_of_attorney();
+        self.member_powers_of_attorney(member_id)
+            .iter()
+            .filter_map(|tx_hash| powers_of_attorney.get(&tx_hash))
+            .filter(|poa| !poa.revoked() && poa.representative() == representative)
+            .collect()
+    }
+
+    /// Checks that the representative holds a power of attorney for `powers` valid at `time`,
+    /// the validity period isn't checked without `time`. Operations without powers can't be
+    /// performed by a power of attorney
+    pub fn representative_may(
+        &self,
+        member_id: &MemberId,
+        representative: &str,
+        powers: Powers,
+        time: Option<DateTime<Utc>>,
+    ) -> bool {
+        !powers.is_empty()
+            && self
+                .representative_powers(member_id, representative)
+                .iter()
+                .any(|poa| {
+                    poa.allows(representative, powers)
+                        && time.map_or(true, |time| poa.is_valid_at(time))
+                })
+    }
+
     // It will contain hash of SignContract transaction for deed and application document if contract
     pub fn get_sign_contract_tx(&self, document_id: &DocumentId) -> Option<ContractSign> {
         self.sign_contract_tx().get(document_id)
@@ -260,10 +597,10 @@ where
             })
     }
 
-    // TODO time service as a dependency
-    /*pub fn time(&self) -> Entry<&T, DateTime<Utc>> {
+    /// Consensus time of the validators maintained by the time service
+    pub fn time(&self) -> Entry<&T, DateTime<Utc>> {
         Entry::new("exonum_time.time", &self.view)
-    }*/
+    }
 
     pub fn is_owner(&self, member_id: &MemberIdentity, obj_id: &ObjectId) -> bool {
         self.rightholders(obj_id)
@@ -279,6 +616,18 @@ where
             .map(|(uid, _)| uid)
     }
 
+    /// Height of the block which is being created
+    pub fn next_height(&self) -> u64 {
+        CoreSchema::new(&self.view).block_hashes_by_height().len()
+    }
+
+    pub fn tx_height(&self, tx_hash: &Hash) -> Option<u64> {
+        CoreSchema::new(&self.view)
+            .transactions_locations()
+            .get(tx_hash)
+            .map(|location| location.block_height().0)
+    }
+
     pub fn is_validator(&self, node_id: &PublicKey) -> bool {
         CoreSchema::new(&self.view)
             .actual_configuration()
@@ -287,10 +636,45 @@ where
             .any(|v| v.consensus_key.eq(node_id))
     }
 
+    /// Checks that the node may attest the member: it's a validator or an attestation node
+    /// of the member. Nodes which only receive the member data can't attest it
+    pub fn is_trusted_node(&self, member_id: &MemberId, node: &PublicKey) -> bool {
+        let is_validator = CoreSchema::new(&self.view)
+            .actual_configuration()
+            .validator_keys
+            .iter()
+            .any(|v| v.consensus_key.eq(node) || v.service_key.eq(node));
+        is_validator
+            || self
+                .participant_keys(member_id)
+                .iter()
+                .any(|key| key.key() == node && key.is_attestation())
+    }
+
+    /// Attestations accepted by the transactions, each one may be used only once.
+    /// Expired attestations can't be used anyway, they're removed on block commit
+    pub fn esia_attestations(&self) -> MapIndex<&T, Hash, u64> {
+        MapIndex::new("fips.esia.attestations", &self.view)
+    }
+
+    /// Accepted attestations by the big-endian height they are valid until followed by their id
+    fn esia_attestation_expiry(&self) -> MapIndex<&T, Vec<u8>, Hash> {
+        MapIndex::new("fips.esia.attestations.expiry", &self.view)
+    }
+
     pub fn rights(&self, member_id: &MemberIdentity, obj_id: &ObjectId) -> Option<Rights> {
         self.rightholders(obj_id).get(member_id)
     }
 
+    /// All records of the member's rights to the object
+    pub fn member_rights(&self, member_id: &MemberIdentity, obj_id: &ObjectId) -> Vec<Rights> {
+        self.object_rights(obj_id)
+            .into_iter()
+            .filter(|(rightholder, _)| rightholder == member_id)
+            .map(|(_, rights)| rights)
+            .collect()
+    }
+
     pub fn core_private_data(&self) -> MapIndex<&T, Hash, RawMessage> {
         MapIndex::new("core.private_data", &self.view)
     }
@@ -317,14 +701,7 @@ where
             .contracts()
             .get(contract_tx_hash)
             .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
-        [&contract.seller(), &contract.buyer()]
-            .iter()
-            .flat_map(|p| self.participants(&p.id()).iter().collect::<Vec<String>>())
-            .map(|s| {
-                PublicKey::from_slice(s.as_bytes())
-                    .ok_or_else(|| Error::bad_stored_member(s.as_str()))
-            })
-            .collect::<Result<Vec<PublicKey>>>()
+        Ok(self.members_share(&[&contract.seller(), &contract.buyer()]))
     }
 
     pub fn check_result(&self, tx_hash: &Hash) -> Result<()> {
@@ -368,6 +745,7 @@ where
                     || state == State::REFUSED
                     || state == State::REJECTED
                     || state == State::APPROVED
+                    || state == State::TERMINATED
                     || state == State::REGISTERING
                     || state == State::AWAITING_USER_ACTION
                 {
@@ -404,6 +782,44 @@ impl<'a> Schema<&'a mut Fork> {
         ProofListIndex::new_in_family("fips.object_history", object_id, &mut self.view)
     }
 
+    fn contract_rights_mut(
+        &mut self,
+        object_id: &ObjectId,
+    ) -> MapIndex<&mut Fork, ContractId, ContractRights> {
+        MapIndex::new_in_family("fips.contract_rights", object_id, &mut self.view)
+    }
+
+    pub fn add_contract_rights(
+        &mut self,
+        object: &ObjectIdentity,
+        contract_id: &ContractId,
+        contract_rights: ContractRights,
+    ) {
+        self.ownership_mut(&contract_rights.rightholder().id())
+            .insert(object.clone());
+        self.contract_rights_mut(&object.id())
+            .put(contract_id, contract_rights);
+    }
+
+    /// Removes the rights received under the contract, returns `false` if there are none
+    pub fn remove_contract_rights(
+        &mut self,
+        object: &ObjectIdentity,
+        contract_id: &ContractId,
+    ) -> bool {
+        let object_id = &object.id();
+        let contract_rights = match self.contract_rights(object_id).get(contract_id) {
+            Some(contract_rights) => contract_rights,
+            None => return false,
+        };
+        self.contract_rights_mut(object_id).remove(contract_id);
+        let rightholder = contract_rights.rightholder();
+        if self.member_rights(&rightholder, object_id).is_empty() {
+            self.ownership_mut(&rightholder.id()).remove(object);
+        }
+        true
+    }
+
     fn ownership_mut(&mut self, member_id: &MemberId) -> ValueSetIndex<&mut Fork, ObjectIdentity> {
         ValueSetIndex::new_in_family("fips.ownership", member_id, &mut self.view)
     }
@@ -464,10 +880,109 @@ impl<'a> Schema<&'a mut Fork> {
         MapIndex::new("fips.contracts.correspondence_contacts", &mut self.view)
     }
 
-    pub fn put_member_token(&mut self, member_id: &MemberIdentity, token: String, oid: String) {
-        let mut index: MapIndex<&mut Fork, MemberIdentity, MemberEsiaToken> =
+    fn member_tokens_mut(&mut self) -> MapIndex<&mut Fork, MemberIdentity, MemberEsiaToken> {
+        MapIndex::new("fips.esia.member.token_fingerprint", &mut self.view)
+    }
+
+    pub fn put_member_token(
+        &mut self,
+        member_id: &MemberIdentity,
+        fingerprint: &Hash,
+        expires_at: DateTime<Utc>,
+    ) {
+        self.member_tokens_mut()
+            .put(member_id, MemberEsiaToken::new(fingerprint, expires_at))
+    }
+
+    /// Records the attestation as used, returns `false` if it was already accepted
+    pub fn accept_esia_attestation(&mut self, attestation: &EsiaAttestation) -> bool {
+        let id = attestation.id();
+        if self.esia_attestations().contains(&id) {
+            return false;
+        }
+        let mut index: MapIndex<&mut Fork, Hash, u64> =
+            MapIndex::new("fips.esia.attestations", &mut self.view);
+        index.put(&id, attestation.valid_until());
+        let mut expiry: MapIndex<&mut Fork, Vec<u8>, Hash> =
+            MapIndex::new("fips.esia.attestations.expiry", &mut self.view);
+        expiry.put(&attestation_expiry_key(attestation.valid_until(), &id), id);
+        true
+    }
+
+    /// Removes the accepted attestations which can't be used at `height` and later
+    pub fn prune_esia_attestations(&mut self, height: u64) {
+        let expired = self
+            .esia_attestation_expiry()
+            .iter()
+            .take_while(|(key, _)| key[..8] < height.to_be_bytes()[..])
+            .collect::<Vec<(Vec<u8>, Hash)>>();
+        let mut index: MapIndex<&mut Fork, Hash, u64> =
+            MapIndex::new("fips.esia.attestations", &mut self.view);
+        for (_, id) in &expired {
+            index.remove(id);
+        }
+        let mut expiry: MapIndex<&mut Fork, Vec<u8>, Hash> =
+            MapIndex::new("fips.esia.attestations.expiry", &mut self.view);
+        for (key, _) in &expired {
+            expiry.remove(key);
+        }
+    }
+
+    /// Applies the state migrations which weren't applied yet. Runs on every block commit,
+    /// so every migration is applied once, at the first block after the node update
+    pub fn migrate(&mut self) {
+        let mut applied: Entry<&mut Fork, u64> = Entry::new("fips.migrations", &mut self.view);
+        let from = applied.get().unwrap_or(0);
+        if from >= MIGRATIONS {
+            return;
+        }
+        applied.set(MIGRATIONS);
+        for migration in from..MIGRATIONS {
+            match migration {
+                0 => self.clear_deprecated_member_tokens(),
+                1 => self.backfill_contract_payers(),
+                _ => unreachable!("unknown migration {}", migration),
+            }
+        }
+    }
+
+    /// Removes plain ESIA tokens which were kept in the state before the `CredentialStore`
+    fn clear_deprecated_member_tokens(&mut self) {
+        let mut index: MapIndex<&mut Fork, MemberIdentity, DeprecatedMemberEsiaToken> =
             MapIndex::new("fips.esia.member.token", &mut self.view);
-        index.put(member_id, MemberEsiaToken::new(&token, &oid))
+        index.clear();
+    }
+
+    /// Records the payers of the fee calculations requested before `TaxRequest` recorded them,
+    /// the last successful request of the contract wins as it does on execution
+    fn backfill_contract_payers(&mut self) {
+        let mut requests = Vec::new();
+        for tx_hash in self.core_transactions().keys() {
+            let (contract_id, requestor) = match get_private_tx(self, &tx_hash) {
+                Ok(OwnershipTransactions::TaxRequest(tx)) => {
+                    (*tx.contract_tx_hash(), tx.requestor())
+                }
+                Ok(OwnershipTransactions::DeprecatedTaxRequest(tx)) => {
+                    (*tx.contract_tx_hash(), tx.requestor())
+                }
+                _ => continue,
+            };
+            let core = CoreSchema::new(&self.view);
+            let executed = core
+                .transaction_results()
+                .get(&tx_hash)
+                .map_or(false, |result| result.0.is_ok());
+            let location = match core.transactions_locations().get(&tx_hash) {
+                Some(location) if executed => location,
+                _ => continue,
+            };
+            let order = (location.block_height().0, location.position_in_block());
+            requests.push((order, contract_id, requestor));
+        }
+        requests.sort_by_key(|(order, _, _)| *order);
+        for (_, contract_id, requestor) in requests {
+            self.set_contract_payer(&contract_id, &requestor);
+        }
     }
 
     pub fn add_contracts_contacts_mut(
@@ -505,6 +1020,279 @@ impl<'a> Schema<&'a mut Fork> {
         }
     }
 
+    fn contract_revisions_mut(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> ProofListIndex<&mut Fork, ContractRevision> {
+        ProofListIndex::new_in_family("fips.contract_revisions", contract_id, &mut self.view)
+    }
+
+    /// Records the revision made at `timestamp`, `None` if the time is unknown
+    pub fn add_contract_revision(
+        &mut self,
+        contract_id: &ContractId,
+        proposer: MemberIdentity,
+        tx_hash: &Hash,
+        price: u64,
+        conditions: Conditions,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> Hash {
+        let mut revisions = self.contract_revisions_mut(contract_id);
+        let number = revisions.len();
+        revisions.push(ContractRevision::new(
+            number, proposer, tx_hash, price, conditions, timestamp,
+        ));
+        revisions.merkle_root()
+    }
+
+    fn contract_rights_changes_mut(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> ListIndex<&mut Fork, RightsChange> {
+        ListIndex::new_in_family(
+            "fips.contract_rights_changes",
+            contract_id,
+            &mut self.view,
+        )
+    }
+
+    pub fn add_rights_change(&mut self, contract_id: &ContractId, change: RightsChange) {
+        self.contract_rights_changes_mut(contract_id).push(change);
+    }
+
+    pub fn add_unrestored_rights(&mut self, contract_id: &ContractId, object: ObjectIdentity) {
+        let mut unrestored: ListIndex<&mut Fork, ObjectIdentity> = ListIndex::new_in_family(
+            "fips.contract_unrestored_rights",
+            contract_id,
+            &mut self.view,
+        );
+        unrestored.push(object);
+    }
+
+    /// Undoes the changes the contract made to the rights of the parties on approval and returns
+    /// the objects whose rights were changed after the approval, so they couldn't be restored.
+    /// Rights of other rightholders are left untouched.
+    pub fn undo_contract_rights(
+        &mut self,
+        contract_id: &ContractId,
+        buyer: &MemberIdentity,
+        seller: &MemberIdentity,
+    ) -> Vec<ObjectIdentity> {
+        let changes = self
+            .contract_rights_changes(contract_id)
+            .iter()
+            .collect::<Vec<RightsChange>>();
+        let mut unrestored = Vec::new();
+        for change in changes {
+            let object = change.object();
+            let mut rights = self
+                .rightholders(&object.id())
+                .iter()
+                .collect::<HashMap<MemberIdentity, Rights>>();
+            let mut restored = true;
+            // The buyer keeps rights received after the approval
+            if change.separate() {
+                restored = self.remove_contract_rights(&object, contract_id);
+            } else if rights.get(buyer) == Some(&change.granted()) {
+                match change.buyer_before() {
+                    Some(before) => rights.insert(buyer.clone(), before),
+                    None => rights.remove(buyer),
+                };
+            } else {
+                restored = false;
+            }
+            // The seller gets back the removed rights unless it has received others since then
+            if let Some(before) = change.seller_before() {
+                if rights.contains_key(seller) {
+                    restored = false;
+                } else {
+                    rights.insert(seller.clone(), before);
+                }
+            }
+            self.update_rights(&object, rights);
+            if !restored {
+                unrestored.push(object);
+            }
+        }
+        unrestored
+    }
+
+    fn contract_history_mut(
+        &mut self,
+        contract_id: &ContractId,
+    ) -> ProofListIndex<&mut Fork, ContractTransition> {
+        ProofListIndex::new_in_family("fips.contract_history", contract_id, &mut self.view)
+    }
+
+    /// Applies the action to the contract and records the transition in the contract history
+    pub fn apply_contract_action(
+        &mut self,
+        contract_id: &ContractId,
+        contract: Contract,
+        action: Action,
+        actor: Option<MemberIdentity>,
+        tx_hash: &Hash,
+    ) -> Result<Contract> {
+        let from = contract.state();
+        let name = action.name();
+        let contract = contract.apply(action)?;
+        let height = self.next_height();
+        let transition =
+            ContractTransition::new(from, contract.state(), name, actor, tx_hash, height);
+        self.contract_history_mut(contract_id).push(transition);
+        Ok(contract)
+    }
+
+    fn contract_deadlines_mut(&mut self) -> MapIndex<&mut Fork, String, u64> {
+        MapIndex::new("fips.contract_deadlines", &mut self.view)
+    }
+
+    fn contract_state_changes_mut(
+        &mut self,
+    ) -> MapIndex<&mut Fork, ContractId, ContractStateChange> {
+        MapIndex::new("fips.contract_state_changes", &mut self.view)
+    }
+
+    fn contract_status_times_mut(
+        &mut self,
+        status: &str,
+    ) -> MapIndex<&mut Fork, Vec<u8>, ContractId> {
+        MapIndex::new_in_family("fips.contract_status_times", &status.to_string(), &mut self.view)
+    }
+
+    fn contract_untimed_states_mut(&mut self) -> MapIndex<&mut Fork, ContractId, ()> {
+        MapIndex::new("fips.contract_untimed_states", &mut self.view)
+    }
+
+    fn contract_refuse_reasons_mut(&mut self) -> MapIndex<&mut Fork, ContractId, String> {
+        MapIndex::new("fips.contract_refuse_reasons", &mut self.view)
+    }
+
+    fn contract_payers_mut(&mut self) -> MapIndex<&mut Fork, ContractId, MemberIdentity> {
+        MapIndex::new("fips.contract_payers", &mut self.view)
+    }
+
+    pub fn set_contract_deadline(&mut self, status: &str, days: u64) {
+        if days == 0 {
+            self.contract_deadlines_mut().remove(&status.to_string())
+        } else {
+            self.contract_deadlines_mut().put(&status.to_string(), days)
+        }
+    }
+
+    pub fn set_contract_refuse_reason(&mut self, contract_id: &ContractId, reason: String) {
+        self.contract_refuse_reasons_mut().put(contract_id, reason)
+    }
+
+    pub fn set_contract_payer(&mut self, contract_id: &ContractId, payer: &MemberIdentity) {
+        self.contract_payers_mut().put(contract_id, payer.clone())
+    }
+
+    /// Refuses the contracts which held their status longer than the deadline at the consensus
+    /// time. Runs on every block commit, so a failed contract is logged and skipped instead of
+    /// failing the block.
+    pub fn expire_contracts(&mut self) -> Vec<ContractId> {
+        let time = match self.time().get() {
+            Some(time) => time,
+            None => return Vec::new(),
+        };
+        self.backfill_contract_state_times(time);
+
+        let mut expired = Vec::new();
+        for contract_id in self.expired_contracts(time) {
+            let contract = match self.contracts().get(&contract_id) {
+                Some(contract) => contract,
+                None => continue,
+            };
+            let status = match ContractStatus::try_from(contract.state()) {
+                Ok(status) => status,
+                Err(_) => continue,
+            };
+            // No transaction expires the contract, the transition refers to the zero hash
+            let contract = match self.apply_contract_action(
+                &contract_id,
+                contract,
+                Action::Expire,
+                None,
+                &Hash::zero(),
+            ) {
+                Ok(contract) => contract,
+                Err(e) => {
+                    warn!("Unable to expire contract {}: {}", contract_id, e.info());
+                    continue;
+                }
+            };
+
+            for ownership in contract.conditions().objects() {
+                self.set_unpublished_contract(&ownership.object().id(), &contract_id);
+            }
+            let reason = format!("deadline for status '{}' expired at {}", status, time);
+            self.set_contract_refuse_reason(&contract_id, reason);
+            self.update_contract(&contract_id, contract);
+            expired.push(contract_id);
+        }
+        expired
+    }
+
+    /// Status changes made before the consensus time was known count from the first known time
+    fn backfill_contract_state_times(&mut self, time: DateTime<Utc>) {
+        let untimed = self
+            .contract_untimed_states()
+            .keys()
+            .collect::<Vec<ContractId>>();
+        for cid in untimed {
+            if let Some(change) = self.contract_state_changes().get(&cid) {
+                let change = ContractStateChange::new(change.status(), Some(time));
+                self.put_contract_state_change(&cid, change);
+            }
+        }
+        self.contract_untimed_states_mut().clear();
+    }
+
+    fn track_contract_state(&mut self, cid: &ContractId, contract: &Contract) {
+        let status = match ContractStatus::try_from(contract.state()) {
+            Ok(status) => status.to_string(),
+            Err(_) => return,
+        };
+        if contract.is_finished().unwrap_or(true) {
+            self.remove_contract_state_change(cid);
+            return;
+        }
+        let is_changed = self
+            .contract_state_changes()
+            .get(cid)
+            .map_or(true, |change| change.status() != status);
+        if is_changed {
+            let change = ContractStateChange::new(&status, self.time().get());
+            self.put_contract_state_change(cid, change);
+        }
+    }
+
+    fn put_contract_state_change(&mut self, cid: &ContractId, change: ContractStateChange) {
+        self.remove_contract_state_change(cid);
+        match change.timestamp() {
+            Some(timestamp) => self
+                .contract_status_times_mut(change.status())
+                .put(&status_time_key(timestamp, cid), *cid),
+            None => self.contract_untimed_states_mut().put(cid, ()),
+        }
+        self.contract_state_changes_mut().put(cid, change);
+    }
+
+    fn remove_contract_state_change(&mut self, cid: &ContractId) {
+        let change = match self.contract_state_changes().get(cid) {
+            Some(change) => change,
+            None => return,
+        };
+        match change.timestamp() {
+            Some(timestamp) => self
+                .contract_status_times_mut(change.status())
+                .remove(&status_time_key(timestamp, cid)),
+            None => self.contract_untimed_states_mut().remove(cid),
+        }
+        self.contract_state_changes_mut().remove(cid);
+    }
+
     fn checks_mut(&mut self, id: &Hash) -> MapIndex<&mut Fork, u16, CheckResult> {
         MapIndex::new_in_family("fips.checks", id, &mut self.view)
     }
@@ -544,6 +1332,41 @@ impl<'a> Schema<&'a mut Fork> {
         MapIndex::new("fips.contract_files.application", &mut self.view)
     }
 
+    fn contract_file_hashes_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, Hash> {
+        MapIndex::new("fips.contract_files.data_hash", &mut self.view)
+    }
+
+    fn generated_documents_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, Hash> {
+        MapIndex::new("fips.contract_files.generated", &mut self.view)
+    }
+
+    fn scan_verdicts_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, u8> {
+        MapIndex::new("fips.documents.scan_verdict", &mut self.view)
+    }
+
+    fn encrypted_documents_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, bool> {
+        MapIndex::new("fips.documents.encrypted", &mut self.view)
+    }
+
+    fn data_documents_mut(&mut self, data_hash: &Hash) -> ValueSetIndex<&mut Fork, DocumentId> {
+        ValueSetIndex::new_in_family("fips.documents.by_data_hash", data_hash, &mut self.view)
+    }
+
+    fn contract_file_owners_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, MemberIdentity> {
+        MapIndex::new("fips.contract_files.owner", &mut self.view)
+    }
+
+    fn contract_file_roots_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, DocumentId> {
+        MapIndex::new("fips.contract_files.roots", &mut self.view)
+    }
+
+    fn contract_file_versions_mut(
+        &mut self,
+        root: &DocumentId,
+    ) -> ListIndex<&mut Fork, DocumentId> {
+        ListIndex::new_in_family("fips.contract_files.versions", root, &mut self.view)
+    }
+
     fn deprecated_sign_contract_tx_mut(&mut self) -> MapIndex<&mut Fork, DocumentId, Hash> {
         MapIndex::new("fips.attachment_signs", &mut self.view)
     }
@@ -556,6 +1379,36 @@ impl<'a> Schema<&'a mut Fork> {
         ListIndex::new_in_family("fips.participants", member_id, &mut self.view)
     }
 
+    fn member_profiles_mut(&mut self) -> MapIndex<&mut Fork, MemberIdentity, MemberProfile> {
+        MapIndex::new("fips.members.profiles", &mut self.view)
+    }
+
+    fn member_keys_mut(
+        &mut self,
+        member_id: &MemberId,
+    ) -> MapIndex<&mut Fork, PublicKey, ParticipantKey> {
+        MapIndex::new_in_family("fips.members.keys", member_id, &mut self.view)
+    }
+
+    fn member_links_mut(&mut self) -> MapIndex<&mut Fork, MemberIdentity, MemberLink> {
+        MapIndex::new("fips.members.links", &mut self.view)
+    }
+
+    fn powers_of_attorney_mut(&mut self) -> MapIndex<&mut Fork, Hash, PowerOfAttorney> {
+        MapIndex::new("fips.members.powers_of_attorney", &mut self.view)
+    }
+
+    fn member_powers_of_attorney_mut(
+        &mut self,
+        member_id: &MemberId,
+    ) -> ListIndex<&mut Fork, Hash> {
+        ListIndex::new_in_family(
+            "fips.members.powers_of_attorney.by_member",
+            member_id,
+            &mut self.view,
+        )
+    }
+
     fn contract_reference_number_mut(&mut self) -> MapIndex<&mut Fork, ContractId, String> {
         MapIndex::new("fips.contracts.reference_number", &mut self.view)
     }
@@ -607,8 +1460,16 @@ impl<'a> Schema<&'a mut Fork> {
         for uid in rights.keys() {
             self.ownership_mut(&uid.id()).insert(object.clone());
         }
+        // Members keep the object while they hold rights received under contracts
+        let contract_rightholders = self
+            .contract_rights(object_id)
+            .iter()
+            .map(|(_, contract_rights)| contract_rights.rightholder())
+            .collect::<Vec<MemberIdentity>>();
         for uid in to_remove.iter() {
-            self.ownership_mut(&uid.id()).remove(&object);
+            if !contract_rightholders.contains(uid) {
+                self.ownership_mut(&uid.id()).remove(&object);
+            }
         }
         let mut rightholders = self.rightholders_mut(object_id);
         for uid in to_remove.iter() {
@@ -705,15 +1566,23 @@ impl<'a> Schema<&'a mut Fork> {
         self.bid_history_mut(lot_id).push(tx_hash)
     }
 
-    pub fn add_contract(&mut self, cid: &ContractId, contract: Contract) {
+    /// Stores a new contract opened by `actor`, the contract id is the hash of the opening
+    /// transaction
+    pub fn add_contract(&mut self, cid: &ContractId, contract: Contract, actor: MemberIdentity) {
         self.member_contracts_mut(&contract.buyer().id())
             .put(cid, ());
         self.member_contracts_mut(&contract.seller().id())
             .put(cid, ());
+        let state = contract.state();
+        let height = self.next_height();
+        let transition =
+            ContractTransition::new(state, state, OPEN_ACTION, Some(actor), cid, height);
+        self.contract_history_mut(cid).push(transition);
         self.update_contract(cid, contract);
     }
 
     pub fn update_contract(&mut self, cid: &ContractId, contract: Contract) {
+        self.track_contract_state(cid, &contract);
         self.contracts_mut().put(cid, contract);
     }
 
@@ -725,6 +1594,7 @@ impl<'a> Schema<&'a mut Fork> {
             self.member_contracts_mut(&contract.seller().id())
                 .remove(cid);
             self.contracts_mut().remove(cid);
+            self.remove_contract_state_change(cid);
         }
     }
 
@@ -797,6 +1667,55 @@ impl<'a> Schema<&'a mut Fork> {
         );
     }
 
+    pub fn set_contract_file_hash(&mut self, document_id: &DocumentId, data_hash: &Hash) {
+        self.contract_file_hashes_mut().put(document_id, *data_hash);
+    }
+
+    pub fn set_generated_document(&mut self, document_id: &DocumentId, template: &Hash) {
+        self.generated_documents_mut().put(document_id, *template);
+    }
+
+    pub fn set_scan_verdict(&mut self, document_id: &DocumentId, verdict: u8) {
+        self.scan_verdicts_mut().put(document_id, verdict);
+    }
+
+    /// Records the document data in the `FileStore`, encrypted data is flagged so the readers
+    /// don't have to look into the stored file
+    pub fn add_document_data(
+        &mut self,
+        document_id: &DocumentId,
+        data_hash: &Hash,
+        encrypted: bool,
+    ) {
+        if encrypted {
+            self.encrypted_documents_mut().put(document_id, true);
+        }
+        self.data_documents_mut(data_hash).insert(*document_id);
+    }
+
+    pub fn set_contract_file_owner(&mut self, document_id: &DocumentId, owner: &MemberIdentity) {
+        self.contract_file_owners_mut()
+            .put(document_id, owner.clone());
+    }
+
+    /// Links the document which replaces `previous` to its version history
+    pub fn add_contract_file_version(&mut self, previous: &DocumentId, document: &DocumentId) {
+        let root = self
+            .contract_file_roots()
+            .get(previous)
+            .unwrap_or(*previous);
+        {
+            let mut versions = self.contract_file_versions_mut(&root);
+            if versions.is_empty() {
+                versions.push(*previous);
+            }
+            versions.push(*document);
+        }
+        let mut roots = self.contract_file_roots_mut();
+        roots.put(previous, root);
+        roots.put(document, root);
+    }
+
     // pub fn remove_file(&mut self, member_id: &MemberId, document: &DocumentId) {
     //     self.attachments_mut(member_id).remove(document);
     // }
@@ -812,6 +1731,44 @@ impl<'a> Schema<&'a mut Fork> {
         self.contract_notifications_mut(contract_id).clear();
     }
 
+    /// Replaces the contract document with the new version, signatures of the replaced
+    /// deed or application don't pass to the new version and are dropped
+    pub fn replace_contract_document(
+        &mut self,
+        contract_id: &ContractId,
+        replaced: &DocumentId,
+        document: &DocumentId,
+        metadata: AttachmentMetadata,
+        data_hash: &Hash,
+        owner: &MemberIdentity,
+    ) -> Result<()> {
+        let current = self
+            .contract_document(contract_id, replaced)
+            .ok_or_else(|| Error::no_attachment(replaced))?;
+        if current.file_type() != metadata.file_type() {
+            Error::bad_file_type("file type must match the type of the replaced document").ok()?
+        }
+        match AttachmentType::try_from(metadata.file_type())? {
+            AttachmentType::Deed => {
+                self.sign_contract_tx_mut().remove(replaced);
+                self.set_contract_file_hash(document, data_hash);
+                self.attach_contract_deed(contract_id, document, metadata);
+            }
+            AttachmentType::Application => {
+                self.sign_contract_tx_mut().remove(replaced);
+                self.set_contract_file_hash(document, data_hash);
+                self.attach_contract_application(contract_id, document, metadata);
+            }
+            _ => {
+                self.remove_contract_file(contract_id, replaced);
+                self.set_contract_file_owner(document, owner);
+                self.attach_contract_file(contract_id, document, metadata);
+            }
+        }
+        self.add_contract_file_version(replaced, document);
+        Ok(())
+    }
+
     pub fn deprecated_add_sign_contract_tx(&mut self, document_id: &DocumentId, tx_hash: Hash) {
         self.deprecated_sign_contract_tx_mut()
             .put(document_id, tx_hash)
@@ -832,8 +1789,266 @@ impl<'a> Schema<&'a mut Fork> {
         guard.put(document_id, contract_to_sign)
     }
 
-    pub fn add_participant(&mut self, member_id: &MemberId, node_name: String) {
-        self.participants_mut(member_id).push(node_name)
+    pub fn register_member(
+        &mut self,
+        member: &MemberIdentity,
+        profile: MemberProfile,
+    ) -> Result<()> {
+        if self.member_profiles().contains(member) {
+            Error::member_already_registered(member).ok()?
+        }
+        self.member_profiles_mut().put(member, profile);
+        Ok(())
+    }
+
+    pub fn update_member_profile(
+        &mut self,
+        member: &MemberIdentity,
+        profile: MemberProfile,
+    ) -> Result<()> {
+        if !self.member_profiles().contains(member) {
+            Error::no_member_profile(member).ok()?
+        }
+        self.member_profiles_mut().put(member, profile);
+        Ok(())
+    }
+
+    /// Moves the nodes added by name to the registry
+    fn migrate_participants(&mut self, member_id: &MemberId) {
+        let keys = self.participant_keys(member_id);
+        let mut index = self.member_keys_mut(member_id);
+        for key in keys {
+            if !index.contains(key.key()) {
+                index.put(key.key(), key.clone());
+            }
+        }
+        self.participants_mut(member_id).clear();
+    }
+
+    pub fn add_participant_key(
+        &mut self,
+        member: &MemberIdentity,
+        key: ParticipantKey,
+    ) -> Result<()> {
+        let member_id = member.id();
+        self.migrate_participants(&member_id);
+        if self.member_keys(&member_id).contains(key.key()) {
+            Error::participant_key_exists(member, key.key()).ok()?
+        }
+        self.member_keys_mut(&member_id).put(key.key(), key);
+        Ok(())
+    }
+
+    pub fn remove_participant_key(
+        &mut self,
+        member: &MemberIdentity,
+        key: &PublicKey,
+    ) -> Result<()> {
+        let member_id = member.id();
+        self.migrate_participants(&member_id);
+        if !self.member_keys(&member_id).contains(key) {
+            Error::no_participant_key(member, key).ok()?
+        }
+        self.member_keys_mut(&member_id).remove(key);
+        Ok(())
+    }
+
+    /// Replaces the key keeping its type and node name
+    pub fn rotate_participant_key(
+        &mut self,
+        member: &MemberIdentity,
+        old_key: &PublicKey,
+        new_key: &PublicKey,
+    ) -> Result<()> {
+        let member_id = member.id();
+        self.migrate_participants(&member_id);
+        let key = self
+            .member_keys(&member_id)
+            .get(old_key)
+            .ok_or_else(|| Error::no_participant_key(member, old_key))?;
+        if self.member_keys(&member_id).contains(new_key) {
+            Error::participant_key_exists(member, new_key).ok()?
+        }
+        let mut index = self.member_keys_mut(&member_id);
+        index.remove(old_key);
+        index.put(new_key, key.with_key(new_key));
+        Ok(())
+    }
+
+    /// Links the member to its successor, a linked member may be migrated later by the same link
+    pub fn link_member(
+        &mut self,
+        member: &MemberIdentity,
+        successor: &MemberIdentity,
+        migrate: bool,
+        tx_hash: &Hash,
+    ) -> Result<()> {
+        if let Some(link) = self.member_links().get(successor) {
+            Error::member_linked(successor, &link.successor()).ok()?
+        }
+        if let Some(link) = self.member_links().get(member) {
+            if link.successor() != *successor || link.migrated() || !migrate {
+                Error::member_linked(member, &link.successor()).ok()?
+            }
+        }
+        if migrate {
+            self.migrate_member(member, successor, tx_hash)?;
+        }
+        self.member_links_mut()
+            .put(member, MemberLink::new(successor.clone(), tx_hash, migrate));
+        Ok(())
+    }
+
+    /// Moves rights, lots, contracts, powers of attorney and node keys of the member to its
+    /// successor. The profile and the ESIA token identify the member, they aren't moved
+    fn migrate_member(
+        &mut self,
+        member: &MemberIdentity,
+        successor: &MemberIdentity,
+        tx_hash: &Hash,
+    ) -> Result<()> {
+        let member_id = member.id();
+        let successor_id = successor.id();
+        let objects = self
+            .ownership(&member_id)
+            .iter()
+            .map(|(_, object)| object)
+            .collect::<Vec<ObjectIdentity>>();
+        let contracts = self
+            .member_contracts(&member_id)
+            .keys()
+            .collect::<Vec<ContractId>>();
+        if let Some(object) = objects.iter().find(|object| {
+            self.object_rights(&object.id())
+                .iter()
+                .any(|(rightholder, _)| rightholder == successor)
+        }) {
+            Error::member_link_conflict(successor, &format!("rights on '{}'", object)).ok()?
+        }
+        if let Some(contract_id) = contracts
+            .iter()
+            .find(|contract_id| self.member_contracts(&successor_id).contains(contract_id))
+        {
+            Error::member_link_conflict(successor, &format!("contract '{}'", contract_id)).ok()?
+        }
+
+        for object in objects {
+            let object_id = object.id();
+            let contract_rights = self
+                .contract_rights(&object_id)
+                .iter()
+                .filter(|(_, contract_rights)| contract_rights.rightholder() == *member)
+                .collect::<Vec<(ContractId, ContractRights)>>();
+            for (contract_id, contract_rights) in contract_rights {
+                self.contract_rights_mut(&object_id).remove(&contract_id);
+                self.add_contract_rights(
+                    &object,
+                    &contract_id,
+                    ContractRights::new(successor.clone(), contract_rights.rights()),
+                );
+                self.ownership_mut(&member_id).remove(&object);
+            }
+            let rights = self
+                .rightholders(&object_id)
+                .iter()
+                .map(|(rightholder, rights)| {
+                    if rightholder == *member {
+                        (successor.clone(), rights)
+                    } else {
+                        (rightholder, rights)
+                    }
+                })
+                .collect::<HashMap<MemberIdentity, Rights>>();
+            let unstructured = self
+                .ownership_unstructured(&object_id)
+                .iter()
+                .map(|ownership| {
+                    if ownership.rightholder().as_ref() == Some(member) {
+                        OwnershipUnstructured::new(
+                            ownership.data(),
+                            Some(successor.clone()),
+                            ownership.exclusive(),
+                        )
+                    } else {
+                        ownership
+                    }
+                })
+                .collect();
+            self.update_rights(&object, rights);
+            self.update_unstructured_ownership(&object, unstructured);
+            self.update_object_history(&object_id, Change::new(tx_hash));
+        }
+
+        let lots = self
+            .member_lots(&member_id)
+            .iter()
+            .map(|(_, lot_id)| lot_id)
+            .collect::<Vec<LotId>>();
+        for lot_id in lots {
+            if let Some(lot) = self.lots().get(&lot_id) {
+                if lot.seller() == *member {
+                    self.lots_mut()
+                        .put(&lot_id, lot.with_seller(successor.clone()));
+                }
+            }
+            self.member_lots_mut(&member_id).remove(&lot_id);
+            self.member_lots_mut(&successor_id).insert(lot_id);
+        }
+
+        let height = self.next_height();
+        for contract_id in contracts {
+            if let Some(contract) = self.contracts().get(&contract_id) {
+                let state = contract.state();
+                let transition =
+                    ContractTransition::new(state, state, "migrate_member", None, tx_hash, height);
+                self.contract_history_mut(&contract_id).push(transition);
+                self.update_contract(&contract_id, contract.replace_member(member, successor));
+            }
+            self.member_contracts_mut(&member_id).remove(&contract_id);
+            self.member_contracts_mut(&successor_id)
+                .put(&contract_id, ());
+        }
+
+        let powers_of_attorney = self
+            .member_powers_of_attorney(&member_id)
+            .iter()
+            .collect::<Vec<Hash>>();
+        self.member_powers_of_attorney_mut(&successor_id)
+            .extend(powers_of_attorney);
+        self.member_powers_of_attorney_mut(&member_id).clear();
+
+        // Nodes of the member keep the private data shared with them, so they serve the successor
+        self.migrate_participants(&member_id);
+        let keys = self
+            .member_keys(&member_id)
+            .values()
+            .collect::<Vec<ParticipantKey>>();
+        let mut successor_keys = self.member_keys_mut(&successor_id);
+        for key in keys {
+            if !successor_keys.contains(key.key()) {
+                successor_keys.put(key.key(), key.clone());
+            }
+        }
+        self.member_keys_mut(&member_id).clear();
+        Ok(())
+    }
+
+    pub fn add_power_of_attorney(&mut self, tx_hash: &Hash, power_of_attorney: PowerOfAttorney) {
+        let member_id = power_of_attorney.member().id();
+        self.powers_of_attorney_mut()
+            .put(tx_hash, power_of_attorney);
+        self.member_powers_of_attorney_mut(&member_id)
+            .push(*tx_hash);
+    }
+
+    pub fn revoke_power_of_attorney(&mut self, tx_hash: &Hash) -> Result<()> {
+        let power_of_attorney = self
+            .powers_of_attorney()
+            .get(tx_hash)
+            .ok_or_else(|| Error::no_power_of_attorney_tx(tx_hash))?;
+        self.powers_of_attorney_mut()
+            .put(tx_hash, power_of_attorney.revoke());
+        Ok(())
     }
 
     fn contract_calculations_mut(
@@ -903,3 +2118,724 @@ impl<'a> Schema<&'a mut Fork> {
         self.lot_calculations_mut().remove(lot_tx_hash)
     }
 }
+
+/// Big-endian height followed by the attestation id, keeps the attestations ordered by expiry
+fn attestation_expiry_key(valid_until: u64, id: &Hash) -> Vec<u8> {
+    let mut key = valid_until.to_be_bytes().to_vec();
+    key.extend_from_slice(id.as_ref());
+    key
+}
+
+/// Big-endian timestamp in seconds followed by the contract id, keeps the status changes ordered
+/// by time
+fn status_time_key(timestamp: DateTime<Utc>, contract_id: &ContractId) -> Vec<u8> {
+    let seconds = timestamp.timestamp().max(0) as u64;
+    let mut key = seconds.to_be_bytes().to_vec();
+    key.extend_from_slice(contract_id.as_ref());
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use blockp_core::crypto::{self, hash};
+
+    use crate::data::member::Authority;
+    use crate::fixtures::*;
+
+    use super::*;
+
+    fn rights_of(
+        schema: &Schema<&mut Fork>,
+        object: &ObjectIdentity,
+    ) -> HashMap<MemberIdentity, Rights> {
+        schema.rightholders(&object.id()).iter().collect()
+    }
+
+    #[test]
+    fn undo_rights_after_licence() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let other_object = ObjectIdentity::from_str("trademark::654321").unwrap();
+        let owned = Rights::new_owned();
+        let before = vec![(seller(), owned.clone()), (licensee(), licence())]
+            .into_iter()
+            .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), before.clone());
+        schema.update_rights(
+            &other_object,
+            vec![(buyer(), licence())].into_iter().collect(),
+        );
+
+        // Approval of a licence contract keeps the seller's rights
+        let granted = licence();
+        schema.add_rights_change(
+            &contract_id,
+            RightsChange::new(object(), granted.clone(), false, None, None),
+        );
+        let mut after = before.clone();
+        after.insert(buyer(), granted);
+        schema.update_rights(&object(), after);
+
+        let unrestored = schema.undo_contract_rights(&contract_id, &buyer(), &seller());
+        assert!(unrestored.is_empty());
+        assert_eq!(rights_of(&schema, &object()), before);
+        // Rights to objects outside the contract stay with the buyer
+        assert!(rights_of(&schema, &other_object).contains_key(&buyer()));
+        assert!(schema.ownership(&buyer().id()).contains(&other_object));
+        assert!(!schema.ownership(&buyer().id()).contains(&object()));
+    }
+
+    #[test]
+    fn undo_rights_after_expropriation() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let owned = Rights::new_owned();
+        let before = vec![(seller(), owned.clone()), (licensee(), licence())]
+            .into_iter()
+            .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), before.clone());
+
+        // Exclusive right moves from the seller to the buyer with a new starting time
+        let transferred = owned.transfer_owned(Utc::now());
+        schema.add_rights_change(
+            &contract_id,
+            RightsChange::new(object(), transferred.clone(), false, None, Some(owned.clone())),
+        );
+        let after = vec![(buyer(), transferred), (licensee(), licence())]
+            .into_iter()
+            .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), after);
+
+        let unrestored = schema.undo_contract_rights(&contract_id, &buyer(), &seller());
+        assert!(unrestored.is_empty());
+        let restored = rights_of(&schema, &object());
+        assert_eq!(restored, before);
+        assert_eq!(restored.get(&seller()), Some(&owned));
+        assert!(schema.ownership(&seller().id()).contains(&object()));
+    }
+
+    #[test]
+    fn undo_separate_licence() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let before = vec![(seller(), Rights::new_owned()), (buyer(), licence())]
+            .into_iter()
+            .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), before.clone());
+
+        // The second licence of the buyer is kept apart from the first one
+        let granted = licence();
+        schema.add_contract_rights(
+            &object(),
+            &contract_id,
+            ContractRights::new(buyer(), granted.clone()),
+        );
+        schema.add_rights_change(
+            &contract_id,
+            RightsChange::new(object(), granted, true, None, None),
+        );
+        assert_eq!(schema.member_rights(&buyer(), &object().id()).len(), 2);
+
+        let unrestored = schema.undo_contract_rights(&contract_id, &buyer(), &seller());
+        assert!(unrestored.is_empty());
+        assert_eq!(rights_of(&schema, &object()), before);
+        assert_eq!(schema.member_rights(&buyer(), &object().id()).len(), 1);
+        assert!(schema.ownership(&buyer().id()).contains(&object()));
+    }
+
+    #[test]
+    fn undo_rights_keeps_later_changes() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let owned = Rights::new_owned();
+        schema.add_rights_change(
+            &contract_id,
+            RightsChange::new(object(), licence(), false, None, None),
+        );
+        // The registry changed the buyer's rights and granted a licence to another member
+        // after the approval
+        let changed = scoped_licence(0, vec![], vec![]);
+        let current = vec![
+            (seller(), owned),
+            (buyer(), changed),
+            (licensee(), licence()),
+        ]
+        .into_iter()
+        .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), current.clone());
+
+        let unrestored = schema.undo_contract_rights(&contract_id, &buyer(), &seller());
+        assert_eq!(unrestored, vec![object()]);
+        assert_eq!(rights_of(&schema, &object()), current);
+    }
+
+    #[test]
+    fn undo_rights_without_changes() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let rights = vec![(buyer(), Rights::new_owned())]
+            .into_iter()
+            .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), rights.clone());
+
+        let unrestored = schema.undo_contract_rights(&hash(b"contract"), &buyer(), &seller());
+        assert!(unrestored.is_empty());
+        assert_eq!(rights_of(&schema, &object()), rights);
+    }
+
+    fn set_time(schema: &mut Schema<&mut Fork>, time: DateTime<Utc>) {
+        let mut entry: Entry<&mut Fork, DateTime<Utc>> =
+            Entry::new("exonum_time.time", &mut schema.view);
+        entry.set(time);
+    }
+
+    fn open_contract(schema: &mut Schema<&mut Fork>, contract_id: &ContractId) {
+        schema.update_contract(contract_id, contract());
+    }
+
+    fn status(schema: &Schema<&mut Fork>, contract_id: &ContractId) -> ContractStatus {
+        let contract = schema.contracts().get(contract_id).unwrap();
+        ContractStatus::try_from(contract.state()).unwrap()
+    }
+
+    fn start_time() -> DateTime<Utc> {
+        "2021-03-01T10:00:00Z".parse::<DateTime<Utc>>().unwrap()
+    }
+
+    #[test]
+    fn expire_contract_after_deadline() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        schema.set_contract_deadline("request_confirm", 3);
+        set_time(&mut schema, start_time());
+        open_contract(&mut schema, &contract_id);
+        let deadline = start_time() + chrono::Duration::days(3);
+        assert_eq!(schema.contract_deadline(&contract_id), Some(deadline));
+
+        set_time(&mut schema, deadline - chrono::Duration::seconds(1));
+        assert!(schema.expire_contracts().is_empty());
+        assert_eq!(status(&schema, &contract_id).to_string(), "request_confirm");
+
+        set_time(&mut schema, deadline);
+        assert_eq!(schema.expire_contracts(), vec![contract_id]);
+        assert_eq!(status(&schema, &contract_id), ContractStatus::Refused);
+        let reason = schema.contract_refuse_reasons().get(&contract_id).unwrap();
+        assert!(reason.contains("request_confirm"));
+        let transition = schema.contract_history(&contract_id).last().unwrap();
+        assert_eq!(transition.action(), "expire");
+        assert_eq!(transition.actor(), None);
+        assert_eq!(transition.tx_hash(), &Hash::zero());
+        // Finished contracts have no deadline and expire only once
+        assert_eq!(schema.contract_deadline(&contract_id), None);
+        set_time(&mut schema, deadline + chrono::Duration::days(1));
+        assert!(schema.expire_contracts().is_empty());
+    }
+
+    #[test]
+    fn status_change_restarts_deadline() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        schema.set_contract_deadline("request_confirm", 3);
+        schema.set_contract_deadline("draft", 3);
+        set_time(&mut schema, start_time());
+        open_contract(&mut schema, &contract_id);
+
+        let changed = start_time() + chrono::Duration::days(2);
+        set_time(&mut schema, changed);
+        let tx_hash = hash(b"draft");
+        let contract = schema.contracts().get(&contract_id).unwrap();
+        let contract = schema
+            .apply_contract_action(&contract_id, contract, Action::New, None, &tx_hash)
+            .unwrap();
+        let contract = schema
+            .apply_contract_action(&contract_id, contract, Action::MakeDraft, None, &tx_hash)
+            .unwrap();
+        schema.update_contract(&contract_id, contract);
+        let change = schema.contract_state_changes().get(&contract_id).unwrap();
+        assert_eq!(change.status(), "draft");
+        assert_eq!(change.timestamp(), Some(changed));
+
+        set_time(&mut schema, start_time() + chrono::Duration::days(4));
+        assert!(schema.expire_contracts().is_empty());
+        set_time(&mut schema, changed + chrono::Duration::days(3));
+        assert_eq!(schema.expire_contracts(), vec![contract_id]);
+    }
+
+    #[test]
+    fn untimed_status_change_counts_from_first_time() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"untimed");
+        schema.set_contract_deadline("request_confirm", 3);
+        // The time service hasn't reported the consensus time yet
+        open_contract(&mut schema, &contract_id);
+        assert!(schema.expire_contracts().is_empty());
+        assert_eq!(schema.contract_deadline(&contract_id), None);
+
+        set_time(&mut schema, start_time());
+        assert!(schema.expire_contracts().is_empty());
+        let deadline = start_time() + chrono::Duration::days(3);
+        assert_eq!(schema.contract_deadline(&contract_id), Some(deadline));
+        assert!(schema.contract_untimed_states().keys().next().is_none());
+
+        set_time(&mut schema, deadline);
+        assert_eq!(schema.expire_contracts(), vec![contract_id]);
+    }
+
+    #[test]
+    fn no_expiry_without_deadline() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let unlimited = hash(b"unlimited");
+        set_time(&mut schema, start_time());
+        open_contract(&mut schema, &unlimited);
+        set_time(&mut schema, start_time() + chrono::Duration::days(365));
+        assert!(schema.expire_contracts().is_empty());
+        assert_eq!(status(&schema, &unlimited).to_string(), "request_confirm");
+        // Deadlines beyond the date range are capped instead of overflowing
+        schema.set_contract_deadline("request_confirm", u64::MAX);
+        assert!(schema.expire_contracts().is_empty());
+        assert!(schema.contract_deadline(&unlimited).is_some());
+    }
+
+    #[test]
+    fn contract_history_starts_with_opening() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let contract = contract();
+        let opened = contract.state();
+        schema.add_contract(&contract_id, contract, buyer());
+
+        let history = schema.contract_history(&contract_id);
+        assert_eq!(history.len(), 1);
+        let open = history.get(0).unwrap();
+        assert!(open.is_open());
+        assert_eq!(open.from(), opened);
+        assert_eq!(open.to(), opened);
+        assert_eq!(open.actor(), Some(buyer()));
+        assert_eq!(open.tx_hash(), &contract_id);
+        assert!(schema
+            .member_contracts(&seller().id())
+            .contains(&contract_id));
+
+        let tx_hash = hash(b"confirm");
+        let contract = schema.contracts().get(&contract_id).unwrap();
+        let contract = schema
+            .apply_contract_action(
+                &contract_id,
+                contract,
+                Action::Confirm(seller()),
+                Some(seller()),
+                &tx_hash,
+            )
+            .unwrap();
+        schema.update_contract(&contract_id, contract);
+
+        let history = schema.contract_history(&contract_id);
+        assert_eq!(history.len(), 2);
+        let confirm = history.get(1).unwrap();
+        assert!(!confirm.is_open());
+        assert_eq!(confirm.action(), "confirm");
+        assert_eq!(confirm.from(), opened);
+        assert_eq!(confirm.actor(), Some(seller()));
+        assert_eq!(confirm.tx_hash(), &tx_hash);
+    }
+
+    fn file(file_type: AttachmentType) -> AttachmentMetadata {
+        AttachmentMetadata::new("file.docx", None, file_type as u8, Utc::now())
+    }
+
+    #[test]
+    fn replace_deed_drops_signs() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let (deed, application) = (hash(b"deed"), hash(b"application"));
+        schema.attach_contract_deed(&contract_id, &deed, file(AttachmentType::Deed));
+        schema.attach_contract_application(
+            &contract_id,
+            &application,
+            file(AttachmentType::Application),
+        );
+        for document in &[deed, application] {
+            schema.set_contract_file_hash(document, &hash(document.as_ref()));
+            schema.add_sign_contract_tx(document, buyer(), BuyerSeller::Buyer, &hash(b"sign"));
+        }
+
+        let new_deed = hash(b"new deed");
+        schema
+            .replace_contract_document(
+                &contract_id,
+                &deed,
+                &new_deed,
+                file(AttachmentType::Deed),
+                &hash(b"new deed data"),
+                &seller(),
+            )
+            .unwrap();
+        assert_eq!(
+            schema.contract_deed(&contract_id).unwrap().tx_hash(),
+            &new_deed
+        );
+        assert_eq!(
+            schema.contract_file_hashes().get(&new_deed),
+            Some(hash(b"new deed data"))
+        );
+        assert_eq!(
+            schema.contract_file_history(&new_deed),
+            vec![deed, new_deed]
+        );
+        assert!(schema.get_sign_contract_tx(&deed).is_none());
+        // The application keeps its signature until it's replaced too
+        assert!(schema.get_sign_contract_tx(&application).is_some());
+
+        let new_application = hash(b"new application");
+        schema
+            .replace_contract_document(
+                &contract_id,
+                &application,
+                &new_application,
+                file(AttachmentType::Application),
+                &hash(b"new application data"),
+                &seller(),
+            )
+            .unwrap();
+        assert_eq!(
+            schema.contract_application(&contract_id).unwrap().tx_hash(),
+            &new_application
+        );
+        assert!(schema.get_sign_contract_tx(&application).is_none());
+        assert!(schema.contract_files(&contract_id).is_empty());
+    }
+
+    #[test]
+    fn replace_supplementary_document() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let document = hash(b"power of attorney");
+        schema.attach_contract_file(
+            &contract_id,
+            &document,
+            file(AttachmentType::PowerOfAttorney),
+        );
+
+        let new_document = hash(b"new power of attorney");
+        schema
+            .replace_contract_document(
+                &contract_id,
+                &document,
+                &new_document,
+                file(AttachmentType::PowerOfAttorney),
+                &hash(b"data"),
+                &buyer(),
+            )
+            .unwrap();
+        let files = schema.contract_files(&contract_id);
+        assert!(!files.contains(&document));
+        assert!(files.contains(&new_document));
+        assert_eq!(
+            schema.contract_file_owners().get(&new_document),
+            Some(buyer())
+        );
+        assert!(schema.contract_deed(&contract_id).is_none());
+    }
+
+    #[test]
+    fn esia_attestation_accepted_once() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let (public_key, secret_key) = crypto::gen_keypair();
+        let attestation = EsiaAttestation::issue(
+            buyer(),
+            "02583651380",
+            Authority::Role,
+            &hash(b"token"),
+            10,
+            &public_key,
+            &secret_key,
+        );
+        assert!(schema.accept_esia_attestation(&attestation));
+        assert!(!schema.accept_esia_attestation(&attestation));
+    }
+
+    #[test]
+    fn prune_expired_esia_attestations() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let (public_key, secret_key) = crypto::gen_keypair();
+        let attestations = [10, 20, 300]
+            .iter()
+            .map(|&valid_until| {
+                let attestation = EsiaAttestation::issue(
+                    buyer(),
+                    "02583651380",
+                    Authority::Role,
+                    &hash(b"token"),
+                    valid_until,
+                    &public_key,
+                    &secret_key,
+                );
+                assert!(schema.accept_esia_attestation(&attestation));
+                attestation.id()
+            })
+            .collect::<Vec<Hash>>();
+
+        schema.prune_esia_attestations(20);
+        assert!(!schema.esia_attestations().contains(&attestations[0]));
+        assert!(schema.esia_attestations().contains(&attestations[1]));
+        assert!(schema.esia_attestations().contains(&attestations[2]));
+        assert_eq!(schema.esia_attestation_expiry().keys().count(), 2);
+    }
+
+    #[test]
+    fn migration_clears_deprecated_member_tokens() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let deprecated = DeprecatedMemberEsiaToken::new("token", "oid");
+        {
+            let mut index: MapIndex<_, MemberIdentity, DeprecatedMemberEsiaToken> =
+                MapIndex::new("fips.esia.member.token", &mut schema.view);
+            index.put(&buyer(), deprecated.clone());
+        }
+        schema.put_member_token(&buyer(), &hash(b"token"), Utc::now());
+        schema.migrate();
+        {
+            let index: MapIndex<_, MemberIdentity, DeprecatedMemberEsiaToken> =
+                MapIndex::new("fips.esia.member.token", &schema.view);
+            assert!(index.get(&buyer()).is_none());
+            assert!(schema.member_token(&buyer()).is_some());
+        }
+
+        // Migrations aren't applied again
+        {
+            let mut index: MapIndex<_, MemberIdentity, DeprecatedMemberEsiaToken> =
+                MapIndex::new("fips.esia.member.token", &mut schema.view);
+            index.put(&seller(), deprecated);
+        }
+        schema.migrate();
+        let index: MapIndex<_, MemberIdentity, DeprecatedMemberEsiaToken> =
+            MapIndex::new("fips.esia.member.token", &schema.view);
+        assert!(index.get(&seller()).is_some());
+    }
+
+    #[test]
+    fn scan_verdict_of_document() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let document = hash(b"power of attorney");
+        assert_eq!(schema.scan_verdict(&document), ScanVerdict::NotScanned);
+        schema.set_scan_verdict(&document, ScanVerdict::Clean as u8);
+        assert_eq!(schema.scan_verdict(&document), ScanVerdict::Clean);
+        assert_eq!(schema.scan_verdict(&hash(b"deed")), ScanVerdict::NotScanned);
+    }
+
+    #[test]
+    fn encrypted_document_data() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let (other, encrypted) = (hash(b"other"), hash(b"encrypted other"));
+        let (data, encrypted_data) = (hash(b"data"), hash(b"encrypted data"));
+        schema.add_document_data(&other, &data, false);
+        schema.add_document_data(&encrypted, &encrypted_data, true);
+        assert!(!schema.is_document_encrypted(&other));
+        assert!(schema.is_document_encrypted(&encrypted));
+        assert!(schema.data_documents(&data).contains(&other));
+        assert!(schema.data_documents(&hash(b"plain data")).iter().next().is_none());
+    }
+
+    #[test]
+    fn replace_document_of_other_type() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = hash(b"contract");
+        let deed = hash(b"deed");
+        schema.attach_contract_deed(&contract_id, &deed, file(AttachmentType::Deed));
+
+        let replaced = schema.replace_contract_document(
+            &contract_id,
+            &deed,
+            &hash(b"new deed"),
+            file(AttachmentType::Application),
+            &hash(b"data"),
+            &buyer(),
+        );
+        assert!(replaced.is_err());
+        assert!(schema.contract_application(&contract_id).is_none());
+
+        let replaced = schema.replace_contract_document(
+            &contract_id,
+            &hash(b"unknown"),
+            &hash(b"new deed"),
+            file(AttachmentType::Deed),
+            &hash(b"data"),
+            &buyer(),
+        );
+        assert!(replaced.is_err());
+        assert_eq!(schema.contract_deed(&contract_id).unwrap().tx_hash(), &deed);
+    }
+
+    #[test]
+    fn add_remove_participant_keys() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let (key, _) = crypto::gen_keypair();
+        let node = ParticipantKey::new(&key, KeyType::Node as u8, "node");
+
+        schema.add_participant_key(&buyer(), node.clone()).unwrap();
+        assert!(schema.add_participant_key(&buyer(), node).is_err());
+        assert_eq!(schema.participant_keys(&buyer().id()).len(), 1);
+        assert!(schema.participant_keys(&seller().id()).is_empty());
+
+        schema.remove_participant_key(&buyer(), &key).unwrap();
+        assert!(schema.remove_participant_key(&buyer(), &key).is_err());
+        assert!(schema.participant_keys(&buyer().id()).is_empty());
+    }
+
+    #[test]
+    fn rotate_participant_key() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let (old_key, _) = crypto::gen_keypair();
+        let (new_key, _) = crypto::gen_keypair();
+        let (other_key, _) = crypto::gen_keypair();
+        let attestation = ParticipantKey::new(&old_key, KeyType::Attestation as u8, "attestation");
+        schema.add_participant_key(&buyer(), attestation).unwrap();
+        let other = ParticipantKey::new(&other_key, KeyType::Node as u8, "other");
+        schema.add_participant_key(&buyer(), other).unwrap();
+
+        assert!(schema
+            .rotate_participant_key(&buyer(), &old_key, &other_key)
+            .is_err());
+        assert!(schema
+            .rotate_participant_key(&buyer(), &new_key, &old_key)
+            .is_err());
+        schema
+            .rotate_participant_key(&buyer(), &old_key, &new_key)
+            .unwrap();
+
+        let keys = schema.member_keys(&buyer().id());
+        assert!(!keys.contains(&old_key));
+        let rotated = keys.get(&new_key).unwrap();
+        assert_eq!(rotated.key(), &new_key);
+        assert_eq!(rotated.key_type(), KeyType::Attestation as u8);
+        assert_eq!(rotated.name(), "attestation");
+    }
+
+    #[test]
+    fn migrate_participants() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let name = "node-added-by-name-before-registry";
+        let name = &name[..crypto::PUBLIC_KEY_LENGTH];
+        let named_key = PublicKey::from_slice(name.as_bytes()).unwrap();
+        schema.participants_mut(&buyer().id()).push(name.to_owned());
+        schema
+            .participants_mut(&buyer().id())
+            .push("malformed".to_owned());
+        assert_eq!(schema.participant_keys(&buyer().id()).len(), 1);
+
+        let (key, _) = crypto::gen_keypair();
+        let node = ParticipantKey::new(&key, KeyType::Node as u8, "node");
+        schema.add_participant_key(&buyer(), node).unwrap();
+
+        assert!(schema.participants(&buyer().id()).is_empty());
+        let keys = schema.member_keys(&buyer().id());
+        assert_eq!(keys.values().count(), 2);
+        let migrated = keys.get(&named_key).unwrap();
+        assert_eq!(migrated.key_type(), KeyType::NodeAttestation as u8);
+        assert!(migrated.is_node() && migrated.is_attestation());
+        assert_eq!(migrated.name(), name);
+        assert!(schema.is_trusted_node(&buyer().id(), &named_key));
+        assert!(!schema.is_trusted_node(&buyer().id(), &key));
+    }
+
+    fn profile(name: &str) -> MemberProfile {
+        MemberProfile::new(name, "7707083893", "Москва", None, None, None)
+    }
+
+    #[test]
+    fn migrate_member() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let successor = MemberIdentity::from_str("ogrn::1027700132195").unwrap();
+        let contract_id = hash(b"contract");
+        let attorney_tx_hash = hash(b"power of attorney");
+        let (key, _) = crypto::gen_keypair();
+        let rights = vec![(seller(), Rights::new_owned())]
+            .into_iter()
+            .collect::<HashMap<MemberIdentity, Rights>>();
+        schema.update_rights(&object(), rights);
+        let contract = contract();
+        schema.add_contract(&contract_id, contract, buyer());
+        let now = Utc::now();
+        let metadata =
+            AttachmentMetadata::new("poa.xml", None, AttachmentType::PowerOfAttorney as u8, now);
+        let power_of_attorney = PowerOfAttorney::new(
+            seller(),
+            "02583651380",
+            Powers::SIGN_CONTRACT.bits(),
+            now - chrono::Duration::days(1),
+            now + chrono::Duration::days(1),
+            Attachment::new(metadata, &hash(b"poa"), None),
+            false,
+        );
+        schema.add_power_of_attorney(&attorney_tx_hash, power_of_attorney);
+        let node = ParticipantKey::new(&key, KeyType::Node as u8, "node");
+        schema.add_participant_key(&seller(), node).unwrap();
+        schema.register_member(&seller(), profile("ИП")).unwrap();
+        schema.put_member_token(&seller(), &hash(b"token"), now + chrono::Duration::hours(1));
+
+        let tx_hash = hash(b"link");
+        schema
+            .link_member(&seller(), &successor, true, &tx_hash)
+            .unwrap();
+
+        assert_eq!(
+            rights_of(&schema, &object()).get(&successor),
+            Some(&Rights::new_owned())
+        );
+        assert!(schema.ownership(&successor.id()).contains(&object()));
+        assert!(!schema.ownership(&seller().id()).contains(&object()));
+        assert!(schema
+            .member_contracts(&successor.id())
+            .contains(&contract_id));
+        assert!(!schema
+            .member_contracts(&seller().id())
+            .contains(&contract_id));
+        assert_eq!(
+            schema.contracts().get(&contract_id).unwrap().seller(),
+            successor
+        );
+        assert!(schema.member_has_power_of_attorney(&successor.id(), &attorney_tx_hash));
+        assert!(!schema.member_has_power_of_attorney(&seller().id(), &attorney_tx_hash));
+        assert!(schema.representative_may(
+            &successor.id(),
+            "02583651380",
+            Powers::SIGN_CONTRACT,
+            Some(now)
+        ));
+        assert!(schema.member_keys(&successor.id()).contains(&key));
+        assert!(schema.participant_keys(&seller().id()).is_empty());
+        // The successor attests with its own token and profile
+        assert!(schema.member_profiles().get(&successor).is_none());
+        assert_eq!(schema.member_profiles().get(&seller()), Some(profile("ИП")));
+        assert!(schema.member_token(&successor).is_none());
+        assert!(schema.member_token(&seller()).is_some());
+        let link = schema.member_links().get(&seller()).unwrap();
+        assert_eq!(link.successor(), successor);
+        assert!(link.migrated());
+        assert!(schema
+            .link_member(&seller(), &successor, true, &hash(b"again"))
+            .is_err());
+    }
+}
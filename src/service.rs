@@ -40,6 +40,16 @@ impl Service for OwnershipService {
         unimplemented!()
     }
 
+    /// State migrations are applied once, expired attestations are removed and contracts past
+    /// their status deadline are refused at the end of every block
+    fn before_commit(&self, fork: &mut Fork) {
+        let mut schema = Schema::new(fork);
+        schema.migrate();
+        let height = schema.next_height();
+        schema.prune_esia_attestations(height);
+        schema.expire_contracts();
+    }
+
     fn tx_from_raw(&self, raw: RawTransaction) -> Result<Box<dyn Transaction>, EncodingError> {
         OwnershipTransactions::tx_from_raw(raw).map(Into::into)
     }
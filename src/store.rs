@@ -0,0 +1,394 @@
+//! Content-addressed storage of attachment data.
+//!
+//! Transactions carry only the hash of the file, the bytes are kept in the node local
+//! directory and pushed to the nodes the document is shared with.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use blockp_core::crypto::{self, Hash, HashStream, PublicKey, SecretKey, Signature};
+use blockp_core::encoding::serialize::FromHex;
+
+use crate::error::{Error, Result};
+
+/// Directory of the store
+const STORE_DIR_VAR: &str = "FIPS_FILE_STORE";
+/// Private API addresses of other nodes: `<public key>=<url>` separated by commas
+const STORE_PEERS_VAR: &str = "FIPS_FILE_STORE_PEERS";
+const DEFAULT_STORE_DIR: &str = "files";
+const REPLICATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Size limit of a replica received from another node
+pub const REPLICA_SIZE_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Distinguishes temporary files of concurrent uploads
+static UPLOAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+pub struct StoredFile {
+    pub content: FileContent,
+    pub name: String,
+}
+
+pub enum FileContent {
+    File(File),
+    /// Decrypted data or data kept in the transaction, which is never written to the store
+    Data(Vec<u8>),
+}
+
+pub struct FileStore {
+    root: PathBuf,
+    peers: HashMap<PublicKey, String>,
+}
+
+impl FileStore {
+    pub fn from_env() -> Result<Self> {
+        let root = std::env::var(STORE_DIR_VAR).unwrap_or_else(|_| DEFAULT_STORE_DIR.to_owned());
+        let peers = match std::env::var(STORE_PEERS_VAR) {
+            Ok(peers) => parse_peers(&peers)?,
+            Err(_) => HashMap::new(),
+        };
+        Ok(FileStore {
+            root: PathBuf::from(root),
+            peers,
+        })
+    }
+
+    fn path(&self, hash: &Hash) -> PathBuf {
+        let name = hash.to_hex();
+        self.root.join(&name[..2]).join(name)
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        self.path(hash).is_file()
+    }
+
+    /// Saves the data and returns its hash
+    pub fn put(&self, data: &[u8]) -> Result<Hash> {
+        let hash = crypto::hash(data);
+        if !self.contains(&hash) {
+            self.write(&hash, data)?;
+        }
+        Ok(hash)
+    }
+
+    /// Saves the data received from another node
+    pub fn put_replica(&self, hash: &Hash, data: &[u8]) -> Result<()> {
+        if &crypto::hash(data) != hash {
+            Error::bad_file_data(hash).ok()?
+        }
+        if !self.contains(hash) {
+            self.write(hash, data)?;
+        }
+        Ok(())
+    }
+
+    /// Starts saving data which is received in chunks
+    pub fn writer(&self) -> Result<StoreWriter> {
+        fs::create_dir_all(&self.root)?;
+        let n = UPLOAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let tmp = self
+            .root
+            .join(format!(".upload-{}-{}.tmp", std::process::id(), n));
+        let file = File::create(&tmp)?;
+        Ok(StoreWriter {
+            root: self.root.clone(),
+            tmp,
+            file,
+            hasher: Some(HashStream::new()),
+            size: 0,
+        })
+    }
+
+    fn write(&self, hash: &Hash, data: &[u8]) -> Result<()> {
+        let path = self.path(hash);
+        let dir = path.parent().expect("file path always has a parent");
+        fs::create_dir_all(dir)?;
+        // Write to a temporary file first so a file under the hash name is always complete
+        let tmp = dir.join(format!(".{}.tmp", hash.to_hex()));
+        let mut file = File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, hash: &Hash) -> Result<()> {
+        if self.contains(hash) {
+            fs::remove_file(self.path(hash))?;
+        }
+        Ok(())
+    }
+
+    /// Opens the file, fetching it from other nodes if it's missing locally
+    pub fn open(&self, hash: &Hash) -> Result<File> {
+        if !self.contains(hash) {
+            self.fetch(hash)?;
+        }
+        File::open(self.path(hash)).map_err(Error::from)
+    }
+
+    /// Path of the file in the store, fetching it from other nodes if it's missing locally
+    pub fn local_path(&self, hash: &Hash) -> Result<PathBuf> {
+        if !self.contains(hash) {
+            self.fetch(hash)?;
+        }
+        Ok(self.path(hash))
+    }
+
+    /// Opens the file only if it's stored on this node
+    pub fn open_local(&self, hash: &Hash) -> Result<File> {
+        if !self.contains(hash) {
+            Error::no_file_data(hash).ok()?
+        }
+        File::open(self.path(hash)).map_err(Error::from)
+    }
+
+    pub fn get(&self, hash: &Hash) -> Result<Vec<u8>> {
+        if !self.contains(hash) {
+            self.fetch(hash)?;
+        }
+        let data = fs::read(self.path(hash))?;
+        if &crypto::hash(&data) != hash {
+            Error::bad_file_data(hash).ok()?
+        }
+        Ok(data)
+    }
+
+    /// Checks that the replica is pushed by one of the peers, which signs the hash of the data
+    pub fn verify_replica_sender(&self, hash: &Hash, node: &str, signature: &str) -> Result<()> {
+        let node = PublicKey::from_hex(node).map_err(|_| Error::unexpected_param_value("node"))?;
+        let signature = Signature::from_hex(signature)
+            .map_err(|_| Error::unexpected_param_value("signature"))?;
+        if !self.peers.contains_key(&node) || !crypto::verify(&signature, hash.as_ref(), &node) {
+            Error::no_permissions().ok()?
+        }
+        Ok(())
+    }
+
+    /// Pushes the file to the nodes it is shared with on behalf of the `node`
+    pub fn replicate(
+        &self,
+        hash: &Hash,
+        share: &[PublicKey],
+        node: &PublicKey,
+        secret_key: &SecretKey,
+    ) -> Result<()> {
+        let data = self.get(hash)?;
+        let signature = crypto::sign(hash.as_ref(), secret_key);
+        let client = reqwest::blocking::Client::builder()
+            .timeout(REPLICATION_TIMEOUT)
+            .build()?;
+        for key in share {
+            let url = match self.peers.get(key) {
+                Some(url) => format!(
+                    "{}/v1/files?hash={}&node={}&signature={}",
+                    url,
+                    hash.to_hex(),
+                    node.to_hex(),
+                    signature.to_hex()
+                ),
+                // own node or node without configured address, it will fetch the file on request
+                None => continue,
+            };
+            let result = client.put(&url).body(data.clone()).send();
+            match result {
+                Ok(resp) if resp.status().is_success() => {}
+                Ok(resp) => warn!(
+                    "Failed to replicate file {} to {}: {}",
+                    hash,
+                    url,
+                    resp.status()
+                ),
+                Err(e) => warn!("Failed to replicate file {} to {}: {}", hash, url, e),
+            }
+        }
+        Ok(())
+    }
+
+    fn fetch(&self, hash: &Hash) -> Result<()> {
+        let client = reqwest::blocking::Client::builder()
+            .timeout(REPLICATION_TIMEOUT)
+            .build()?;
+        for url in self.peers.values() {
+            let url = format!("{}/v1/files?hash={}", url, hash.to_hex());
+            let data = match client
+                .get(&url)
+                .send()
+                .and_then(|resp| resp.error_for_status())
+            {
+                Ok(resp) => resp.bytes()?,
+                Err(e) => {
+                    debug!("File {} wasn't fetched from {}: {}", hash, url, e);
+                    continue;
+                }
+            };
+            if self.put_replica(hash, &data).is_ok() {
+                return Ok(());
+            }
+        }
+        Error::no_file_data(hash).ok()
+    }
+}
+
+/// Data being saved to the store, the temporary file is removed if it isn't finished
+pub struct StoreWriter {
+    root: PathBuf,
+    tmp: PathBuf,
+    file: File,
+    hasher: Option<HashStream>,
+    size: usize,
+}
+
+impl StoreWriter {
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.file.write_all(data)?;
+        self.hasher = self.hasher.take().map(|hasher| hasher.update(data));
+        self.size += data.len();
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Saves the file under the hash of the written data,
+    /// tells if the store didn't have the data before
+    pub fn finish(mut self) -> Result<(Hash, bool)> {
+        self.file.sync_all()?;
+        let hash = self
+            .hasher
+            .take()
+            .expect("hasher is taken only by finish")
+            .hash();
+        let store = FileStore {
+            root: self.root.clone(),
+            peers: HashMap::new(),
+        };
+        let path = store.path(&hash);
+        let stored = !store.contains(&hash);
+        if stored {
+            fs::create_dir_all(path.parent().expect("file path always has a parent"))?;
+            fs::rename(&self.tmp, &path)?;
+        } else {
+            fs::remove_file(&self.tmp)?;
+        }
+        Ok((hash, stored))
+    }
+}
+
+impl Drop for StoreWriter {
+    fn drop(&mut self) {
+        if self.hasher.is_some() {
+            let _ = fs::remove_file(&self.tmp);
+        }
+    }
+}
+
+fn parse_peers(peers: &str) -> Result<HashMap<PublicKey, String>> {
+    peers
+        .split(',')
+        .filter(|peer| !peer.trim().is_empty())
+        .map(|peer| {
+            let mut parts = peer.trim().splitn(2, '=');
+            let key = parts.next().unwrap_or_default();
+            let url = parts
+                .next()
+                .ok_or_else(|| Error::unexpected_param_value(STORE_PEERS_VAR))?;
+            let key = PublicKey::from_hex(key)
+                .map_err(|_| Error::unexpected_param_value(STORE_PEERS_VAR))?;
+            Ok((key, url.trim_end_matches('/').to_owned()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn store(name: &str) -> FileStore {
+        let root = std::env::temp_dir().join(format!("fips-store-{}-{}", name, std::process::id()));
+        FileStore {
+            root,
+            peers: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn put_and_get() {
+        let store = store("put");
+        let hash = store.put(b"deed").unwrap();
+        assert_eq!(hash, crypto::hash(b"deed"));
+        assert_eq!(store.get(&hash).unwrap(), b"deed".to_vec());
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn replica_hash_mismatch() {
+        let store = store("replica");
+        let hash = crypto::hash(b"deed");
+        store.put_replica(&hash, b"application").unwrap_err();
+        assert!(!store.contains(&hash));
+    }
+
+    #[test]
+    fn writer_saves_under_hash() {
+        let store = store("writer");
+        let mut writer = store.writer().unwrap();
+        writer.write(b"de").unwrap();
+        writer.write(b"ed").unwrap();
+        assert_eq!(writer.size(), 4);
+        let (hash, stored) = writer.finish().unwrap();
+        assert_eq!(hash, crypto::hash(b"deed"));
+        assert!(stored);
+        assert_eq!(store.get(&hash).unwrap(), b"deed".to_vec());
+        let mut writer = store.writer().unwrap();
+        writer.write(b"deed").unwrap();
+        assert_eq!(writer.finish().unwrap(), (hash, false));
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn dropped_writer_removes_data() {
+        let store = store("dropped");
+        let mut writer = store.writer().unwrap();
+        writer.write(b"deed").unwrap();
+        drop(writer);
+        assert_eq!(fs::read_dir(&store.root).unwrap().count(), 0);
+        fs::remove_dir_all(&store.root).unwrap();
+    }
+
+    #[test]
+    fn missing_file() {
+        let store = store("missing");
+        store.get(&crypto::hash(b"deed")).unwrap_err();
+    }
+
+    #[test]
+    fn replica_from_peer() {
+        let (node, secret_key) = crypto::gen_keypair();
+        let (other, other_secret_key) = crypto::gen_keypair();
+        let mut store = store("peer");
+        store.peers.insert(node, "http://127.0.0.1:8200".to_owned());
+        let hash = crypto::hash(b"deed");
+        let signature = crypto::sign(hash.as_ref(), &secret_key).to_hex();
+        store
+            .verify_replica_sender(&hash, &node.to_hex(), &signature)
+            .unwrap();
+
+        // Signature of another file
+        let other_hash = crypto::hash(b"application");
+        store
+            .verify_replica_sender(&other_hash, &node.to_hex(), &signature)
+            .unwrap_err();
+
+        // Node which isn't a peer
+        let signature = crypto::sign(hash.as_ref(), &other_secret_key).to_hex();
+        store
+            .verify_replica_sender(&hash, &other.to_hex(), &signature)
+            .unwrap_err();
+    }
+}
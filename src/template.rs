@@ -0,0 +1,482 @@
+//! Canonical deed and application documents generated from the contract data.
+//!
+//! Documents are rendered only from the contract record, so every node produces
+//! byte-identical files and can compare them with the ones it generated before. Data which
+//! may change independently of the contract, like the object descriptions, isn't used.
+//! The wording comes from the `templates` directory compiled into the node, generated
+//! documents record its `version`.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{Cursor, Write};
+
+use blockp_core::crypto::{self, Hash};
+use blockp_core::storage::Snapshot;
+use serde::{Deserialize, Serialize};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::data::attachment::AttachmentType;
+use crate::data::conditions::{ContractType, ObjectOwnership};
+use crate::data::contract::{Contract, ContractId, CorrespondenceContacts};
+use crate::data::ownership::Distribution;
+use crate::data::time::{Specification, Term};
+use crate::error::{Error, Result};
+use crate::schema::Schema;
+
+pub const GENERATED_DESCRIPTION: &str = "generated";
+
+pub fn file_name(file_type: &AttachmentType) -> Result<&'static str> {
+    match file_type {
+        AttachmentType::Deed => Ok("deed.docx"),
+        AttachmentType::Application => Ok("application.docx"),
+        _ => Error::bad_file_type("file type must be one of: 'deed', 'application'").ok(),
+    }
+}
+
+/// Renders the document of `file_type` for the contract
+pub fn render<T>(
+    schema: &Schema<T>,
+    contract_id: &ContractId,
+    file_type: &AttachmentType,
+) -> Result<Vec<u8>>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let contract = schema
+        .contracts()
+        .get(contract_id)
+        .ok_or_else(|| Error::no_contract(contract_id))?;
+    let contacts = schema
+        .correspondence_contacts()
+        .get(contract_id)
+        .unwrap_or_else(|| CorrespondenceContacts::new(None, None));
+
+    let text = match file_type {
+        AttachmentType::Deed => deed(contract_id, &contract, &contacts)?,
+        AttachmentType::Application => application(contract_id, &contract, &contacts)?,
+        _ => Error::bad_file_type("file type must be one of: 'deed', 'application'").ok()?,
+    };
+    to_docx(&text)
+}
+
+/// Hash of the document of `file_type` generated for the contract
+pub fn document_hash<T>(
+    schema: &Schema<T>,
+    contract_id: &ContractId,
+    file_type: &AttachmentType,
+) -> Result<Hash>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    render(schema, contract_id, file_type).map(|data| crypto::hash(&data))
+}
+
+/// Version of the templates and the renderer, generated documents are compared only
+/// by the nodes rendering them the same way
+pub fn version() -> Hash {
+    let sources = [RENDERER, DEED, APPLICATION, OWNERSHIP, CONTACTS, LABELS];
+    crypto::hash(sources.join("\0").as_bytes())
+}
+
+/// Names of the generated documents which differ from the ones generated now, `None` if
+/// some of them can't be compared: uploaded by the parties or generated by other templates
+pub fn mismatched_documents<T>(
+    schema: &Schema<T>,
+    contract_id: &ContractId,
+    documents: &[(AttachmentType, Hash)],
+) -> Result<Option<Vec<&'static str>>>
+where
+    T: AsRef<dyn Snapshot>,
+{
+    let version = version();
+    let mut mismatched = Vec::new();
+    for (file_type, document_id) in documents {
+        if schema.generated_documents().get(document_id) != Some(version) {
+            return Ok(None);
+        }
+        let data_hash = match schema.contract_file_hashes().get(document_id) {
+            Some(data_hash) => data_hash,
+            None => return Ok(None),
+        };
+        if data_hash != document_hash(schema, contract_id, file_type)? {
+            mismatched.push(file_name(file_type)?);
+        }
+    }
+    Ok(Some(mismatched))
+}
+
+/// Changed along with the DOCX output, e.g. on an update of the zip crate
+const RENDERER: &str = "docx-1";
+const DEED: &str = include_str!("../templates/deed.txt");
+const APPLICATION: &str = include_str!("../templates/application.txt");
+const OWNERSHIP: &str = include_str!("../templates/ownership.txt");
+const CONTACTS: &str = include_str!("../templates/contacts.txt");
+const LABELS: &str = include_str!("../templates/labels.json");
+
+/// Wording of the values in the documents, keys are the serialized names of the values
+#[derive(Deserialize)]
+struct Labels {
+    yes: String,
+    no: String,
+    contract_types: HashMap<String, String>,
+    distribution: HashMap<String, String>,
+    terms: HashMap<String, String>,
+}
+
+impl Labels {
+    fn load() -> Result<Self> {
+        serde_json::from_str(LABELS).map_err(|_| Error::internal_bad_struct("Labels"))
+    }
+
+    fn flag(&self, value: bool) -> &str {
+        if value {
+            &self.yes
+        } else {
+            &self.no
+        }
+    }
+
+    fn label<'a, V>(labels: &'a HashMap<String, String>, value: &V) -> Result<&'a str>
+    where
+        V: Serialize,
+    {
+        serde_plain::to_string(value)
+            .ok()
+            .and_then(|key| labels.get(&key))
+            .map(String::as_str)
+            .ok_or_else(|| Error::internal_bad_struct("Labels"))
+    }
+
+    fn contract_type(&self, contract_type: u8) -> Result<&str> {
+        let contract_type = ContractType::try_from(contract_type)
+            .map_err(|_| Error::internal_bad_struct("ContractType"))?;
+        Self::label(&self.contract_types, &contract_type)
+    }
+
+    fn distribution(&self, can_distribute: u8) -> Result<&str> {
+        let distribution = Distribution::try_from(can_distribute)
+            .map_err(|_| Error::internal_bad_struct("Distribution"))?;
+        Self::label(&self.distribution, &distribution)
+    }
+
+    fn term(&self, term: &Term) -> Result<String> {
+        let specification = Specification::try_from(term.specification())
+            .map_err(|_| Error::internal_bad_struct("Term"))?;
+        let template = Self::label(&self.terms, &specification)?;
+        let values = match (specification, term.duration(), term.date()) {
+            (Specification::For, Some(duration), _) => vec![
+                ("months", duration.months().to_string()),
+                ("days", duration.days().to_string()),
+            ],
+            (Specification::To, _, Some(date)) | (Specification::Until, _, Some(date)) => {
+                vec![("date", date.format("%d.%m.%Y").to_string())]
+            }
+            (Specification::Forever, _, _) => vec![],
+            _ => Error::internal_bad_struct("Term").ok()?,
+        };
+        fill(template, &values)
+    }
+}
+
+fn deed(
+    contract_id: &ContractId,
+    contract: &Contract,
+    contacts: &CorrespondenceContacts,
+) -> Result<String> {
+    let labels = Labels::load()?;
+    let conditions = contract.conditions();
+    let objects = conditions
+        .objects()
+        .iter()
+        .enumerate()
+        .map(|(i, ownership)| ownership_text(&labels, &format!("1.{}", i + 1), ownership))
+        .collect::<Result<Vec<String>>>()?;
+    let mut payment = vec![conditions.payment_conditions().to_owned()];
+    if !conditions.payment_comment().is_empty() {
+        payment.push(conditions.payment_comment().to_owned());
+    }
+    let termination = conditions
+        .termination_conditions()
+        .iter()
+        .enumerate()
+        .map(|(i, condition)| format!("3.{}. {}", i + 1, condition.info()))
+        .collect::<Vec<String>>();
+    let extras = conditions
+        .contract_extras()
+        .iter()
+        .enumerate()
+        .map(|(i, extra)| format!("4.{}. {}", i + 1, extra.info()))
+        .collect::<Vec<String>>();
+
+    fill(
+        DEED,
+        &[
+            ("contract_type", labels.contract_type(conditions.contract_type())?.to_owned()),
+            ("contract_id", contract_id.to_string()),
+            ("seller", contract.seller().to_string()),
+            ("buyer", contract.buyer().to_string()),
+            ("price", contract.price().to_string()),
+            ("objects", objects.join("\n")),
+            ("payment", payment.join("\n")),
+            ("termination", termination.join("\n")),
+            ("extras", extras.join("\n")),
+            ("contacts", contacts_text(contacts)?),
+        ],
+    )
+}
+
+fn application(
+    contract_id: &ContractId,
+    contract: &Contract,
+    contacts: &CorrespondenceContacts,
+) -> Result<String> {
+    let labels = Labels::load()?;
+    let conditions = contract.conditions();
+    let objects = conditions
+        .objects()
+        .iter()
+        .enumerate()
+        .map(|(i, ownership)| format!("{}. {}", i + 1, ownership.object()))
+        .collect::<Vec<String>>();
+
+    fill(
+        APPLICATION,
+        &[
+            ("contract_type", labels.contract_type(conditions.contract_type())?.to_owned()),
+            ("contract_id", contract_id.to_string()),
+            ("seller", contract.seller().to_string()),
+            ("buyer", contract.buyer().to_string()),
+            ("objects", objects.join("\n")),
+            ("contacts", contacts_text(contacts)?),
+        ],
+    )
+}
+
+fn ownership_text(labels: &Labels, number: &str, ownership: &ObjectOwnership) -> Result<String> {
+    let location = ownership
+        .location()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let classifiers = ownership
+        .classifiers()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    fill(
+        OWNERSHIP.trim_end(),
+        &[
+            ("number", number.to_owned()),
+            ("object", ownership.object().to_string()),
+            ("term", labels.term(&ownership.contract_term())?),
+            ("exclusive", labels.flag(ownership.exclusive()).to_owned()),
+            ("distribution", labels.distribution(ownership.can_distribute())?.to_owned()),
+            ("location", location),
+            ("classifiers", classifiers),
+        ],
+    )
+}
+
+fn contacts_text(contacts: &CorrespondenceContacts) -> Result<String> {
+    fill(
+        CONTACTS.trim_end(),
+        &[
+            (
+                "contract_correspondence",
+                contacts.contract_correspondence().unwrap_or_default(),
+            ),
+            (
+                "objects_correspondence",
+                contacts.objects_correspondence().unwrap_or_default(),
+            ),
+        ],
+    )
+}
+
+/// Substitutes the `{name}` placeholders in a single pass, so the values are never taken
+/// for placeholders
+fn fill(template: &str, values: &[(&str, String)]) -> Result<String> {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| Error::internal_bad_struct("Template"))?;
+        let name = &rest[start + 1..end];
+        let value = values
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| Error::internal_bad_struct("Template"))?;
+        text.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+    Ok(text)
+}
+
+/// Writes the text as a DOCX document, one paragraph per line
+fn to_docx(text: &str) -> Result<Vec<u8>> {
+    const XML_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#;
+    let content_types = format!(
+        "{}{}{}{}{}",
+        XML_HEADER,
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+        r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+        r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+        r#"<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/></Types>"#,
+    );
+    let relationships = format!(
+        "{}{}{}",
+        XML_HEADER,
+        r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#,
+        r#"<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#,
+    );
+    let mut document = format!(
+        "{}{}",
+        XML_HEADER,
+        r#"<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>"#,
+    );
+    for line in text.lines() {
+        if line.is_empty() {
+            document.push_str("<w:p/>");
+            continue;
+        }
+        document.push_str(concat!(
+            r#"<w:p><w:r><w:rPr><w:rFonts w:ascii="Times New Roman" w:hAnsi="Times New Roman" w:cs="Times New Roman"/>"#,
+            r#"<w:sz w:val="24"/></w:rPr><w:t xml:space="preserve">"#,
+        ));
+        document.push_str(&xml_escape(line));
+        document.push_str("</w:t></w:r></w:p>");
+    }
+    document.push_str("</w:body></w:document>");
+
+    to_zip(&[
+        ("[Content_Types].xml", content_types.as_bytes()),
+        ("_rels/.rels", relationships.as_bytes()),
+        ("word/document.xml", document.as_bytes()),
+    ])
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters aren't allowed in XML
+            c if c.is_control() && c != '\t' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes an uncompressed ZIP archive. Entries have a fixed modification time,
+/// so the same files always produce the same archive.
+fn to_zip(files: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(zip::DateTime::default());
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in files {
+        zip.start_file(*name, options)?;
+        zip.write_all(data)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use zip::ZipArchive;
+
+    use crate::fixtures::{buyer, contract, fork};
+
+    use super::*;
+
+    #[test]
+    fn xml_escapes_text() {
+        assert_eq!(
+            xml_escape("<a & 'b'>\u{1}Я"),
+            "&lt;a &amp; &apos;b&apos;&gt;Я"
+        );
+    }
+
+    #[test]
+    fn fill_substitutes_values_once() {
+        let values = [("a", String::from("{b}")), ("b", String::from("2"))];
+        assert_eq!(fill("{a} + {b}", &values).unwrap(), "{b} + 2");
+        assert!(fill("{c}", &values).is_err());
+        assert!(fill("{a", &values).is_err());
+    }
+
+    #[test]
+    fn labels_cover_values() {
+        let labels = Labels::load().unwrap();
+        for contract_type in 0..=6 {
+            assert!(labels.contract_type(contract_type).is_ok());
+        }
+        assert_eq!(labels.distribution(Distribution::Unable as u8).unwrap(), "нет");
+        assert_eq!(
+            labels.distribution(Distribution::WithWrittenPermission as u8).unwrap(),
+            "с письменного согласия правообладателя"
+        );
+        assert!(labels.distribution(0).is_err());
+    }
+
+    #[test]
+    fn docx_is_stable_zip() {
+        let text = "ДОГОВОР\n\nЦена: 100";
+        let docx = to_docx(text).unwrap();
+        assert_eq!(docx, to_docx(text).unwrap());
+        let mut archive = ZipArchive::new(Cursor::new(docx)).unwrap();
+        assert_eq!(archive.len(), 3);
+        assert_eq!(archive.by_index(0).unwrap().name(), "[Content_Types].xml");
+        let mut document = String::new();
+        archive
+            .by_name("word/document.xml")
+            .unwrap()
+            .read_to_string(&mut document)
+            .unwrap();
+        assert!(document.contains("<w:t xml:space=\"preserve\">ДОГОВОР</w:t>"));
+        assert!(document.contains("<w:p/>"));
+    }
+
+    #[test]
+    fn compare_only_generated_documents() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        let contract_id = crypto::hash(b"contract");
+        schema.add_contract(&contract_id, contract(), buyer());
+        let deed = crypto::hash(b"deed");
+        let documents = [(AttachmentType::Deed, deed)];
+        let data_hash = document_hash(&schema, &contract_id, &AttachmentType::Deed).unwrap();
+        schema.set_contract_file_hash(&deed, &data_hash);
+
+        // Uploaded by a party or generated by other templates
+        assert_eq!(mismatched_documents(&schema, &contract_id, &documents).unwrap(), None);
+        schema.set_generated_document(&deed, &crypto::hash(b"other templates"));
+        assert_eq!(mismatched_documents(&schema, &contract_id, &documents).unwrap(), None);
+
+        schema.set_generated_document(&deed, &version());
+        assert_eq!(
+            mismatched_documents(&schema, &contract_id, &documents).unwrap(),
+            Some(vec![])
+        );
+        schema.set_contract_file_hash(&deed, &crypto::hash(b"outdated"));
+        assert_eq!(
+            mismatched_documents(&schema, &contract_id, &documents).unwrap(),
+            Some(vec!["deed.docx"])
+        );
+    }
+}
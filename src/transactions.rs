@@ -5,28 +5,35 @@ use chrono::{DateTime, Utc};
 use rand::{thread_rng, RngCore};
 
 use blockp_core::blockchain::{
-    Blockchain, ExecutionError, ExecutionResult, PreExecutionError, PreExecutionResult,
-    Transaction, TransactionSet,
+    Blockchain, ExecutionError, ExecutionResult, PreExecutionError, Transaction, TransactionSet,
 };
 use blockp_core::crypto::{get_cert_from_detached_sign, Certificate, Hash, PublicKey};
 use blockp_core::messages::RawMessage;
 use blockp_core::storage::{Fork, Snapshot};
 
-use crate::data::attachment::{Attachment, AttachmentType, DocumentId, Sign};
-use crate::data::conditions::{Check, CheckKey, Conditions};
-use crate::data::contract::{Action, BuyerSeller, Contract, ContractId, ContractStatus};
+use crate::data::attachment::{
+    Attachment, AttachmentType, DeprecatedAttachment, DocumentId, ScanVerdict, Sign,
+};
+use crate::data::attorney::{PowerOfAttorney, Powers};
+use crate::data::conditions::{Check, CheckKey, Conditions, ContractType};
+use crate::data::contract::{
+    Action, BuyerSeller, Contract, ContractId, ContractStatus, TerminationReason,
+    EXPIRABLE_STATUSES, MAX_DEADLINE_DAYS,
+};
 use crate::data::cost::Cost;
 use crate::data::lot::{Bid, Lot, LotId, LotState, LotStatus, SaleType};
-use crate::data::member::MemberIdentity;
-use crate::data::object::ObjectIdentity;
-use crate::data::ownership::{Ownership, OwnershipUnstructured, Rights};
+use crate::data::member::{Authority, EsiaAttestation, MemberIdentity};
+use crate::data::object::{Change, ObjectIdentity};
+use crate::data::ownership::{
+    ContractRights, Ownership, OwnershipUnstructured, Rights, RightsChange,
+};
 #[cfg(feature = "internal_api")]
 use crate::data::payment::PaymentStatus;
 use crate::data::payment::{Calculation, PaymentDetail, PaymentDetailsWrapper};
+use crate::data::registry::{KeyType, MemberProfile, ParticipantKey};
 use crate::data::strings::verify_node_name;
 use crate::error::{self, Error};
 use crate::schema::Schema;
-use crate::EsiaAuth;
 
 impl From<Error> for ExecutionError {
     fn from(err: Error) -> Self {
@@ -142,8 +149,96 @@ pub fn add_participant(
     AddParticipant::new(0, TxType::AddParticipant as u8, member, node_name, cert).into()
 }
 
+#[cfg(feature = "internal_api")]
+pub fn register_member(
+    member: MemberIdentity,
+    profile: MemberProfile,
+    keys: Vec<ParticipantKey>,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    RegisterMember::new(0, TxType::RegisterMember as u8, member, profile, keys, cert).into()
+}
+
+#[cfg(feature = "internal_api")]
+pub fn update_member_profile(
+    member: MemberIdentity,
+    profile: MemberProfile,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    UpdateMemberProfile::new(
+        salt(),
+        TxType::UpdateMemberProfile as u8,
+        member,
+        profile,
+        cert,
+    )
+    .into()
+}
+
+#[cfg(feature = "internal_api")]
+pub fn add_participant_key(
+    member: MemberIdentity,
+    key: ParticipantKey,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    AddParticipantKey::new(salt(), TxType::AddParticipantKey as u8, member, key, cert).into()
+}
+
+#[cfg(feature = "internal_api")]
+pub fn remove_participant_key(
+    member: MemberIdentity,
+    key: &PublicKey,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    RemoveParticipantKey::new(
+        salt(),
+        TxType::RemoveParticipantKey as u8,
+        member,
+        key,
+        cert,
+    )
+    .into()
+}
+
+#[cfg(feature = "internal_api")]
+pub fn rotate_participant_key(
+    member: MemberIdentity,
+    old_key: &PublicKey,
+    new_key: &PublicKey,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    RotateParticipantKey::new(
+        salt(),
+        TxType::RotateParticipantKey as u8,
+        member,
+        old_key,
+        new_key,
+        cert,
+    )
+    .into()
+}
+
+#[cfg(feature = "internal_api")]
+pub fn link_member(
+    member: MemberIdentity,
+    successor: MemberIdentity,
+    migrate: bool,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    LinkMember::new(
+        salt(),
+        TxType::LinkMember as u8,
+        member,
+        successor,
+        migrate,
+        cert,
+    )
+    .into()
+}
+
 pub fn open_lot(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     lot: Lot,
     conditions: Conditions,
     cert: &Certificate,
@@ -152,6 +247,7 @@ pub fn open_lot(
         salt(),
         TxType::OpenLot as u8,
         requestor,
+        attestation,
         lot,
         conditions,
         cert,
@@ -161,10 +257,19 @@ pub fn open_lot(
 
 pub fn close_lot(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     lot_id: &LotId,
     cert: &Certificate,
 ) -> Box<dyn Transaction> {
-    CloseLot::new(salt(), TxType::CloseLot as u8, requestor, lot_id, cert).into()
+    CloseLot::new(
+        salt(),
+        TxType::CloseLot as u8,
+        requestor,
+        attestation,
+        lot_id,
+        cert,
+    )
+    .into()
 }
 
 #[cfg(feature = "internal_api")]
@@ -195,6 +300,7 @@ pub fn execute_lot(lot_tx_hash: &LotId, cert: &Certificate) -> Box<dyn Transacti
 
 pub fn extend_lot_period(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     lot_tx_hash: &LotId,
     new_expiration_date: DateTime<Utc>,
     cert: &Certificate,
@@ -203,6 +309,7 @@ pub fn extend_lot_period(
         0,
         TxType::ExtendLotPeriod as u8,
         requestor,
+        attestation,
         lot_tx_hash,
         new_expiration_date,
         cert,
@@ -212,6 +319,7 @@ pub fn extend_lot_period(
 
 pub fn acquire_lot(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     lot_tx_hash: &LotId,
     share: Vec<PublicKey>,
     cert: &Certificate,
@@ -220,6 +328,7 @@ pub fn acquire_lot(
         salt(),
         TxType::AcquireLot as u8,
         requestor,
+        attestation,
         lot_tx_hash,
         share,
         cert,
@@ -229,6 +338,7 @@ pub fn acquire_lot(
 
 pub fn purchase_offer(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     buyer: MemberIdentity,
     rightholder: MemberIdentity,
     price: Cost,
@@ -240,6 +350,7 @@ pub fn purchase_offer(
         salt(),
         TxType::PurchaseOffer as u8,
         requestor,
+        attestation,
         buyer,
         rightholder,
         price.into(),
@@ -268,6 +379,7 @@ pub fn draft_contract(
 
 pub fn refuse_contract(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_tx_hash: &ContractId,
     reason: &str,
     share: Vec<PublicKey>,
@@ -277,6 +389,7 @@ pub fn refuse_contract(
         0,
         TxType::RefuseContract as u8,
         requestor,
+        attestation,
         contract_tx_hash,
         reason,
         share,
@@ -287,6 +400,7 @@ pub fn refuse_contract(
 
 pub fn confirm_contract(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_tx_hash: &ContractId,
     deed_tx_hash: &DocumentId,
     application_tx_hash: &DocumentId,
@@ -297,6 +411,7 @@ pub fn confirm_contract(
         salt(),
         TxType::ConfirmContract as u8,
         requestor,
+        attestation,
         contract_tx_hash,
         deed_tx_hash,
         application_tx_hash,
@@ -309,6 +424,7 @@ pub fn confirm_contract(
 pub fn update_contract(
     contract_tx_hash: &ContractId,
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     price: Cost,
     conditions: Conditions,
     contract_correspondence: Option<String>,
@@ -321,6 +437,7 @@ pub fn update_contract(
         TxType::UpdateContract as u8,
         contract_tx_hash,
         requestor,
+        attestation,
         price.into(),
         conditions,
         contract_correspondence,
@@ -333,8 +450,11 @@ pub fn update_contract(
 
 pub fn attach_contract_other_file(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_tx_hash: &ContractId,
     file: Attachment,
+    scan_verdict: ScanVerdict,
+    encrypted: bool,
     share: Vec<PublicKey>,
     cert: &Certificate,
 ) -> Box<dyn Transaction> {
@@ -342,8 +462,38 @@ pub fn attach_contract_other_file(
         0,
         TxType::AttachContractOtherFile as u8,
         requestor,
+        attestation,
+        contract_tx_hash,
+        file,
+        scan_verdict as u8,
+        encrypted,
+        share,
+        cert,
+    )
+    .into()
+}
+
+pub fn replace_contract_file(
+    requestor: MemberIdentity,
+    attestation: EsiaAttestation,
+    contract_tx_hash: &ContractId,
+    doc_tx_hash: &DocumentId,
+    file: Attachment,
+    scan_verdict: ScanVerdict,
+    encrypted: bool,
+    share: Vec<PublicKey>,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    ReplaceContractFile::new(
+        0,
+        TxType::ReplaceContractFile as u8,
+        requestor,
+        attestation,
         contract_tx_hash,
+        doc_tx_hash,
         file,
+        scan_verdict as u8,
+        encrypted,
         share,
         cert,
     )
@@ -354,6 +504,9 @@ pub fn attach_contract_other_file(
 pub fn attach_contract_main_file(
     contract_tx_hash: &ContractId,
     file: Attachment,
+    scan_verdict: ScanVerdict,
+    encrypted: bool,
+    template: &Hash,
     share: Vec<PublicKey>,
     cert: &Certificate,
 ) -> Box<dyn Transaction> {
@@ -362,6 +515,9 @@ pub fn attach_contract_main_file(
         TxType::AttachContractMainFile as u8,
         contract_tx_hash,
         file,
+        scan_verdict as u8,
+        encrypted,
+        template,
         share,
         cert,
     )
@@ -370,6 +526,7 @@ pub fn attach_contract_main_file(
 
 pub fn delete_contract_files(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_tx_hash: &ContractId,
     doc_tx_hashes: &[DocumentId],
     share: Vec<PublicKey>,
@@ -379,6 +536,7 @@ pub fn delete_contract_files(
         salt(),
         TxType::DeleteContractFiles as u8,
         requestor,
+        attestation,
         contract_tx_hash,
         doc_tx_hashes,
         share,
@@ -425,11 +583,36 @@ pub fn reject_contract(
     .into()
 }
 
+#[cfg(feature = "internal_api")]
+pub fn terminate_contract(
+    contract_tx_hash: &ContractId,
+    reason: TerminationReason,
+    comment: &str,
+    attachment: Option<Attachment>,
+    share: Vec<PublicKey>,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    TerminateContract::new(
+        0,
+        TxType::TerminateContract as u8,
+        contract_tx_hash,
+        reason as u8,
+        comment,
+        attachment,
+        share,
+        cert,
+    )
+    .into()
+}
+
 pub fn sign_contract(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_tx_hash: &ContractId,
     deed_sign: Sign,
     application_sign: Sign,
+    deed_hash: &Hash,
+    application_hash: &Hash,
     share: Vec<PublicKey>,
     cert: &Certificate,
 ) -> Box<dyn Transaction> {
@@ -437,9 +620,12 @@ pub fn sign_contract(
         salt(),
         TxType::SignContract as u8,
         requestor,
+        attestation,
         contract_tx_hash,
         deed_sign,
         application_sign,
+        deed_hash,
+        application_hash,
         share,
         cert,
     )
@@ -448,11 +634,21 @@ pub fn sign_contract(
 
 pub fn add_bid(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     lot_id: &LotId,
     bid: Bid,
     cert: &Certificate,
 ) -> Box<dyn Transaction> {
-    AddBid::new(0, TxType::AddBid as u8, requestor, lot_id, bid, cert).into()
+    AddBid::new(
+        0,
+        TxType::AddBid as u8,
+        requestor,
+        attestation,
+        lot_id,
+        bid,
+        cert,
+    )
+    .into()
 }
 
 #[cfg(feature = "internal_api")]
@@ -554,6 +750,7 @@ pub fn contract_reference_number(
 
 pub fn tax_request(
     requestor: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_tx_hash: &ContractId,
     share: Vec<PublicKey>,
     cert: &Certificate,
@@ -562,6 +759,7 @@ pub fn tax_request(
         salt(),
         TxType::TaxRequest as u8,
         requestor,
+        attestation,
         contract_tx_hash,
         share,
         cert,
@@ -647,6 +845,7 @@ pub fn tax_status(
 
 pub fn contract_confirm_create(
     requestor_id: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_id: &ContractId,
     share: Vec<PublicKey>,
     cert: &Certificate,
@@ -655,6 +854,7 @@ pub fn contract_confirm_create(
         salt(),
         TxType::ContractConfirmCreate as u8,
         requestor_id,
+        attestation,
         contract_id,
         share,
         cert,
@@ -665,6 +865,7 @@ pub fn contract_confirm_create(
 #[cfg(feature = "internal_api")]
 pub fn contract_unconfirm_create(
     member_id: MemberIdentity,
+    attestation: EsiaAttestation,
     contract_id: &ContractId,
     share: Vec<PublicKey>,
     cert: &Certificate,
@@ -673,6 +874,7 @@ pub fn contract_unconfirm_create(
         salt(),
         TxType::ContractUnconfirmCreate as u8,
         member_id,
+        attestation,
         contract_id,
         share,
         cert,
@@ -707,13 +909,69 @@ pub fn contract_new(
     ContractNew::new(salt(), TxType::ContractNew as u8, contract_id, share, cert).into()
 }
 
+#[cfg(feature = "internal_api")]
+pub fn set_contract_deadline(status: &str, days: u64, cert: &Certificate) -> Box<dyn Transaction> {
+    SetContractDeadline::new(
+        salt(),
+        TxType::SetContractDeadline as u8,
+        status,
+        days,
+        cert,
+    )
+    .into()
+}
+
 pub fn member_token(
     member: MemberIdentity,
-    token: &str,
-    oid: &str,
+    fingerprint: &Hash,
+    expires_at: DateTime<Utc>,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    MemberToken::new(
+        0,
+        TxType::MemberToken as u8,
+        member,
+        fingerprint,
+        expires_at,
+        cert,
+    )
+    .into()
+}
+
+pub fn add_power_of_attorney(
+    requestor: MemberIdentity,
+    attestation: EsiaAttestation,
+    power_of_attorney: PowerOfAttorney,
+    scan_verdict: ScanVerdict,
+    cert: &Certificate,
+) -> Box<dyn Transaction> {
+    AddPowerOfAttorney::new(
+        salt(),
+        TxType::AddPowerOfAttorney as u8,
+        requestor,
+        attestation,
+        power_of_attorney,
+        scan_verdict as u8,
+        cert,
+    )
+    .into()
+}
+
+pub fn revoke_power_of_attorney(
+    requestor: MemberIdentity,
+    attestation: EsiaAttestation,
+    attorney_tx_hash: &Hash,
     cert: &Certificate,
 ) -> Box<dyn Transaction> {
-    MemberToken::new(0, TxType::MemberToken as u8, member, token, oid, cert).into()
+    RevokePowerOfAttorney::new(
+        0,
+        TxType::RevokePowerOfAttorney as u8,
+        requestor,
+        attestation,
+        attorney_tx_hash,
+        cert,
+    )
+    .into()
 }
 
 fn convert_tx<T: AsRef<dyn Snapshot>>(
@@ -723,30 +981,95 @@ fn convert_tx<T: AsRef<dyn Snapshot>>(
     OwnershipTransactions::tx_from_raw(raw).map_err(|_| Error::unexpected_tx_type(tx_hash))
 }
 
-pub fn get_transaction<T: AsRef<dyn Snapshot>>(
+fn get_private_raw<T: AsRef<dyn Snapshot>>(
     schema: &Schema<T>,
-    tx_hash: &Hash,
-) -> error::Result<OwnershipTransactions> {
-    let raw = schema
+    pub_tx_hash: &Hash,
+) -> error::Result<RawMessage> {
+    let pub_tx_raw = schema
         .core_transactions()
-        .get(tx_hash)
-        .ok_or(Error::no_transaction(tx_hash))?;
-    convert_tx::<T>(tx_hash, raw)
+        .get(pub_tx_hash)
+        .ok_or(Error::no_transaction(pub_tx_hash))?;
+
+    Blockchain::from_private_tx(&pub_tx_raw, &schema.core_private_data())
+        .ok_or(Error::no_private_data(pub_tx_hash))
 }
 
 pub fn get_private_tx<T: AsRef<dyn Snapshot>>(
     schema: &Schema<T>,
     pub_tx_hash: &Hash,
 ) -> error::Result<OwnershipTransactions> {
-    let pub_tx_raw = schema
-        .core_transactions()
-        .get(pub_tx_hash)
-        .ok_or(Error::no_transaction(pub_tx_hash))?;
+    let priv_tx_raw = get_private_raw(schema, pub_tx_hash)?;
+    convert_tx::<T>(pub_tx_hash, priv_tx_raw)
+}
+
+/// Checks that the signed data is the current version of the document and the signature
+/// matches its hash, so the signature is checked without the document data
+fn verify_signed_document<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    document_id: &DocumentId,
+    signed_hash: &Hash,
+    sign: &Sign,
+) -> Result<(), Error> {
+    match schema.contract_file_hashes().get(document_id) {
+        Some(data_hash) if &data_hash == signed_hash => sign.verify_hash(&data_hash),
+        _ => Error::signed_document_mismatch(document_id).ok(),
+    }
+}
 
-    let priv_tx_raw = Blockchain::from_private_tx(&pub_tx_raw, &schema.core_private_data())
-        .ok_or(Error::no_private_data(pub_tx_hash))?;
+/// Checks the ESIA attestation of the member the transaction is sent on behalf of,
+/// `powers` are the operations a representative may perform by a power of attorney
+fn verify_attestation(
+    schema: &mut Schema<&mut Fork>,
+    member: &MemberIdentity,
+    attestation: &EsiaAttestation,
+    powers: Powers,
+) -> Result<(), Error> {
+    if attestation.member() != *member {
+        Error::bad_esia_attestation(member, "issued for another member").ok()?
+    }
+    if !attestation.verify_signature() {
+        Error::bad_esia_attestation(member, "bad signature").ok()?
+    }
+    if !schema.is_trusted_node(&member.id(), attestation.node()) {
+        Error::bad_esia_attestation(member, "issued by unknown node").ok()?
+    }
+    if schema.next_height() > attestation.valid_until() {
+        Error::esia_attestation_expired(member, attestation.valid_until()).ok()?
+    }
+    let token = schema
+        .member_token(member)
+        .ok_or_else(Error::no_member_token)?;
+    if token.fingerprint() != attestation.token_fingerprint() {
+        Error::esia_token_mismatch(member).ok()?
+    }
+    verify_powers(schema, attestation, powers)?;
+    if !schema.accept_esia_attestation(attestation) {
+        Error::bad_esia_attestation(member, "already used").ok()?
+    }
+    Ok(())
+}
 
-    convert_tx::<T>(pub_tx_hash, priv_tx_raw)
+/// Checks that the attested person may perform the operation on behalf of the member,
+/// a representative must hold an unrevoked power of attorney for `powers`. The validity period
+/// is checked at the network time if the time service runs, otherwise the attesting node has
+/// checked it by its clock and the attestation is short-lived. Operations without
+/// `powers` can't be performed by a power of attorney
+fn verify_powers<T: AsRef<dyn Snapshot>>(
+    schema: &Schema<T>,
+    attestation: &EsiaAttestation,
+    powers: Powers,
+) -> Result<(), Error> {
+    let authority = Authority::try_from(attestation.authority())
+        .map_err(|_| Error::internal_bad_struct("authority"))?;
+    if authority != Authority::PowerOfAttorney {
+        return Ok(());
+    }
+    let member = attestation.member();
+    let time = schema.time().get();
+    if !schema.representative_may(&member.id(), attestation.person(), powers, time) {
+        Error::no_power_of_attorney(&member, attestation.person()).ok()?
+    }
+    Ok(())
 }
 
 #[allow(unreachable_code)]
@@ -858,6 +1181,18 @@ enum TxType {
     ContractUnconfirmCreate = 45,
     ContractNew = 46,
     AttachContractMainFile = 47,
+    TerminateContract = 48,
+    // ExpireContracts = 49,
+    SetContractDeadline = 50,
+    ReplaceContractFile = 51,
+    AddPowerOfAttorney = 52,
+    RevokePowerOfAttorney = 53,
+    RegisterMember = 54,
+    UpdateMemberProfile = 55,
+    AddParticipantKey = 56,
+    RemoveParticipantKey = 57,
+    RotateParticipantKey = 58,
+    LinkMember = 59,
 }
 
 transactions! {
@@ -908,14 +1243,14 @@ transactions! {
             node_name: &str,
         }
 
-        struct OpenLot {
+        struct DeprecatedOpenLot {
             _type: u8,
             requestor: MemberIdentity,
             lot: Lot,
             conditions: Conditions,
         }
 
-        struct CloseLot {
+        struct DeprecatedCloseLot {
             _type: u8,
             requestor: MemberIdentity,
             lot_tx_hash: &LotId,
@@ -932,14 +1267,14 @@ transactions! {
             lot_tx_hash: &LotId,
         }
 
-        struct ExtendLotPeriod {
+        struct DeprecatedExtendLotPeriod {
             _type: u8,
             requestor: MemberIdentity,
             lot_tx_hash: &LotId,
             new_expiration_date: DateTime<Utc>,
         }
 
-        struct AddBid {
+        struct DeprecatedAddBid {
             _type: u8,
             requestor: MemberIdentity,
             lot_tx_hash: &LotId,
@@ -952,14 +1287,14 @@ transactions! {
             bids: Vec<u64>,
         }
 
-        struct AcquireLot {
+        struct DeprecatedAcquireLot {
             _type: u8,
             requestor: MemberIdentity,
             lot_tx_hash: &LotId,
             share: Vec<PublicKey>,
         }
 
-        struct PurchaseOffer {
+        struct DeprecatedPurchaseOffer {
             _type: u8,
             requestor: MemberIdentity,
             buyer: MemberIdentity,
@@ -975,7 +1310,7 @@ transactions! {
             share: Vec<PublicKey>,
         }
 
-        struct RefuseContract {
+        struct DeprecatedRefuseContract {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
@@ -983,7 +1318,7 @@ transactions! {
             share: Vec<PublicKey>,
         }
 
-        struct ConfirmContract {
+        struct DeprecatedConfirmContract {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
@@ -992,22 +1327,22 @@ transactions! {
             share: Vec<PublicKey>,
         }
 
-        struct AttachContractOtherFile {
+        struct DeprecatedAttachContractOtherFile {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
-            file: Attachment,
+            file: DeprecatedAttachment,
             share: Vec<PublicKey>,
         }
 
-        struct AttachContractMainFile {
+        struct DeprecatedAttachContractMainFile {
             _type: u8,
             contract_tx_hash: &ContractId,
-            file: Attachment,
+            file: DeprecatedAttachment,
             share: Vec<PublicKey>,
         }
 
-        struct DeleteContractFiles {
+        struct DeprecatedDeleteContractFiles {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
@@ -1015,22 +1350,22 @@ transactions! {
             share: Vec<PublicKey>,
         }
 
-        struct ApproveContract {
+        struct DeprecatedApproveContract {
             _type: u8,
             contract_tx_hash: &ContractId,
-            attachment: Option<Attachment>,
+            attachment: Option<DeprecatedAttachment>,
             share: Vec<PublicKey>,
         }
 
-        struct RejectContract {
+        struct DeprecatedRejectContract {
             _type: u8,
             contract_tx_hash: &ContractId,
             reason: &str,
-            attachment: Option<Attachment>,
+            attachment: Option<DeprecatedAttachment>,
             share: Vec<PublicKey>,
         }
 
-        struct UpdateContract {
+        struct DeprecatedUpdateContract {
             _type: u8,
             contract_tx_hash: &ContractId,
             requestor: MemberIdentity,
@@ -1053,7 +1388,7 @@ transactions! {
             share: Vec<PublicKey>,
         }
 
-        struct SignContract {
+        struct DeprecatedSignContract {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
@@ -1084,7 +1419,7 @@ transactions! {
             admit: bool
         }
 
-        struct TaxRequest {
+        struct DeprecatedTaxRequest {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
@@ -1136,21 +1471,21 @@ transactions! {
             share: Vec<PublicKey>,
         }
 
-        struct MemberToken {
+        struct DeprecatedMemberToken {
             _type: u8,
             member: MemberIdentity,
             token: &str,
             oid: &str,
         }
 
-        struct ContractConfirmCreate {
+        struct DeprecatedContractConfirmCreate {
             _type: u8,
             requestor: MemberIdentity,
             contract_tx_hash: &ContractId,
             share: Vec<PublicKey>,
         }
 
-        struct ContractUnconfirmCreate {
+        struct DeprecatedContractUnconfirmCreate {
             _type: u8,
             member: MemberIdentity,
             contract_tx_hash: &ContractId,
@@ -1162,77 +1497,646 @@ transactions! {
             contract_tx_hash: &ContractId,
             share: Vec<PublicKey>,
         }
-    }
-}
 
-// impl Transaction for AddAttachmentSign {
-//     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
-//         self.requestor().is_valid() && self.sign().verify().is_ok()
-//     }
-//
-//     fn participants(&self) -> Vec<PublicKey> {
-//         self.share()
-//     }
-//
-//     fn execute(&self, fork: &mut Fork, _hash: &Hash, _: &PublicKey) -> ExecutionResult {
-//         let mut schema = Schema::new(fork);
-//         let doc_tx_hash = self.doc_tx_hash();
-//         let uid = &self.requestor().id();
-//
-//         if !schema.attachments(uid).contains(doc_tx_hash) {
-//             Error::no_attachment(doc_tx_hash).ok()?
-//         }
-//
-//         let attachment: Attachment = {
-//             let doc_tx = get_private_tx(&schema, doc_tx_hash)?;
-//             match doc_tx {
-//                 // OwnershipTransactions::AttachFile(tx) if tx.requestor().id() == *uid => {
-//                 //     Ok(tx.file())
-//                 // }
-//                 OwnershipTransactions::AttachContractFile(tx) => Ok(tx.file()),
-//                 _ => Error::unexpected_tx_type(doc_tx_hash).ok(),
-//             }
-//         }?;
-//         let doc_data = attachment.data();
-//         let sign = self.sign();
-//         member_matches_sign(&self.requestor(), &sign)?;
-//         sign.verify_data(doc_data)?;
-//
-//         Ok(schema.add_attachment_sign(doc_tx_hash, uid, sign))
-//     }
-// }
+        struct SetContractDeadline {
+            _type: u8,
+            status: &str,
+            days: u64,
+        }
 
-// impl Transaction for AttachFile {
-//     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
-//         self.requestor().is_valid()
-//             && self.file().verify().is_ok()
-//             && !contains_diplicates(self.members())
-//             && !contains_diplicates(self.share())
-//     }
-//
-//     fn participants(&self) -> Vec<PublicKey> {
-//         self.share()
-//     }
-//
-//     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
-//         let mut schema = Schema::new(fork);
-//         let data_hash = crypto::hash(self.file().data());
-//         schema.attach_file(&self.requestor().id(), tx_hash, data_hash);
-//
-//         for p in self.members().iter() {
-//             schema.attach_file(&p.id(), tx_hash, data_hash);
-//         }
-//         Ok(())
-//     }
-// }
+        struct ReplaceContractFile {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            doc_tx_hash: &DocumentId,
+            file: Attachment,
+            scan_verdict: u8,
+            encrypted: bool,
+            share: Vec<PublicKey>,
+        }
 
-// impl Transaction for DeleteFiles {
-//     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
-//         self.requestor().is_valid()
-//     }
-//
-//     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
-//         let mut schema = Schema::new(fork);
+        struct AddPowerOfAttorney {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            power_of_attorney: PowerOfAttorney,
+            scan_verdict: u8,
+        }
+
+        struct RevokePowerOfAttorney {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            attorney_tx_hash: &Hash,
+        }
+
+        struct RegisterMember {
+            _type: u8,
+            member: MemberIdentity,
+            profile: MemberProfile,
+            keys: Vec<ParticipantKey>,
+        }
+
+        struct UpdateMemberProfile {
+            _type: u8,
+            member: MemberIdentity,
+            profile: MemberProfile,
+        }
+
+        struct AddParticipantKey {
+            _type: u8,
+            member: MemberIdentity,
+            key: ParticipantKey,
+        }
+
+        struct RemoveParticipantKey {
+            _type: u8,
+            member: MemberIdentity,
+            key: &PublicKey,
+        }
+
+        struct RotateParticipantKey {
+            _type: u8,
+            member: MemberIdentity,
+            old_key: &PublicKey,
+            new_key: &PublicKey,
+        }
+
+        struct LinkMember {
+            _type: u8,
+            member: MemberIdentity,
+            successor: MemberIdentity,
+            migrate: bool,
+        }
+
+        struct TerminateContract {
+            _type: u8,
+            contract_tx_hash: &ContractId,
+            reason: u8,
+            comment: &str,
+            attachment: Option<Attachment>,
+            share: Vec<PublicKey>,
+        }
+
+        struct AttachContractOtherFile {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            file: Attachment,
+            scan_verdict: u8,
+            encrypted: bool,
+            share: Vec<PublicKey>,
+        }
+
+        struct AttachContractMainFile {
+            _type: u8,
+            contract_tx_hash: &ContractId,
+            file: Attachment,
+            scan_verdict: u8,
+            encrypted: bool,
+            // Version of the templates the node generated the file with, zero for uploaded files
+            template: &Hash,
+            share: Vec<PublicKey>,
+        }
+
+        struct ApproveContract {
+            _type: u8,
+            contract_tx_hash: &ContractId,
+            attachment: Option<Attachment>,
+            share: Vec<PublicKey>,
+        }
+
+        struct RejectContract {
+            _type: u8,
+            contract_tx_hash: &ContractId,
+            reason: &str,
+            attachment: Option<Attachment>,
+            share: Vec<PublicKey>,
+        }
+
+        struct SignContract {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            deed_sign: Sign,
+            application_sign: Sign,
+            // Hashes of the signed deed and application data, the signatures are
+            // checked against the data by the node which sends the transaction
+            deed_hash: &Hash,
+            application_hash: &Hash,
+            share: Vec<PublicKey>,
+        }
+
+        struct MemberToken {
+            _type: u8,
+            member: MemberIdentity,
+            fingerprint: &Hash,
+            expires_at: DateTime<Utc>,
+        }
+
+        struct OpenLot {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            lot: Lot,
+            conditions: Conditions,
+        }
+
+        struct CloseLot {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            lot_tx_hash: &LotId,
+        }
+
+        struct ExtendLotPeriod {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            lot_tx_hash: &LotId,
+            new_expiration_date: DateTime<Utc>,
+        }
+
+        struct AddBid {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            lot_tx_hash: &LotId,
+            bid: Bid,
+        }
+
+        struct AcquireLot {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            lot_tx_hash: &LotId,
+            share: Vec<PublicKey>,
+        }
+
+        struct PurchaseOffer {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            buyer: MemberIdentity,
+            rightholder: MemberIdentity,
+            price: u64,
+            conditions: Conditions,
+            share: Vec<PublicKey>,
+        }
+
+        struct RefuseContract {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            reason: &str,
+            share: Vec<PublicKey>,
+        }
+
+        struct ConfirmContract {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            deed_tx_hash: &DocumentId,
+            application_tx_hash: &DocumentId,
+            share: Vec<PublicKey>,
+        }
+
+        struct DeleteContractFiles {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            doc_tx_hashes: &[DocumentId],
+            share: Vec<PublicKey>,
+        }
+
+        struct UpdateContract {
+            _type: u8,
+            contract_tx_hash: &ContractId,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            price: u64,
+            conditions: Conditions,
+            contract_correspondence: Option<String>,
+            objects_correspondence: Option<String>,
+            share: Vec<PublicKey>,
+        }
+
+        struct TaxRequest {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            share: Vec<PublicKey>,
+        }
+
+        struct ContractConfirmCreate {
+            _type: u8,
+            requestor: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            share: Vec<PublicKey>,
+        }
+
+        struct ContractUnconfirmCreate {
+            _type: u8,
+            member: MemberIdentity,
+            attestation: EsiaAttestation,
+            contract_tx_hash: &ContractId,
+            share: Vec<PublicKey>,
+        }
+    }
+}
+
+impl OwnershipTransactions {
+    /// Contract and the attachment of the document transaction, the attachments of
+    /// the original layouts refer to the data kept in the transaction by its hash
+    pub fn attachment(&self) -> Option<(ContractId, Attachment)> {
+        use OwnershipTransactions::*;
+        match self {
+            AttachContractMainFile(tx) => Some((*tx.contract_tx_hash(), tx.file())),
+            AttachContractOtherFile(tx) => Some((*tx.contract_tx_hash(), tx.file())),
+            ReplaceContractFile(tx) => Some((*tx.contract_tx_hash(), tx.file())),
+            ApproveContract(tx) => tx.attachment().map(|file| (*tx.contract_tx_hash(), file)),
+            RejectContract(tx) => tx.attachment().map(|file| (*tx.contract_tx_hash(), file)),
+            TerminateContract(tx) => tx.attachment().map(|file| (*tx.contract_tx_hash(), file)),
+            _ => self
+                .deprecated_attachment()
+                .map(|(contract_id, file)| (contract_id, file.upgrade())),
+        }
+    }
+
+    /// Attachment of the document transaction sent before the file data was moved
+    /// to the `FileStore`, the data is kept in the attachment
+    pub fn deprecated_attachment(&self) -> Option<(ContractId, DeprecatedAttachment)> {
+        use OwnershipTransactions::*;
+        match self {
+            DeprecatedAttachContractMainFile(tx) => Some((*tx.contract_tx_hash(), tx.file())),
+            DeprecatedAttachContractOtherFile(tx) => Some((*tx.contract_tx_hash(), tx.file())),
+            DeprecatedApproveContract(tx) => tx
+                .attachment()
+                .map(|file| (*tx.contract_tx_hash(), file)),
+            DeprecatedRejectContract(tx) => tx
+                .attachment()
+                .map(|file| (*tx.contract_tx_hash(), file)),
+            _ => None,
+        }
+    }
+
+    /// Sign of the contract document of `file_type` made by the signing transaction
+    pub fn document_sign(&self, file_type: &AttachmentType) -> Option<Sign> {
+        let (deed_sign, application_sign) = match self {
+            OwnershipTransactions::SignContract(tx) => (tx.deed_sign(), tx.application_sign()),
+            OwnershipTransactions::DeprecatedSignContract(tx) => {
+                (tx.deed_sign(), tx.application_sign())
+            }
+            _ => return None,
+        };
+        match file_type {
+            AttachmentType::Deed => Some(deed_sign),
+            AttachmentType::Application => Some(application_sign),
+            _ => None,
+        }
+    }
+}
+
+// Transactions of the original layouts are decoded to read the history. The node
+// doesn't send them anymore and doesn't execute new ones
+impl Transaction for DeprecatedAttachContractOtherFile {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.requestor().is_valid()
+            && self.file().upgrade().verify().is_ok()
+            && self.file().metadata().file_type() == AttachmentType::Other as u8
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedAttachContractMainFile {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.file().upgrade().verify().is_ok()
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedApproveContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.attachment()
+            .map(|attach| attach.upgrade().verify())
+            .transpose()
+            .is_ok()
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedRejectContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.attachment()
+            .map(|attach| attach.upgrade().verify())
+            .transpose()
+            .is_ok()
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedSignContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+// The plain token and OID stay in the blocks of the original transactions
+impl Transaction for DeprecatedMemberToken {
+    fn verify(&self, _certificates: &HashMap<PublicKey, Certificate>) -> bool {
+        true
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedOpenLot {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.lot().verify().is_ok()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedCloseLot {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self._type() == TxType::CloseLot as u8
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedExtendLotPeriod {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        true
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedAddBid {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        let type_valid = self._type() == TxType::AddBid as u8;
+
+        type_valid && self.requestor().is_valid()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedAcquireLot {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        // TODO
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedPurchaseOffer {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.buyer() != self.rightholder()
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedRefuseContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        // TODO
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedConfirmContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        // TODO
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedDeleteContractFiles {
+    fn verify(&self, _certificates: &HashMap<PublicKey, Certificate>) -> bool {
+        // TODO
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedUpdateContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        // TODO
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedTaxRequest {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedContractConfirmCreate {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+impl Transaction for DeprecatedContractUnconfirmCreate {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        true
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, _fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        Err(Error::unexpected_tx_type(tx_hash))?
+    }
+}
+
+// impl Transaction for AddAttachmentSign {
+//     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+//         self.requestor().is_valid() && self.sign().verify().is_ok()
+//     }
+//
+//     fn participants(&self) -> Vec<PublicKey> {
+//         self.share()
+//     }
+//
+//     fn execute(&self, fork: &mut Fork, _hash: &Hash, _: &PublicKey) -> ExecutionResult {
+//         let mut schema = Schema::new(fork);
+//         let doc_tx_hash = self.doc_tx_hash();
+//         let uid = &self.requestor().id();
+//
+//         if !schema.attachments(uid).contains(doc_tx_hash) {
+//             Error::no_attachment(doc_tx_hash).ok()?
+//         }
+//
+//         let attachment: Attachment = {
+//             let doc_tx = get_private_tx(&schema, doc_tx_hash)?;
+//             match doc_tx {
+//                 // OwnershipTransactions::AttachFile(tx) if tx.requestor().id() == *uid => {
+//                 //     Ok(tx.file())
+//                 // }
+//                 OwnershipTransactions::AttachContractFile(tx) => Ok(tx.file()),
+//                 _ => Error::unexpected_tx_type(doc_tx_hash).ok(),
+//             }
+//         }?;
+//         let doc_data = attachment.data();
+//         let sign = self.sign();
+//         member_matches_sign(&self.requestor(), &sign)?;
+//         sign.verify_data(doc_data)?;
+//
+//         Ok(schema.add_attachment_sign(doc_tx_hash, uid, sign))
+//     }
+// }
+
+// impl Transaction for AttachFile {
+//     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+//         self.requestor().is_valid()
+//             && self.file().verify().is_ok()
+//             && !contains_diplicates(self.members())
+//             && !contains_diplicates(self.share())
+//     }
+//
+//     fn participants(&self) -> Vec<PublicKey> {
+//         self.share()
+//     }
+//
+//     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+//         let mut schema = Schema::new(fork);
+//         let data_hash = crypto::hash(self.file().data());
+//         schema.attach_file(&self.requestor().id(), tx_hash, data_hash);
+//
+//         for p in self.members().iter() {
+//             schema.attach_file(&p.id(), tx_hash, data_hash);
+//         }
+//         Ok(())
+//     }
+// }
+
+// impl Transaction for DeleteFiles {
+//     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+//         self.requestor().is_valid()
+//     }
+//
+//     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+//         let mut schema = Schema::new(fork);
 //         let uid = &self.requestor().id();
 //
 //         let docs = self.doc_tx_hashes();
@@ -1313,22 +2217,124 @@ impl Transaction for UpdateObject {
 }
 
 impl Transaction for AddParticipant {
+    /// The node name must be the node public key, otherwise it would break every share of the member
     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
-        self.member().is_valid() && verify_node_name(self.node_name()).is_ok()
+        self.member().is_valid()
+            && verify_node_name(self.node_name()).is_ok()
+            && PublicKey::from_slice(self.node_name().as_bytes()).is_some()
     }
 
     fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let member = self.member();
-        let member_id = &member.id();
         let node_name = self.node_name();
+        let key = PublicKey::from_slice(node_name.as_bytes())
+            .ok_or_else(|| Error::bad_stored_member(node_name))?;
 
-        for participant in schema.participants(member_id).iter() {
-            if participant == node_name {
-                Error::participant_already_exists(&member).ok()?
-            }
+        if schema
+            .participant_keys(&member.id())
+            .iter()
+            .any(|participant| *participant.key() == key)
+        {
+            Error::participant_already_exists(&member).ok()?
         }
-        schema.add_participant(member_id, node_name.to_string());
+        let key = ParticipantKey::new(&key, KeyType::NodeAttestation as u8, node_name);
+        schema.add_participant_key(&member, key)?;
+        Ok(())
+    }
+}
+
+impl Transaction for RegisterMember {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        let keys = self.keys();
+        self.member().is_valid()
+            && self.profile().verify().is_ok()
+            && keys.iter().all(|key| key.verify().is_ok())
+            && keys
+                .iter()
+                .enumerate()
+                .all(|(i, key)| keys[..i].iter().all(|k| k.key() != key.key()))
+    }
+
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        let member = self.member();
+        schema.register_member(&member, self.profile())?;
+        for key in self.keys() {
+            schema.add_participant_key(&member, key)?;
+        }
+        Ok(())
+    }
+}
+
+impl Transaction for UpdateMemberProfile {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.member().is_valid() && self.profile().verify().is_ok()
+    }
+
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        schema.update_member_profile(&self.member(), self.profile())?;
+        Ok(())
+    }
+}
+
+impl Transaction for AddParticipantKey {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.member().is_valid() && self.key().verify().is_ok()
+    }
+
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        schema.add_participant_key(&self.member(), self.key())?;
+        Ok(())
+    }
+}
+
+impl Transaction for RemoveParticipantKey {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.member().is_valid()
+    }
+
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        schema.remove_participant_key(&self.member(), self.key())?;
+        Ok(())
+    }
+}
+
+impl Transaction for RotateParticipantKey {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.member().is_valid() && self.old_key() != self.new_key()
+    }
+
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        schema.rotate_participant_key(&self.member(), self.old_key(), self.new_key())?;
+        Ok(())
+    }
+}
+
+impl Transaction for LinkMember {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.member().is_valid() && self.successor().is_valid() && self.member() != self.successor()
+    }
+
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        schema.link_member(&self.member(), &self.successor(), self.migrate(), tx_hash)?;
+        Ok(())
+    }
+}
+
+impl Transaction for SetContractDeadline {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        EXPIRABLE_STATUSES.contains(&self.status()) && self.days() <= MAX_DEADLINE_DAYS
+    }
+
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        schema.set_contract_deadline(self.status(), self.days());
         Ok(())
     }
 }
@@ -1341,6 +2347,12 @@ impl Transaction for OpenLot {
     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
         let lot = self.lot();
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let lot_id = &tx_hash;
         /*let time = schema.time().get().ok_or_else(|| Error::no_time_provider())?;
         if lot.is_started(time) {
@@ -1366,27 +2378,6 @@ impl Transaction for OpenLot {
         schema.add_lot(**lot_id, lot, conditions);
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for CloseLot {
@@ -1396,6 +2387,12 @@ impl Transaction for CloseLot {
 
     fn execute(&self, fork: &mut Fork, _: &Hash, _: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let lot_id = self.lot_tx_hash();
         /*
         let lot = schema.lots().get(lot_id).ok_or(Error::no_lot(lot_id))?;
@@ -1427,27 +2424,6 @@ impl Transaction for CloseLot {
         schema.remove_lot_data(lot_id);
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for EditLotStatus {
@@ -1554,15 +2530,14 @@ impl Transaction for AcquireLot {
 
     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let lot_id = self.lot_tx_hash();
         let acquirer = self.requestor();
-        // TODO not best solution for determining the right holder.
-        let lot_tx = match get_transaction(&schema, lot_id) {
-            Ok(OwnershipTransactions::OpenLot(tx)) => tx,
-            _ => Error::no_lot(lot_id).ok()?,
-        };
-        let rightholder = lot_tx.requestor();
-
         if schema.member_lots(&acquirer.id()).contains(lot_id) {
             Error::no_permissions().ok()?
         }
@@ -1571,6 +2546,8 @@ impl Transaction for AcquireLot {
             .lots()
             .get(lot_id)
             .ok_or_else(|| Error::no_lot(lot_id))?;
+        // The seller of the lot, the lot is moved to the successor of a migrated member
+        let rightholder = lot.seller();
 
         let state = schema
             .lot_states()
@@ -1598,11 +2575,12 @@ impl Transaction for AcquireLot {
                 let max_bid = state.price();
                 let mut requestor_is_the_highest_bidder = false;
                 for bid_tx_hash in schema.bid_history(lot_id).iter() {
-                    let bid_tx = match get_private_tx(&schema, &bid_tx_hash)? {
-                        OwnershipTransactions::AddBid(tx) => tx,
+                    let (bidder, bid) = match get_private_tx(&schema, &bid_tx_hash)? {
+                        OwnershipTransactions::AddBid(tx) => (tx.requestor(), tx.bid()),
+                        OwnershipTransactions::DeprecatedAddBid(tx) => (tx.requestor(), tx.bid()),
                         _ => unreachable!(),
                     };
-                    if bid_tx.requestor() == acquirer && bid_tx.bid().value() == max_bid {
+                    if bidder == acquirer && bid.value() == max_bid {
                         requestor_is_the_highest_bidder = true;
                         break;
                     }
@@ -1625,34 +2603,25 @@ impl Transaction for AcquireLot {
             .ok_or_else(|| Error::no_lot(lot_id))?;
 
         schema.set_check(tx_hash, conditions.check_buyer(&acquirer));
+        schema.set_check(
+            tx_hash,
+            conditions.check_conflicts(&schema, tx_hash, &rightholder, &acquirer)?,
+        );
         schema.check_result(tx_hash)?;
 
         for ownership in conditions.objects() {
             schema.set_published_contract(&ownership.object().id(), tx_hash);
         }
         let contract = Contract::buy(acquirer, rightholder, price, conditions);
-        schema.add_contract(tx_hash, contract);
-
-        Ok(())
-    }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
+        schema.add_contract_revision(
+            tx_hash,
+            contract.buyer(),
+            tx_hash,
+            contract.price(),
+            contract.conditions(),
+            schema.time().get(),
+        );
+        schema.add_contract(tx_hash, contract, self.requestor());
 
         Ok(())
     }
@@ -1669,6 +2638,12 @@ impl Transaction for PurchaseOffer {
 
     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let buyer = self.buyer();
         let rightholder = self.rightholder();
         let conditions = self.conditions();
@@ -1683,6 +2658,10 @@ impl Transaction for PurchaseOffer {
         schema.set_check(tx_hash, conditions.check_buyer(&buyer));
         schema.set_check(tx_hash, conditions.check_seller(&rightholder));
         schema.apply_checks(tx_hash, conditions.check_rights(&schema, &rightholder)?);
+        schema.set_check(
+            tx_hash,
+            conditions.check_conflicts(&schema, tx_hash, &rightholder, &buyer)?,
+        );
         schema.check_result(tx_hash)?;
 
         for ownership in conditions.objects() {
@@ -1695,28 +2674,15 @@ impl Transaction for PurchaseOffer {
             Contract::sell(buyer, rightholder, self.price(), conditions)
         };
 
-        schema.add_contract(tx_hash, contract);
-        Ok(())
-    }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
+        schema.add_contract_revision(
+            tx_hash,
+            self.requestor(),
+            tx_hash,
+            contract.price(),
+            contract.conditions(),
+            schema.time().get(),
+        );
+        schema.add_contract(tx_hash, contract, self.requestor());
         Ok(())
     }
 }
@@ -1731,7 +2697,7 @@ impl Transaction for DraftContract {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let contract_tx_hash = self.contract_tx_hash();
 
@@ -1739,8 +2705,14 @@ impl Transaction for DraftContract {
         let contract = schema
             .contracts()
             .get(contract_tx_hash)
-            .ok_or_else(|| Error::no_contract(contract_tx_hash))?
-            .apply(Action::MakeDraft)?;
+            .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
+        let contract = schema.apply_contract_action(
+            contract_tx_hash,
+            contract,
+            Action::MakeDraft,
+            None,
+            tx_hash,
+        )?;
 
         schema
             .contract_deed(contract_tx_hash)
@@ -1749,7 +2721,13 @@ impl Transaction for DraftContract {
             .contract_application(contract_tx_hash)
             .ok_or(Error::application_file_not_found(contract_tx_hash))?;
 
-        schema.set_check(contract_tx_hash, CheckKey::DocumentsMatchCondition.ok());
+        let check = contract.conditions().check_documents(
+            &schema,
+            contract_tx_hash,
+            &contract.seller(),
+            &contract.buyer(),
+        )?;
+        schema.set_check(contract_tx_hash, check);
         schema.update_contract(contract_tx_hash, contract);
         Ok(())
     }
@@ -1765,8 +2743,14 @@ impl Transaction for RefuseContract {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
 
         let contract_tx_hash = self.contract_tx_hash();
 
@@ -1775,7 +2759,13 @@ impl Transaction for RefuseContract {
             .get(contract_tx_hash)
             .ok_or_else(|| Error::no_contract(self.contract_tx_hash()))?;
 
-        let contract = contract.apply(Action::Refuse)?;
+        let contract = schema.apply_contract_action(
+            contract_tx_hash,
+            contract,
+            Action::Refuse,
+            Some(self.requestor()),
+            tx_hash,
+        )?;
         for ownership in contract.conditions().objects() {
             schema.set_unpublished_contract(&ownership.object().id(), contract_tx_hash);
         }
@@ -1783,27 +2773,6 @@ impl Transaction for RefuseContract {
 
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for ConfirmContract {
@@ -1816,8 +2785,14 @@ impl Transaction for ConfirmContract {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::CONFIRM_CONTRACT,
+        )?;
         let requestor = self.requestor();
         let contract_id = self.contract_tx_hash();
         let contract = schema
@@ -1850,41 +2825,36 @@ impl Transaction for ConfirmContract {
         let conditions = contract.conditions();
         let seller = contract.seller();
         schema.apply_checks(contract_id, conditions.check_rights(&schema, &seller)?);
+        schema.set_check(
+            contract_id,
+            conditions.check_conflicts(&schema, contract_id, &seller, &contract.buyer())?,
+        );
+        schema.set_check(
+            contract_id,
+            conditions.check_documents(&schema, contract_id, &seller, &contract.buyer())?,
+        );
         schema.check_result(contract_id)?;
 
-        let contract = contract.apply(Action::Confirm(requestor))?;
+        let contract = schema.apply_contract_action(
+            contract_id,
+            contract,
+            Action::Confirm(requestor.clone()),
+            Some(requestor),
+            tx_hash,
+        )?;
         schema.update_contract(contract_id, contract);
         // TODO lock objects if they're not locked (example PurchaseOffer)
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for AttachContractOtherFile {
     fn verify(&self, _certificates: &HashMap<PublicKey, Certificate>) -> bool {
         self.requestor().is_valid()
             && self.file().verify().is_ok()
-            && self.file().metadata().file_type() == AttachmentType::Other as u8
+            && ScanVerdict::try_from(self.scan_verdict()).is_ok()
+            && AttachmentType::try_from(self.file().metadata().file_type())
+                .map_or(false, |file_type| file_type.is_supplementary())
     }
 
     fn participants(&self) -> Vec<PublicKey> {
@@ -1893,6 +2863,12 @@ impl Transaction for AttachContractOtherFile {
 
     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let file = self.file();
 
         let contract_tx_hash = self.contract_tx_hash();
@@ -1914,7 +2890,9 @@ impl Transaction for AttachContractOtherFile {
         // schema.attach_file(&self.requestor().id(), tx_hash, data_hash);
         let file_metadata = file.metadata();
         match file_metadata.file_type().try_into()? {
-            AttachmentType::Other => {
+            AttachmentType::Other
+            | AttachmentType::PowerOfAttorney
+            | AttachmentType::ParentContract => {
                 let status = ContractStatus::try_from(contract.state())?;
                 match status {
                     ContractStatus::New => {}
@@ -1922,40 +2900,22 @@ impl Transaction for AttachContractOtherFile {
                     ContractStatus::Confirmed(_) => {}
                     ContractStatus::Signed => {}
                     x => Error::bad_contract_state(x, "attaching file to contract").ok()?,
-                }
-                schema.attach_contract_file(contract_tx_hash, tx_hash, file_metadata)
-            }
-            _ => unreachable!(),
-        }
-        // schema.update_contract(contract_tx_hash, contract);
-        Ok(())
-    }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
+                }
+                schema.set_contract_file_owner(tx_hash, &self.requestor());
+                schema.set_scan_verdict(tx_hash, self.scan_verdict());
+                schema.add_document_data(tx_hash, file.hash(), self.encrypted());
+                schema.attach_contract_file(contract_tx_hash, tx_hash, file_metadata)
+            }
+            _ => unreachable!(),
         }
-
+        // schema.update_contract(contract_tx_hash, contract);
         Ok(())
     }
 }
 
 impl Transaction for AttachContractMainFile {
     fn verify(&self, _certificates: &HashMap<PublicKey, Certificate>) -> bool {
-        self.file().verify().is_ok()
+        self.file().verify().is_ok() && ScanVerdict::try_from(self.scan_verdict()).is_ok()
     }
 
     fn participants(&self) -> Vec<PublicKey> {
@@ -1978,6 +2938,8 @@ impl Transaction for AttachContractMainFile {
         );
 
         // schema.attach_file(&self.requestor().id(), tx_hash, data_hash);
+        schema.set_scan_verdict(tx_hash, self.scan_verdict());
+        schema.add_document_data(tx_hash, file.hash(), self.encrypted());
         let file_metadata = file.metadata();
         match file_metadata.file_type().try_into()? {
             AttachmentType::Deed => {
@@ -1985,6 +2947,13 @@ impl Transaction for AttachContractMainFile {
                     let status = ContractStatus::try_from(contract.state())?;
                     Error::bad_contract_state(status, "attaching deed file to contract").ok()?;
                 };
+                schema.set_contract_file_hash(tx_hash, file.hash());
+                if self.template() != &Hash::zero() {
+                    schema.set_generated_document(tx_hash, self.template());
+                }
+                if let Some(previous) = schema.contract_deed(contract_tx_hash) {
+                    schema.add_contract_file_version(previous.tx_hash(), tx_hash);
+                }
                 schema.attach_contract_deed(contract_tx_hash, tx_hash, file_metadata)
             }
             AttachmentType::Application => {
@@ -1993,6 +2962,13 @@ impl Transaction for AttachContractMainFile {
                     Error::bad_contract_state(status, "attaching application file to contract")
                         .ok()?;
                 };
+                schema.set_contract_file_hash(tx_hash, file.hash());
+                if self.template() != &Hash::zero() {
+                    schema.set_generated_document(tx_hash, self.template());
+                }
+                if let Some(previous) = schema.contract_application(contract_tx_hash) {
+                    schema.add_contract_file_version(previous.tx_hash(), tx_hash);
+                }
                 schema.attach_contract_application(contract_tx_hash, tx_hash, file_metadata)
             }
             AttachmentType::Notification => {
@@ -2006,7 +2982,9 @@ impl Transaction for AttachContractMainFile {
                 // }
                 schema.attach_contract_notification(contract_tx_hash, tx_hash, file_metadata)
             }
-            AttachmentType::Other => {
+            AttachmentType::Other
+            | AttachmentType::PowerOfAttorney
+            | AttachmentType::ParentContract => {
                 schema.attach_contract_file(contract_tx_hash, tx_hash, file_metadata)
             }
         }
@@ -2027,6 +3005,12 @@ impl Transaction for DeleteContractFiles {
 
     fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let contract_tx_hash = self.contract_tx_hash();
         let contract = schema
             .contracts()
@@ -2061,25 +3045,78 @@ impl Transaction for DeleteContractFiles {
 
         Ok(())
     }
+}
 
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
+impl Transaction for ReplaceContractFile {
+    fn verify(&self, _certificates: &HashMap<PublicKey, Certificate>) -> bool {
+        self.requestor().is_valid()
+            && self.file().verify().is_ok()
+            && ScanVerdict::try_from(self.scan_verdict()).is_ok()
+            && AttachmentType::try_from(self.file().metadata().file_type())
+                .map_or(false, |file_type| file_type != AttachmentType::Notification)
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
+        let contract_tx_hash = self.contract_tx_hash();
+        let doc_tx_hash = self.doc_tx_hash();
+        let contract = schema
+            .contracts()
+            .get(contract_tx_hash)
+            .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
 
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
+        if !contract.is_member(&self.requestor()) {
+            Err(Error::no_permissions())?
+        }
 
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
+        if schema
+            .contract_document(contract_tx_hash, doc_tx_hash)
+            .is_none()
+        {
+            Error::no_attachment(doc_tx_hash).ok()?
         }
 
+        // Confirmations and signatures don't pass to the new version, so the contract
+        // returns to an unconfirmed Draft and the parties confirm and sign it again
+        let status = ContractStatus::try_from(contract.state())?;
+        let contract = match status {
+            ContractStatus::New => contract,
+            ContractStatus::Draft(_) | ContractStatus::Confirmed(_) => schema.apply_contract_action(
+                contract_tx_hash,
+                contract,
+                Action::MakeDraft,
+                Some(self.requestor()),
+                tx_hash,
+            )?,
+            x => Error::bad_contract_state(x, "replacing contract file").ok()?,
+        };
+
+        let file = self.file();
+        schema.replace_contract_document(
+            contract_tx_hash,
+            doc_tx_hash,
+            tx_hash,
+            file.metadata(),
+            file.hash(),
+            &self.requestor(),
+        )?;
+        schema.set_scan_verdict(tx_hash, self.scan_verdict());
+        schema.add_document_data(tx_hash, file.hash(), self.encrypted());
+        schema.set_check(
+            contract_tx_hash,
+            CheckKey::DocumentsMatchCondition.unknown(),
+        );
+        schema.update_contract(contract_tx_hash, contract);
         Ok(())
     }
 }
@@ -2104,11 +3141,18 @@ impl Transaction for ApproveContract {
             .get(contract_tx_hash)
             .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
 
-        let contract = contract.apply(Action::Approve)?;
+        let contract = schema.apply_contract_action(
+            contract_tx_hash,
+            contract,
+            Action::Approve,
+            None,
+            tx_hash,
+        )?;
 
         self.attachment()
             .map(|attach| {
                 attach.verify().map(|_| {
+                    schema.add_document_data(tx_hash, attach.hash(), false);
                     schema.attach_contract_notification(
                         contract_tx_hash,
                         tx_hash,
@@ -2121,12 +3165,95 @@ impl Transaction for ApproveContract {
         for ownership in contract.conditions().objects() {
             schema.set_unpublished_contract(&ownership.object().id(), contract_tx_hash);
         }
+        self.transfer_rights(&mut schema, &contract, tx_hash)?;
         schema.update_contract(contract_tx_hash, contract);
 
         Ok(())
     }
 }
 
+impl ApproveContract {
+    /// Applies rights obtained by the buyer to the registry
+    fn transfer_rights(
+        &self,
+        schema: &mut Schema<&mut Fork>,
+        contract: &Contract,
+        tx_hash: &Hash,
+    ) -> Result<(), Error> {
+        let conditions = contract.conditions();
+        let contract_type = ContractType::try_from(conditions.contract_type())
+            .map_err(|_| Error::internal_bad_struct("Conditions"))?;
+        if !conditions.transfers_rights() {
+            return Ok(());
+        }
+
+        let buyer = contract.buyer();
+        let seller = contract.seller();
+        for ownership in conditions.objects() {
+            let object = ownership.object();
+            let obj_id = &object.id();
+            // Unstructured ownership information has to be processed by the registry first
+            if !schema.ownership_unstructured(obj_id).is_empty() {
+                let check = CheckKey::NoUnstructuredData.err_with(&object.to_string());
+                Error::check_failed(vec![check]).ok()?
+            }
+            let mut rights = schema
+                .rightholders(obj_id)
+                .iter()
+                .collect::<HashMap<MemberIdentity, Rights>>();
+            // Rights received by the seller under a contract can be licensed further
+            let seller_rights = match rights.get(&seller) {
+                Some(seller_rights) => Some(seller_rights.clone()),
+                None if !conditions.is_expropriation() => schema
+                    .member_rights(&seller, obj_id)
+                    .into_iter()
+                    .next(),
+                None => None,
+            };
+            let seller_rights = match seller_rights {
+                Some(seller_rights) => seller_rights,
+                None => {
+                    let check = CheckKey::CanSell.err_with(&object.to_string());
+                    Error::check_failed(vec![check]).ok()?
+                }
+            };
+            // Rights start with the registration notice, otherwise with the seller's rights
+            let starting_time = self
+                .attachment()
+                .map(|attach| attach.metadata().timestamp())
+                .unwrap_or_else(|| seller_rights.starting_time());
+
+            let (buyer_rights, seller_before) = if conditions.is_expropriation() {
+                rights.remove(&seller);
+                (seller_rights.transfer_owned(starting_time), Some(seller_rights))
+            } else {
+                let buyer_rights =
+                    Rights::from_contract(&ownership, contract_type as u8, starting_time)?;
+                (buyer_rights, None)
+            };
+            // Rights from different contracts aren't merged into a single record
+            let change = if !conditions.is_expropriation() && rights.contains_key(&buyer) {
+                schema.add_contract_rights(
+                    &object,
+                    self.contract_tx_hash(),
+                    ContractRights::new(buyer.clone(), buyer_rights.clone()),
+                );
+                RightsChange::new(object.clone(), buyer_rights, true, None, seller_before)
+            } else {
+                let buyer_before = rights.insert(buyer.clone(), buyer_rights.clone());
+                RightsChange::new(object.clone(), buyer_rights, false, buyer_before, seller_before)
+            };
+            schema.add_rights_change(self.contract_tx_hash(), change);
+
+            schema.update_rights(&object, rights);
+            schema.invalidate_published_lots(obj_id);
+            schema.invalidate_published_contracts(obj_id);
+            schema.update_object_history(obj_id, Change::new(tx_hash));
+        }
+        Ok(())
+    }
+}
+
 impl Transaction for RejectContract {
     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
         self.attachment()
@@ -2151,6 +3278,7 @@ impl Transaction for RejectContract {
             self.attachment()
                 .map(|attach| {
                     attach.verify().map(|_| {
+                        schema.add_document_data(tx_hash, attach.hash(), false);
                         schema.attach_contract_notification(
                             contract_tx_hash,
                             tx_hash,
@@ -2160,7 +3288,13 @@ impl Transaction for RejectContract {
                 })
                 .transpose()?;
         };
-        let new_contract = old_contract.apply(Action::Reject)?;
+        let new_contract = schema.apply_contract_action(
+            contract_tx_hash,
+            old_contract,
+            Action::Reject,
+            None,
+            tx_hash,
+        )?;
         for ownership in new_contract.conditions().objects() {
             schema.set_unpublished_contract(&ownership.object().id(), contract_tx_hash);
         }
@@ -2169,6 +3303,81 @@ impl Transaction for RejectContract {
     }
 }
 
+impl Transaction for TerminateContract {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        TerminationReason::try_from(self.reason()).is_ok()
+            && self
+                .attachment()
+                .map(|attach| attach.verify())
+                .transpose()
+                .is_ok()
+    }
+
+    fn participants(&self) -> Vec<PublicKey> {
+        self.share()
+    }
+
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        let contract_tx_hash = self.contract_tx_hash();
+        let contract = schema
+            .contracts()
+            .get(contract_tx_hash)
+            .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
+
+        let contract = schema.apply_contract_action(
+            contract_tx_hash,
+            contract,
+            Action::Terminate,
+            None,
+            tx_hash,
+        )?;
+
+        self.attachment()
+            .map(|attach| {
+                attach.verify().map(|_| {
+                    schema.add_document_data(tx_hash, attach.hash(), false);
+                    schema.attach_contract_notification(
+                        contract_tx_hash,
+                        tx_hash,
+                        attach.metadata(),
+                    )
+                })
+            })
+            .transpose()?;
+
+        // Undo the changes the contract made to the parties' rights on approval. Rights changed
+        // after the approval and rights of contracts approved before the changes were recorded
+        // are left to the registry
+        let conditions = contract.conditions();
+        let unrestored = if !conditions.transfers_rights() {
+            Vec::new()
+        } else if schema.contract_rights_changes(contract_tx_hash).is_empty() {
+            conditions
+                .objects()
+                .into_iter()
+                .map(|ownership| ownership.object())
+                .collect()
+        } else {
+            schema.undo_contract_rights(contract_tx_hash, &contract.buyer(), &contract.seller())
+        };
+        for object in unrestored {
+            schema.add_unrestored_rights(contract_tx_hash, object);
+        }
+        if conditions.transfers_rights() {
+            for ownership in conditions.objects() {
+                let obj_id = &ownership.object().id();
+                schema.invalidate_published_lots(obj_id);
+                schema.invalidate_published_contracts(obj_id);
+                schema.update_object_history(obj_id, Change::new(tx_hash));
+            }
+        }
+        schema.update_contract(contract_tx_hash, contract);
+
+        Ok(())
+    }
+}
+
 impl Transaction for UpdateContract {
     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
         // TODO
@@ -2179,8 +3388,14 @@ impl Transaction for UpdateContract {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let contract_tx_hash = self.contract_tx_hash();
         let old_contract = schema
             .contracts()
@@ -2207,8 +3422,35 @@ impl Transaction for UpdateContract {
         );
         schema.check_result(contract_tx_hash)?;
 
+        // Contracts opened before revisions were introduced have no initial revision
+        if schema.contract_revisions(contract_tx_hash).is_empty() {
+            let initiator = schema.contract_initiator(contract_tx_hash)?;
+            schema.add_contract_revision(
+                contract_tx_hash,
+                initiator,
+                contract_tx_hash,
+                old_contract.price(),
+                old_contract.conditions(),
+                None,
+            );
+        }
+
         let price = self.price();
-        let contract = old_contract.apply(Action::Update { price, conditions })?;
+        let contract = schema.apply_contract_action(
+            contract_tx_hash,
+            old_contract,
+            Action::Update { price, conditions },
+            Some(requestor.clone()),
+            tx_hash,
+        )?;
+        schema.add_contract_revision(
+            contract_tx_hash,
+            requestor,
+            tx_hash,
+            contract.price(),
+            contract.conditions(),
+            schema.time().get(),
+        );
         schema.update_contract(contract_tx_hash, contract);
         schema.clear_contract_files(contract_tx_hash);
         schema.add_contracts_contacts_mut(
@@ -2218,27 +3460,6 @@ impl Transaction for UpdateContract {
         );
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for AddBid {
@@ -2250,6 +3471,12 @@ impl Transaction for AddBid {
 
     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let lot_id = self.lot_tx_hash();
         let lot = schema
             .lots()
@@ -2279,27 +3506,6 @@ impl Transaction for AddBid {
         schema.put_bid_tx(lot_id, tx_hash.clone());
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for PublishBids {
@@ -2343,11 +3549,12 @@ impl Transaction for PublishBids {
                 if i >= num_bids {
                     break;
                 }
-                let tx = match get_private_tx(&schema, &tx_hash)? {
-                    OwnershipTransactions::AddBid(tx) => Ok(tx),
+                let private_bid = match get_private_tx(&schema, &tx_hash)? {
+                    OwnershipTransactions::AddBid(tx) => Ok(tx.bid()),
+                    OwnershipTransactions::DeprecatedAddBid(tx) => Ok(tx.bid()),
                     _ => Error::unexpected_tx_type(tx_hash).ok(),
-                }?;
-                let private_bid = tx.bid().value();
+                }?
+                .value();
                 if bids[i] != private_bid {
                     return Err(MissedBidError(tx_hash).into_err());
                     Error::missed_bid(tx_hash).ok()?
@@ -2384,6 +3591,12 @@ impl Transaction for ExtendLotPeriod {
 
     fn execute(&self, fork: &mut Fork, _: &Hash, _: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let lot_id = self.lot_tx_hash();
         let requestor = self.requestor();
 
@@ -2425,27 +3638,6 @@ impl Transaction for ExtendLotPeriod {
         schema.update_lot(*lot_id, lot, conditions);
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for ExecuteLot {
@@ -2494,14 +3686,20 @@ impl Transaction for RegisterContract {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let contract_tx_hash = self.contract_tx_hash();
         let old_contract = schema
             .contracts()
             .get(contract_tx_hash)
             .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
-        let new_contract = old_contract.apply(Action::Register)?;
+        let new_contract = schema.apply_contract_action(
+            contract_tx_hash,
+            old_contract,
+            Action::Register,
+            None,
+            tx_hash,
+        )?;
         schema.update_contract(contract_tx_hash, new_contract);
         Ok(())
     }
@@ -2516,14 +3714,20 @@ impl Transaction for AwaitUserActionContract {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let contract_tx_hash = self.contract_tx_hash();
         let old_contract = schema
             .contracts()
             .get(contract_tx_hash)
             .ok_or_else(|| Error::no_contract(contract_tx_hash))?;
-        let new_contract = old_contract.apply(Action::AwaitUserAction)?;
+        let new_contract = schema.apply_contract_action(
+            contract_tx_hash,
+            old_contract,
+            Action::AwaitUserAction,
+            None,
+            tx_hash,
+        )?;
         schema.update_contract(contract_tx_hash, new_contract);
         Ok(())
     }
@@ -2531,7 +3735,9 @@ impl Transaction for AwaitUserActionContract {
 
 impl Transaction for SignContract {
     fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
-        true
+        self.requestor().is_valid()
+            && Sign::new_verified(self.deed_sign().data()).is_ok()
+            && Sign::new_verified(self.application_sign().data()).is_ok()
     }
 
     fn participants(&self) -> Vec<PublicKey> {
@@ -2540,6 +3746,12 @@ impl Transaction for SignContract {
 
     fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::SIGN_CONTRACT,
+        )?;
         let contract_tx_hash = self.contract_tx_hash();
         let requestor = self.requestor();
         let old_contract = schema
@@ -2551,25 +3763,32 @@ impl Transaction for SignContract {
             Error::contract_is_undefined(contract_tx_hash).ok()?;
         }
 
-        let new_contract = old_contract.apply(Action::Sign(requestor.clone()))?;
+        let new_contract = schema.apply_contract_action(
+            contract_tx_hash,
+            old_contract,
+            Action::Sign(requestor.clone()),
+            Some(requestor.clone()),
+            tx_hash,
+        )?;
 
         let deed_file = schema
             .contract_deed(contract_tx_hash)
             .ok_or(Error::deed_file_not_found(contract_tx_hash))?;
-        let deed_attachment: Attachment = schema.get_attachment(deed_file.tx_hash())?;
-        let deed_data = deed_attachment.data();
         let deed_sign = self.deed_sign();
         member_matches_sign(&requestor, &deed_sign)?;
-        deed_sign.verify_data(deed_data)?;
+        verify_signed_document(&schema, deed_file.tx_hash(), self.deed_hash(), &deed_sign)?;
 
         let application_file = schema
             .contract_application(contract_tx_hash)
             .ok_or(Error::deed_file_not_found(contract_tx_hash))?;
-        let application_attachment = schema.get_attachment(application_file.tx_hash())?;
-        let application_data = application_attachment.data();
         let application_sign = self.application_sign();
         member_matches_sign(&requestor, &application_sign)?;
-        application_sign.verify_data(application_data)?;
+        verify_signed_document(
+            &schema,
+            application_file.tx_hash(),
+            self.application_hash(),
+            &application_sign,
+        )?;
 
         // The row `let new_contract = old_contract.apply(Action::Sign(self.requestor()))?;` guarantee that requestor buyer or seller.
         match requestor {
@@ -2607,27 +3826,6 @@ impl Transaction for SignContract {
         schema.update_contract(contract_tx_hash, new_contract);
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for ContractSubmitChecks {
@@ -2639,7 +3837,7 @@ impl Transaction for ContractSubmitChecks {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let contract_tx_hash = self.contract_tx_hash();
         let mut contract = schema
@@ -2656,12 +3854,24 @@ impl Transaction for ContractSubmitChecks {
                 match status {
                     ContractStatus::Draft(_) | ContractStatus::Confirmed(_) => {
                         if self.checks().iter().any(|v| v.result().is_error()) {
-                            contract = contract.apply(Action::MakeDraft)?;
+                            contract = schema.apply_contract_action(
+                                contract_tx_hash,
+                                contract,
+                                Action::MakeDraft,
+                                None,
+                                tx_hash,
+                            )?;
                         }
                     }
                     ContractStatus::Signed => {
                         if self.checks().iter().any(|v| v.result().is_error()) {
-                            contract = contract.apply(Action::Reject)?;
+                            contract = schema.apply_contract_action(
+                                contract_tx_hash,
+                                contract,
+                                Action::Reject,
+                                None,
+                                tx_hash,
+                            )?;
                             for ownership in contract.conditions().objects() {
                                 schema.set_unpublished_contract(
                                     &ownership.object().id(),
@@ -2682,7 +3892,13 @@ impl Transaction for ContractSubmitChecks {
                         .contract_application(contract_tx_hash)
                         .ok_or(Error::application_file_not_found(contract_tx_hash))?;
 
-                    contract = contract.apply(Action::MakeDraft)?;
+                    contract = schema.apply_contract_action(
+                        contract_tx_hash,
+                        contract,
+                        Action::MakeDraft,
+                        None,
+                        tx_hash,
+                    )?;
                 }
 
                 _ => {}
@@ -2795,7 +4011,13 @@ impl Transaction for TaxRequest {
     }
 
     fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
-        let schema = Schema::new(fork);
+        let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
         let contract_tx_hash = self.contract_tx_hash();
         let contract = schema
             .contracts()
@@ -2811,27 +4033,7 @@ impl Transaction for TaxRequest {
         if !contract.is_member(&requestor) {
             Error::no_permissions().ok()?
         }
-        Ok(())
-    }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
+        schema.set_contract_payer(contract_tx_hash, &requestor);
         Ok(())
     }
 }
@@ -2906,7 +4108,7 @@ impl Transaction for TaxWithPaymentDetails {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let contract_tx_hash = self.contract_tx_hash();
         let contract = schema
@@ -2928,7 +4130,13 @@ impl Transaction for TaxWithPaymentDetails {
         schema.add_contract_payment_details(contract_tx_hash, payment_details);
 
         if contract.is_signed()? {
-            let new_contract = contract.apply(Action::ReadyForRegistering)?;
+            let new_contract = schema.apply_contract_action(
+                contract_tx_hash,
+                contract,
+                Action::ReadyForRegistering,
+                None,
+                tx_hash,
+            )?;
             schema.update_contract(contract_tx_hash, new_contract);
         }
 
@@ -2974,7 +4182,7 @@ impl Transaction for ContractUndefined {
         true
     }
 
-    fn execute(&self, fork: &mut Fork, _hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
         let contract_id = self.contract_tx_hash();
         let contract = schema
@@ -2992,7 +4200,13 @@ impl Transaction for ContractUndefined {
                 for ownership in contract.conditions().objects() {
                     schema.set_unpublished_contract(&ownership.object().id(), contract_id);
                 }
-                contract.apply(Action::Reject)?
+                schema.apply_contract_action(
+                    contract_id,
+                    contract,
+                    Action::Reject,
+                    None,
+                    tx_hash,
+                )?
             }
         };
 
@@ -3008,28 +4222,60 @@ impl Transaction for MemberToken {
 
     fn execute(&self, fork: &mut Fork, _hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
-        schema.put_member_token(
-            &self.member(),
-            self.token().to_owned(),
-            self.oid().to_owned(),
-        );
+        schema.put_member_token(&self.member(), self.fingerprint(), self.expires_at());
         Ok(())
     }
+}
 
-    fn pre_execute(
-        &self,
-        _snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let member = self.member();
-
-        let is_success = EsiaAuth::validate(&member, self.token(), self.oid())?;
+impl Transaction for AddPowerOfAttorney {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        let power_of_attorney = self.power_of_attorney();
+        self.requestor().is_valid()
+            && power_of_attorney.member() == self.requestor()
+            && power_of_attorney.verify().is_ok()
+            && ScanVerdict::try_from(self.scan_verdict()).is_ok()
+    }
 
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
+        // Representatives can't delegate their powers further
+        if self.attestation().authority() != Authority::Role as u8 {
+            Error::no_permissions().ok()?
         }
+        schema.set_scan_verdict(tx_hash, self.scan_verdict());
+        schema.add_document_data(tx_hash, self.power_of_attorney().document().hash(), false);
+        schema.add_power_of_attorney(tx_hash, self.power_of_attorney());
+        Ok(())
+    }
+}
+
+impl Transaction for RevokePowerOfAttorney {
+    fn verify(&self, _certs: &HashMap<PublicKey, Certificate>) -> bool {
+        self.requestor().is_valid()
+    }
 
+    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+        let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
+        if self.attestation().authority() != Authority::Role as u8 {
+            Error::no_permissions().ok()?
+        }
+        let attorney_tx_hash = self.attorney_tx_hash();
+        if !schema.member_has_power_of_attorney(&self.requestor().id(), attorney_tx_hash) {
+            Error::no_permissions().ok()?
+        }
+        schema.revoke_power_of_attorney(attorney_tx_hash)?;
         Ok(())
     }
 }
@@ -3043,8 +4289,14 @@ impl Transaction for ContractConfirmCreate {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.requestor(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
 
         let contract = schema
             .contracts()
@@ -3057,32 +4309,17 @@ impl Transaction for ContractConfirmCreate {
             Error::no_permissions().ok()?
         };
 
-        let contract = contract.apply(Action::Confirm(requestor_id.to_owned()))?;
+        let contract = schema.apply_contract_action(
+            self.contract_tx_hash(),
+            contract,
+            Action::Confirm(requestor_id.to_owned()),
+            Some(requestor_id.to_owned()),
+            tx_hash,
+        )?;
         schema.update_contract(self.contract_tx_hash(), contract);
 
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.requestor();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for ContractUnconfirmCreate {
@@ -3094,8 +4331,14 @@ impl Transaction for ContractUnconfirmCreate {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
+        verify_attestation(
+            &mut schema,
+            &self.member(),
+            &self.attestation(),
+            Powers::empty(),
+        )?;
 
         let contract = schema
             .contracts()
@@ -3108,32 +4351,17 @@ impl Transaction for ContractUnconfirmCreate {
             Error::no_permissions().ok()?
         };
 
-        let contract = contract.apply(Action::Unconfirm(self.member()))?;
+        let contract = schema.apply_contract_action(
+            self.contract_tx_hash(),
+            contract,
+            Action::Unconfirm(self.member()),
+            None,
+            tx_hash,
+        )?;
         schema.update_contract(self.contract_tx_hash(), contract);
 
         Ok(())
     }
-
-    fn pre_execute(
-        &self,
-        snapshot: &dyn Snapshot,
-        _hash: &Hash,
-        _executor: &PublicKey,
-    ) -> PreExecutionResult {
-        let schema = Schema::new(snapshot);
-        let member = self.member();
-        let token = schema
-            .member_token(&member)
-            .ok_or_else(|| Error::no_member_token())?;
-
-        let is_success = EsiaAuth::validate(&member, token.token(), token.oid())?;
-
-        if !is_success {
-            Error::esia_invalid_member(&member).ok()?
-        }
-
-        Ok(())
-    }
 }
 
 impl Transaction for ContractNew {
@@ -3145,7 +4373,7 @@ impl Transaction for ContractNew {
         self.share()
     }
 
-    fn execute(&self, fork: &mut Fork, _tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
+    fn execute(&self, fork: &mut Fork, tx_hash: &Hash, _executor: &PublicKey) -> ExecutionResult {
         let mut schema = Schema::new(fork);
 
         let contract = schema
@@ -3153,9 +4381,126 @@ impl Transaction for ContractNew {
             .get(self.contract_tx_hash())
             .ok_or_else(|| Error::no_contract(self.contract_tx_hash()))?;
 
-        let contract = contract.apply(Action::New)?;
+        let contract = schema.apply_contract_action(
+            self.contract_tx_hash(),
+            contract,
+            Action::New,
+            None,
+            tx_hash,
+        )?;
         schema.update_contract(self.contract_tx_hash(), contract);
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use blockp_core::crypto::{self, hash, Signature};
+    use blockp_core::messages::{MessageWriter, PROTOCOL_MAJOR_VERSION};
+    use blockp_core::storage::Entry;
+
+    use crate::data::attachment::AttachmentMetadata;
+    use crate::fixtures::*;
+
+    use super::*;
+
+    fn attestation(authority: Authority) -> EsiaAttestation {
+        let (public_key, secret_key) = crypto::gen_keypair();
+        EsiaAttestation::issue(
+            buyer(),
+            REPRESENTATIVE,
+            authority,
+            &hash(b"token"),
+            10,
+            &public_key,
+            &secret_key,
+        )
+    }
+
+    #[test]
+    fn representative_powers() {
+        let mut fork = fork();
+        Entry::new("exonum_time.time", &mut fork).set(Utc::now());
+        let mut schema = Schema::new(&mut fork);
+        schema.add_power_of_attorney(&hash(b"poa"), power_of_attorney(Powers::CONFIRM_CONTRACT));
+
+        let representative = attestation(Authority::PowerOfAttorney);
+        verify_powers(&schema, &representative, Powers::CONFIRM_CONTRACT).unwrap();
+        verify_powers(&schema, &representative, Powers::SIGN_CONTRACT).unwrap_err();
+        // Operations without powers can't be performed by a power of attorney
+        verify_powers(&schema, &representative, Powers::empty()).unwrap_err();
+
+        verify_powers(&schema, &attestation(Authority::Role), Powers::empty()).unwrap();
+        verify_powers(&schema, &attestation(Authority::Person), Powers::empty()).unwrap();
+    }
+
+    /// `ApproveContract` encoded with the original layout and message id
+    fn original_approve_contract(
+        contract_id: &ContractId,
+        file: DeprecatedAttachment,
+    ) -> RawMessage {
+        let service_id = crate::service::SERVICE_ID;
+        let mut writer = MessageWriter::new(PROTOCOL_MAJOR_VERSION, 0, service_id, 18, 49);
+        writer.write(TxType::ApproveContract as u8, 0, 1);
+        writer.write(contract_id, 1, 33);
+        writer.write(Some(file), 33, 41);
+        writer.write(Vec::<PublicKey>::new(), 41, 49);
+        RawMessage::new(writer.append_signature(&Signature::zero()))
+    }
+
+    #[test]
+    fn original_layout_decodes_by_message_id() {
+        let contract_id = hash(b"contract");
+        let metadata = AttachmentMetadata::new(
+            "notice.pdf",
+            None,
+            AttachmentType::Notification as u8,
+            Utc::now(),
+        );
+        let file = DeprecatedAttachment::new(metadata, b"notice", None);
+        let tx = OwnershipTransactions::tx_from_raw(original_approve_contract(&contract_id, file))
+            .unwrap();
+        assert!(match tx {
+            OwnershipTransactions::DeprecatedApproveContract(_) => true,
+            _ => false,
+        });
+        let (decoded_contract_id, attachment) = tx.attachment().unwrap();
+        assert_eq!(decoded_contract_id, contract_id);
+        assert_eq!(attachment.hash(), &hash(b"notice"));
+        let (_, deprecated) = tx.deprecated_attachment().unwrap();
+        assert_eq!(deprecated.data(), b"notice");
+    }
+
+    #[test]
+    fn original_bid_decodes_without_attestation() {
+        let service_id = crate::service::SERVICE_ID;
+        let mut writer = MessageWriter::new(PROTOCOL_MAJOR_VERSION, 0, service_id, 8, 49);
+        writer.write(TxType::AddBid as u8, 0, 1);
+        writer.write(buyer(), 1, 9);
+        writer.write(&hash(b"lot"), 9, 41);
+        writer.write(Bid::new(100), 41, 49);
+        let raw = RawMessage::new(writer.append_signature(&Signature::zero()));
+        let tx = match OwnershipTransactions::tx_from_raw(raw).unwrap() {
+            OwnershipTransactions::DeprecatedAddBid(tx) => tx,
+            _ => panic!("AddBid of the original layout is expected"),
+        };
+        assert_eq!(tx.requestor(), buyer());
+        assert_eq!(tx.lot_tx_hash(), &hash(b"lot"));
+        assert_eq!(tx.bid().value(), 100);
+    }
+
+    #[test]
+    fn representative_powers_without_time_service() {
+        let mut fork = fork();
+        let mut schema = Schema::new(&mut fork);
+        schema.add_power_of_attorney(&hash(b"poa"), power_of_attorney(Powers::CONFIRM_CONTRACT));
+
+        let representative = attestation(Authority::PowerOfAttorney);
+        verify_powers(&schema, &representative, Powers::CONFIRM_CONTRACT).unwrap();
+        verify_powers(&schema, &representative, Powers::SIGN_CONTRACT).unwrap_err();
+
+        schema.revoke_power_of_attorney(&hash(b"poa")).unwrap();
+        verify_powers(&schema, &representative, Powers::CONFIRM_CONTRACT).unwrap_err();
+    }
+}
@@ -1,29 +1,372 @@
-use actix_web::{dev::Payload, error, multipart};
+//! Multipart form upload.
+//!
+//! Text fields are read into memory, files are streamed into the `FileStore` and replaced
+//! with the hash of their data. Every field and the whole request are limited in size.
+
+use std::collections::HashMap;
+
+use actix_web::{dev::Payload, multipart};
 use futures::{future, Future, Stream};
 
-type BoxStream<T> = Box<dyn Stream<Item = T, Error = error::MultipartError>>;
-type BoxFuture<T> = Box<dyn Future<Item = T, Error = error::MultipartError>>;
+use blockp_core::crypto::Hash;
+
+use crate::error::{Error, Result};
+use crate::store::{FileStore, StoreWriter};
+
+type BoxStream<T> = Box<dyn Stream<Item = T, Error = Error>>;
+type BoxFuture<T> = Box<dyn Future<Item = T, Error = Error>>;
+type Multipart = multipart::Multipart<Payload>;
 type MultipartItem = multipart::MultipartItem<Payload>;
 type MultipartField = multipart::Field<Payload>;
 
-pub fn handle_multipart_item(item: MultipartItem) -> BoxStream<(String, Vec<u8>)> {
+/// Field with file data, its value is replaced with the hex hash of the stored data
+pub const FILE_FIELD: &str = "file";
+/// Field set by the node if the store didn't have the file data before the request
+pub const STORED_FIELD: &str = "file_stored";
+/// Size limit of a text field
+pub const FIELD_SIZE_LIMIT: usize = 1024 * 1024;
+/// Size limit of a file
+pub const FILE_SIZE_LIMIT: usize = 32 * 1024 * 1024;
+/// Size limit of all fields of a request
+pub const REQUEST_SIZE_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Number of leading bytes used to detect the file type
+const HEAD_LEN: usize = 64;
+
+/// Content types of files accepted for upload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    Pdf,
+    Png,
+    Jpeg,
+    Tiff,
+    Gif,
+    Cms,
+    /// Machine-readable powers of attorney
+    Xml,
+    /// Office Open XML documents, including the generated deed and application
+    Docx,
+}
+
+impl FileKind {
+    const ALL: &'static [FileKind] = &[
+        FileKind::Pdf,
+        FileKind::Png,
+        FileKind::Jpeg,
+        FileKind::Tiff,
+        FileKind::Gif,
+        FileKind::Cms,
+        FileKind::Xml,
+        FileKind::Docx,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            FileKind::Pdf => "PDF",
+            FileKind::Png => "PNG",
+            FileKind::Jpeg => "JPEG",
+            FileKind::Tiff => "TIFF",
+            FileKind::Gif => "GIF",
+            FileKind::Cms => "CMS signature",
+            FileKind::Xml => "XML",
+            FileKind::Docx => "DOCX",
+        }
+    }
+
+    fn detect(head: &[u8]) -> Option<Self> {
+        // Object identifier of the CMS SignedData content type (1.2.840.113549.1.7.2)
+        const SIGNED_DATA_OID: &[u8] = &[
+            0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02,
+        ];
+        let kind = if head.starts_with(b"%PDF-") {
+            FileKind::Pdf
+        } else if head.starts_with(b"\x89PNG\r\n\x1a\n") {
+            FileKind::Png
+        } else if head.starts_with(&[0xff, 0xd8, 0xff]) {
+            FileKind::Jpeg
+        } else if head.starts_with(b"II*\0") || head.starts_with(b"MM\0*") {
+            FileKind::Tiff
+        } else if head.starts_with(b"GIF87a") || head.starts_with(b"GIF89a") {
+            FileKind::Gif
+        } else if head.starts_with(b"-----BEGIN PKCS7-----")
+            || head.starts_with(b"-----BEGIN CMS-----")
+            || (head.first() == Some(&0x30)
+                && head
+                    .windows(SIGNED_DATA_OID.len())
+                    .any(|w| w == SIGNED_DATA_OID))
+        {
+            FileKind::Cms
+        } else if head.starts_with(b"<?xml") || head.starts_with(b"\xef\xbb\xbf<?xml") {
+            FileKind::Xml
+        } else if is_ooxml_package(head) {
+            FileKind::Docx
+        } else {
+            return None;
+        };
+        Some(kind)
+    }
+
+    fn content_types(self) -> &'static [&'static str] {
+        match self {
+            FileKind::Pdf => &["application/pdf"],
+            FileKind::Png => &["image/png"],
+            FileKind::Jpeg => &["image/jpeg", "image/pjpeg"],
+            FileKind::Tiff => &["image/tiff"],
+            FileKind::Gif => &["image/gif"],
+            FileKind::Cms => &[
+                "application/pkcs7-signature",
+                "application/x-pkcs7-signature",
+                "application/pkcs7-mime",
+                "application/x-pkcs7-mime",
+            ],
+            FileKind::Xml => &["application/xml", "text/xml"],
+            FileKind::Docx => {
+                &["application/vnd.openxmlformats-officedocument.wordprocessingml.document"]
+            }
+        }
+    }
+
+    /// Checks the type declared by the client. Generic binary type is accepted for any file,
+    /// then only the leading bytes of the data are checked, the rest of the file isn't parsed
+    fn matches(self, content_type: &str) -> bool {
+        content_type == "application/octet-stream" || self.content_types().contains(&content_type)
+    }
+}
+
+/// ZIP archive starting with a part of an Office Open XML package
+fn is_ooxml_package(head: &[u8]) -> bool {
+    const LOCAL_HEADER_LEN: usize = 30;
+    if !head.starts_with(b"PK\x03\x04") || head.len() < LOCAL_HEADER_LEN {
+        return false;
+    }
+    let name_len = u16::from_le_bytes([head[26], head[27]]) as usize;
+    let name = &head[LOCAL_HEADER_LEN..head.len().min(LOCAL_HEADER_LEN + name_len)];
+    name == b"[Content_Types].xml"
+        || name.starts_with(b"_rels/")
+        || name.starts_with(b"word/")
+        || name.starts_with(b"docProps/")
+}
+
+/// Reads the multipart form into parameters
+pub fn load_multipart(multipart: Multipart) -> BoxFuture<HashMap<String, Vec<u8>>> {
+    let upload = Upload {
+        params: HashMap::new(),
+        size: 0,
+    };
+    Box::new(
+        multipart
+            .from_err()
+            .map(fields)
+            .flatten()
+            .fold(upload, Upload::read)
+            .map(|upload| upload.params),
+    )
+}
+
+fn fields(item: MultipartItem) -> BoxStream<MultipartField> {
     match item {
-        MultipartItem::Field(field) => {
-            let name = field
-                .content_disposition()
-                .expect("No content disposition")
-                .get_name()
-                .expect("No name")
-                .to_owned();
-            Box::new(load_in_memory(name, field).into_stream())
+        MultipartItem::Field(field) => Box::new(futures::stream::once(Ok(field))),
+        MultipartItem::Nested(mp) => Box::new(mp.from_err().map(fields).flatten()),
+    }
+}
+
+struct Upload {
+    params: HashMap<String, Vec<u8>>,
+    size: usize,
+}
+
+impl Upload {
+    fn read(self, field: MultipartField) -> BoxFuture<Upload> {
+        let name = match field_name(&field) {
+            Ok(name) => name,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        if name == FILE_FIELD {
+            self.read_file(name, field)
+        } else if name == STORED_FIELD {
+            Box::new(future::err(Error::bad_multipart_field("reserved field name")))
+        } else {
+            self.read_text(name, field)
+        }
+    }
+
+    fn read_text(self, name: String, field: MultipartField) -> BoxFuture<Upload> {
+        Box::new(
+            field
+                .from_err()
+                .fold(
+                    (self, name, Vec::new()),
+                    |(mut upload, name, mut data), bytes| {
+                        if data.len() + bytes.len() > FIELD_SIZE_LIMIT {
+                            Error::too_large_param(&name, FIELD_SIZE_LIMIT).ok()?
+                        }
+                        upload.add_size(bytes.len())?;
+                        data.extend_from_slice(bytes.as_ref());
+                        Ok::<_, Error>((upload, name, data))
+                    },
+                )
+                .map(|(mut upload, name, data)| {
+                    upload.params.insert(name, data);
+                    upload
+                }),
+        )
+    }
+
+    fn read_file(self, name: String, field: MultipartField) -> BoxFuture<Upload> {
+        let content_type = {
+            let mime = field.content_type();
+            format!("{}/{}", mime.type_(), mime.subtype())
+        };
+        let writer = match FileStore::from_env().and_then(|store| store.writer()) {
+            Ok(writer) => writer,
+            Err(e) => return Box::new(future::err(e)),
+        };
+        let file = FileUpload {
+            name,
+            content_type,
+            head: Vec::with_capacity(HEAD_LEN),
+            checked: false,
+            writer,
+        };
+        Box::new(
+            field
+                .from_err()
+                .fold((self, file), |(mut upload, mut file), bytes| {
+                    upload.add_size(bytes.len())?;
+                    file.write(bytes.as_ref())?;
+                    Ok::<_, Error>((upload, file))
+                })
+                .and_then(|(mut upload, file)| {
+                    let (name, hash, stored) = file.finish()?;
+                    upload.params.insert(name, hash.to_hex().into_bytes());
+                    if stored {
+                        upload.params.insert(STORED_FIELD.to_owned(), Vec::new());
+                    }
+                    Ok(upload)
+                }),
+        )
+    }
+
+    fn add_size(&mut self, size: usize) -> Result<()> {
+        self.size += size;
+        if self.size > REQUEST_SIZE_LIMIT {
+            Error::too_large_request(REQUEST_SIZE_LIMIT).ok()?
+        }
+        Ok(())
+    }
+}
+
+fn field_name(field: &MultipartField) -> Result<String> {
+    let disposition = field
+        .content_disposition()
+        .ok_or_else(|| Error::bad_multipart_field("no content disposition"))?;
+    disposition
+        .get_name()
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| Error::bad_multipart_field("no field name"))
+}
+
+struct FileUpload {
+    name: String,
+    content_type: String,
+    head: Vec<u8>,
+    checked: bool,
+    writer: StoreWriter,
+}
+
+impl FileUpload {
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        if self.writer.size() + data.len() > FILE_SIZE_LIMIT {
+            Error::too_large_param(&self.name, FILE_SIZE_LIMIT).ok()?
+        }
+        if !self.checked {
+            let len = (HEAD_LEN - self.head.len()).min(data.len());
+            self.head.extend_from_slice(&data[..len]);
+            if self.head.len() == HEAD_LEN {
+                self.check()?;
+            }
+        }
+        self.writer.write(data)
+    }
+
+    /// Rejects the file before it's fully received if its type isn't allowed
+    fn check(&mut self) -> Result<()> {
+        match FileKind::detect(&self.head) {
+            Some(kind) if kind.matches(&self.content_type) => {
+                self.checked = true;
+                Ok(())
+            }
+            Some(_) => Error::bad_content_type(&format!(
+                "content type '{}' of '{}' doesn't match file data",
+                self.content_type, self.name
+            ))
+            .ok(),
+            None => {
+                let allowed = FileKind::ALL
+                    .iter()
+                    .map(|kind| kind.name())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                Error::bad_content_type(&format!(
+                    "file type of '{}' isn't allowed, expected one of: {}",
+                    self.name, allowed
+                ))
+                .ok()
+            }
         }
-        MultipartItem::Nested(mp) => Box::new(mp.map(handle_multipart_item).flatten()),
+    }
+
+    fn finish(mut self) -> Result<(String, Hash, bool)> {
+        if !self.checked {
+            self.check()?;
+        }
+        let (hash, stored) = self.writer.finish()?;
+        Ok((self.name, hash, stored))
     }
 }
 
-fn load_in_memory(name: String, field: MultipartField) -> BoxFuture<(String, Vec<u8>)> {
-    Box::new(field.fold((name, Vec::new()), |mut data, bytes| {
-        data.1.extend_from_slice(bytes.as_ref());
-        future::ok(data).map_err(|e| error::MultipartError::Payload(error::PayloadError::Io(e)))
-    }))
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn detects_allowed_files() {
+        assert_eq!(FileKind::detect(b"%PDF-1.7\n"), Some(FileKind::Pdf));
+        assert_eq!(
+            FileKind::detect(b"\x89PNG\r\n\x1a\n\0\0"),
+            Some(FileKind::Png)
+        );
+        assert_eq!(
+            FileKind::detect(&[0xff, 0xd8, 0xff, 0xe0]),
+            Some(FileKind::Jpeg)
+        );
+        let cms = [
+            0x30, 0x82, 0x05, 0x10, 0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07,
+            0x02,
+        ];
+        assert_eq!(FileKind::detect(&cms), Some(FileKind::Cms));
+        assert_eq!(
+            FileKind::detect(b"<?xml version=\"1.0\"?>"),
+            Some(FileKind::Xml)
+        );
+        assert_eq!(FileKind::detect(b"MZ\x90\0"), None);
+        assert_eq!(FileKind::detect(b"{\\rtf1"), None);
+        let mut docx = b"PK\x03\x04".to_vec();
+        docx.extend_from_slice(&[0; 22]);
+        docx.extend_from_slice(&[19, 0, 0, 0]);
+        docx.extend_from_slice(b"[Content_Types].xml");
+        assert_eq!(FileKind::detect(&docx), Some(FileKind::Docx));
+        let mut zip = docx[..26].to_vec();
+        zip.extend_from_slice(&[9, 0, 0, 0]);
+        zip.extend_from_slice(b"setup.exe");
+        assert_eq!(FileKind::detect(&zip), None);
+    }
+
+    #[test]
+    fn checks_declared_content_type() {
+        assert!(FileKind::Pdf.matches("application/pdf"));
+        assert!(FileKind::Pdf.matches("application/octet-stream"));
+        assert!(!FileKind::Pdf.matches("image/png"));
+        assert!(FileKind::Cms.matches("application/pkcs7-signature"));
+    }
 }
@@ -3,8 +3,12 @@ use std::str::FromStr;
 
 use chrono::Utc;
 
+use blockp_core::crypto::Hash;
+use blockp_core::encoding::serialize::FromHex;
+
 use crate::data::attachment::{Attachment, AttachmentMetadata, AttachmentType};
 use crate::error::{Error, Result};
+use crate::upload::{FILE_FIELD, STORED_FIELD};
 
 #[allow(unused)]
 pub fn utf8_str_param<'a>(name: &'_ str, params: &'a HashMap<String, Vec<u8>>) -> Result<&'a str> {
@@ -72,14 +76,6 @@ pub fn get_from_map_nullable_str<'a>(
     Ok(value)
 }
 
-#[cfg(feature = "internal_api")]
-pub fn get_from_map_nullable_slice<'a>(
-    map: &'a HashMap<String, impl AsRef<[u8]>>,
-    name: &str,
-) -> Option<&'a [u8]> {
-    map.get(name).map(|v| v.as_ref())
-}
-
 #[cfg(feature = "internal_api")]
 pub fn get_from_map_nullable_string(
     map: &HashMap<String, impl AsRef<[u8]>>,
@@ -130,10 +126,15 @@ pub fn get_attachment_from_map(map: &HashMap<String, impl AsRef<[u8]>>) -> Resul
     let name = get_str_from_map(map, "name")?;
     let description = get_from_map_nullable(map, "description")?;
     let file_type: AttachmentType = get_from_multipart_map(map, "file_type")?;
-    let file = get_slice_from_map(map, "file")?;
+    let hash = Hash::from_hex(get_str_from_map(map, FILE_FIELD)?)?;
     let sign = get_from_map_nullable(map, "sign")?;
     let meta = AttachmentMetadata::new(name, description, file_type as u8, Utc::now());
-    Ok(Attachment::new(meta, file, sign))
+    Ok(Attachment::new(meta, &hash, sign))
+}
+
+/// Checks if the file data was saved to the store by the request
+pub fn is_stored_by_request(map: &HashMap<String, impl AsRef<[u8]>>) -> bool {
+    map.contains_key(STORED_FIELD)
 }
 
 #[cfg(feature = "internal_api")]
@@ -143,16 +144,17 @@ pub fn get_attachment_nullable_from_map(
     let name = get_from_map_nullable_str(map, "name")?;
     let description = get_from_map_nullable_string(map, "description");
     let file_type: Option<AttachmentType> = get_from_map_nullable(map, "file_type")?;
-    let file = get_from_map_nullable_slice(map, "file");
+    let file = get_from_map_nullable_str(map, FILE_FIELD)?;
     let sign = get_from_map_nullable(map, "sign")?;
     match (name, file_type, file, sign) {
         (None, None, None, None) => Ok(None),
         (name, file_tpye, file, sign) => {
             let name = name.ok_or_else(|| Error::empty_param("name"))?;
             let file_type = file_tpye.ok_or_else(|| Error::empty_param("file_tpye"))?;
-            let file = file.ok_or_else(|| Error::empty_param("file"))?;
+            let file = file.ok_or_else(|| Error::empty_param(FILE_FIELD))?;
+            let hash = Hash::from_hex(file)?;
             let meta = AttachmentMetadata::new(name, description, file_type as u8, Utc::now());
-            Ok(Some(Attachment::new(meta, file, sign)))
+            Ok(Some(Attachment::new(meta, &hash, sign)))
         }
     }
 }